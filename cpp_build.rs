@@ -1,3 +1,4 @@
 fn main() {
+    #[cfg(feature = "openmp")]
     cpp_build::Config::new().compiler("g++-12").flag("-fopenmp").flag("-std=c++17").opt_level(3).build("src/openmp/cpp_module.rs");
-}
\ No newline at end of file
+}
@@ -0,0 +1,143 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Report summarizes the per-step times of a benchmark run.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub engine: String,
+    pub bodies: usize,
+    pub threads: usize,
+    pub warmup: usize,
+    /// time of every measured step in milliseconds
+    pub times: Vec<f64>,
+}
+
+/// Summary is the JSON form of a Report, with its statistics.
+#[derive(Serialize)]
+struct Summary<'a> {
+    engine: &'a str,
+    bodies: usize,
+    threads: usize,
+    warmup: usize,
+    steps: usize,
+    total_ms: f64,
+    min_ms: f64,
+    median_ms: f64,
+    mean_ms: f64,
+    stddev_ms: f64,
+    max_ms: f64,
+    interactions_per_second: f64,
+    step_ms: &'a [f64],
+}
+
+impl Report {
+    pub fn new(engine: &str, bodies: usize, threads: usize, warmup: usize, times: &[Duration]) -> Self {
+        Report {
+            engine: engine.to_string(),
+            bodies,
+            threads,
+            warmup,
+            times: times.iter().map(|x| x.as_secs_f64() * 1000.0).collect(),
+        }
+    }
+
+    pub fn total(&self) -> f64 {
+        self.times.iter().sum()
+    }
+
+    pub fn min(&self) -> f64 {
+        if self.times.is_empty() {
+            0.0
+        } else {
+            self.times.iter().cloned().fold(f64::INFINITY, f64::min)
+        }
+    }
+
+    pub fn max(&self) -> f64 {
+        self.times.iter().cloned().fold(0.0, f64::max)
+    }
+
+    pub fn median(&self) -> f64 {
+        let mut sorted = self.times.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        match sorted.len() {
+            0 => 0.0,
+            n if n % 2 == 1 => sorted[n / 2],
+            n => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+        }
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.times.is_empty() {
+            0.0
+        } else {
+            self.total() / self.times.len() as f64
+        }
+    }
+
+    /// the sample standard deviation, 0 for less than two steps.
+    pub fn stddev(&self) -> f64 {
+        let n = self.times.len();
+        if n < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let squares: f64 = self.times.iter().map(|x| (x - mean) * (x - mean)).sum();
+        (squares / (n - 1) as f64).sqrt()
+    }
+
+    /// body-interactions per second, counting n * (n - 1) interactions per step whatever the engine actually computes.
+    pub fn throughput(&self) -> f64 {
+        let interactions = self.bodies as f64 * self.bodies.saturating_sub(1) as f64;
+        if self.mean() > 0.0 {
+            interactions / (self.mean() / 1000.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// prints the report in a human readable form.
+    pub fn print(&self) {
+        println!("Duration: {} ms", self.total().round());
+        println!("Steps: {} (warmup {})", self.times.len(), self.warmup);
+        println!(
+            "Step: min {:.3} ms, median {:.3} ms, mean {:.3} ms, stddev {:.3} ms, max {:.3} ms",
+            self.min(),
+            self.median(),
+            self.mean(),
+            self.stddev(),
+            self.max()
+        );
+        println!("Throughput: {:.3e} interactions/s", self.throughput());
+    }
+
+    /// the report as a JSON object, the times are in milliseconds.
+    pub fn to_json(&self) -> String {
+        let summary = Summary {
+            engine: &self.engine,
+            bodies: self.bodies,
+            threads: self.threads,
+            warmup: self.warmup,
+            steps: self.times.len(),
+            total_ms: self.total(),
+            min_ms: self.min(),
+            median_ms: self.median(),
+            mean_ms: self.mean(),
+            stddev_ms: self.stddev(),
+            max_ms: self.max(),
+            interactions_per_second: self.throughput(),
+            step_ms: &self.times,
+        };
+        // non-finite numbers become null, so serializing never fails
+        serde_json::to_string_pretty(&summary).unwrap()
+    }
+
+    /// stores the JSON form of the report in the file at path.
+    pub fn save_json(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+}
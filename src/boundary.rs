@@ -0,0 +1,400 @@
+use std::f64::consts::PI;
+use std::fmt::{Display, Error, Formatter};
+use std::str::FromStr;
+
+use nalgebra::SVector;
+use parking_lot::Mutex;
+
+use crate::config::SimulationConfig;
+use crate::geometry::{Point, Square};
+use crate::global::Physics;
+
+/// Boundary is what happens to the bodies at the edge of the simulation space.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Boundary {
+    /// no edge at all, the quadtree root grows with the bodies.
+    Open,
+    /// the bodies bounce off the edge, keeping restitution of their normal speed.
+    Reflective,
+    /// a body leaving on one side comes back on the other one, and feels the bodies of every periodic image.
+    Periodic,
+}
+
+impl Boundary {
+    pub const ALL: [Boundary; 3] = [Boundary::Open, Boundary::Reflective, Boundary::Periodic];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Boundary::Open => "open",
+            Boundary::Reflective => "reflective",
+            Boundary::Periodic => "periodic",
+        }
+    }
+}
+
+impl Display for Boundary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Boundary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Boundary::ALL
+            .iter()
+            .find(|e| e.name() == s)
+            .cloned()
+            .ok_or_else(|| format!("{} is not a valid boundary", s))
+    }
+}
+
+/// PeriodicSum is how the forces of the periodic images are summed with the periodic boundary.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PeriodicSum {
+    /// only the nearest image of every body pulls.
+    MinimumImage,
+    /// every image pulls, the nearest one directly and the others through a tabulated Ewald correction.
+    Ewald,
+}
+
+impl PeriodicSum {
+    pub const ALL: [PeriodicSum; 2] = [PeriodicSum::MinimumImage, PeriodicSum::Ewald];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PeriodicSum::MinimumImage => "minimum-image",
+            PeriodicSum::Ewald => "ewald",
+        }
+    }
+}
+
+impl Display for PeriodicSum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for PeriodicSum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PeriodicSum::ALL
+            .iter()
+            .find(|e| e.name() == s)
+            .cloned()
+            .ok_or_else(|| format!("{} is not a valid periodic sum", s))
+    }
+}
+
+/// the number of table intervals along each half side of the box.
+const EWALD_INTERVALS: usize = 64;
+
+/// Ewald tabulates, over a quarter of the periodic box, the field and potential of a unit mass and all of its periodic images minus those of the mass alone. The correction is smooth, so that it is interpolated linearly and added to the force of the nearest image.
+pub struct Ewald {
+    /// the table intervals along each half side
+    pub(crate) n: usize,
+    /// the width and height of a table interval
+    pub(crate) step: (f64, f64),
+    /// the field x, field y and potential corrections of the point (i, j) at 3 * (i * (n + 1) + j)
+    pub(crate) values: Vec<f64>,
+}
+
+lazy_static! {
+    /// the Ewald tables are expensive and never change, so there is one per box size for the whole process.
+    static ref EWALD_TABLES : Mutex<Vec<(f64, f64, &'static Ewald)>> = Mutex::new(Vec::new());
+}
+
+impl Ewald {
+    /// the table of the box of the given width and height, computed on first use.
+    pub fn of(width: f64, height: f64) -> &'static Ewald {
+        let mut tables = EWALD_TABLES.lock();
+        if let Some(table) = tables.iter().find(|t| t.0 == width && t.1 == height) {
+            return table.2;
+        }
+        let table: &'static Ewald = Box::leak(Box::new(Ewald::new(width, height)));
+        tables.push((width, height, table));
+        table
+    }
+
+    fn new(width: f64, height: f64) -> Self {
+        let n = EWALD_INTERVALS;
+        let step = (width / 2.0 / n as f64, height / 2.0 / n as f64);
+        let mut values = Vec::with_capacity(3 * (n + 1) * (n + 1));
+        for i in 0..=n {
+            for j in 0..=n {
+                let (x, y, p) = ewald_sum(i as f64 * step.0, j as f64 * step.1, width, height);
+                values.extend_from_slice(&[x, y, p]);
+            }
+        }
+        Ewald { n, step, values }
+    }
+
+    /// the field and potential correction at the separation (dx, dy), which has to be a nearest image.
+    pub fn correction(&self, dx: f64, dy: f64) -> (f64, f64, f64) {
+        let u = dx.abs() / self.step.0;
+        let v = dy.abs() / self.step.1;
+        let i = (u as usize).min(self.n - 1);
+        let j = (v as usize).min(self.n - 1);
+        let (fu, fv) = (u - i as f64, v - j as f64);
+        let at = |i: usize, j: usize, k: usize| self.values[3 * (i * (self.n + 1) + j) + k];
+        let lerp = |k: usize| {
+            (1.0 - fu) * ((1.0 - fv) * at(i, j, k) + fv * at(i, j + 1, k))
+                + fu * ((1.0 - fv) * at(i + 1, j, k) + fv * at(i + 1, j + 1, k))
+        };
+        let x = lerp(0);
+        let y = lerp(1);
+        (if dx < 0.0 { -x } else { x }, if dy < 0.0 { -y } else { y }, lerp(2))
+    }
+}
+
+/// the field and potential of a unit mass at the origin and all of its images in the width x height lattice, at (x, y) and minus those of the mass itself, with Parry's Ewald sum for a plane of masses. The uniform background that keeps the potential finite adds a constant only.
+fn ewald_sum(x: f64, y: f64, width: f64, height: f64) -> (f64, f64, f64) {
+    let alpha = 2.0 / width.min(height);
+    let gauss = 2.0 * alpha / PI.sqrt();
+    let (mut fx, mut fy, mut p) = (0.0, 0.0, 0.0);
+    for nx in -4..=4 {
+        for ny in -4..=4 {
+            let px = x + nx as f64 * width;
+            let py = y + ny as f64 * height;
+            let s = (px * px + py * py).sqrt();
+            let (potential, field) = if nx == 0 && ny == 0 {
+                if s == 0.0 {
+                    p -= gauss;
+                    continue;
+                }
+                let erf = libm::erf(alpha * s);
+                (-erf / s, (gauss * s * (-alpha * alpha * s * s).exp() - erf) / (s * s * s))
+            } else {
+                let erfc = libm::erfc(alpha * s);
+                (erfc / s, (erfc + gauss * s * (-alpha * alpha * s * s).exp()) / (s * s * s))
+            };
+            p += potential;
+            fx += px * field;
+            fy += py * field;
+        }
+    }
+    let area = width * height;
+    let kx_max = (4.0 * width / width.min(height)).ceil() as i32 + 1;
+    let ky_max = (4.0 * height / width.min(height)).ceil() as i32 + 1;
+    for kx in -kx_max..=kx_max {
+        for ky in -ky_max..=ky_max {
+            if kx == 0 && ky == 0 {
+                continue;
+            }
+            let k = (2.0 * PI * kx as f64 / width, 2.0 * PI * ky as f64 / height);
+            let norm = (k.0 * k.0 + k.1 * k.1).sqrt();
+            let c = 2.0 * PI / area * libm::erfc(norm / (2.0 * alpha)) / norm;
+            let phase = k.0 * x + k.1 * y;
+            p += c * phase.cos();
+            fx += c * k.0 * phase.sin();
+            fy += c * k.1 * phase.sin();
+        }
+    }
+    p -= 2.0 * PI.sqrt() / (alpha * area);
+    (fx, fy, p)
+}
+
+/// the distance, in units of the split scale, beyond which the short range forces are left to the mesh too. They are below 0.6% of the Newtonian ones there.
+const CUTOFF: f64 = 5.0;
+
+/// the number of steps of the table of the short range forces.
+const STEPS: usize = 4096;
+
+/// erfc(u) + 2u / sqrt(pi) exp(-u^2), the fraction of the force kept by the force split at r = 2u r_s.
+fn short_force(u: f64) -> f64 {
+    libm::erfc(u) + 2.0 * u / PI.sqrt() * (-u * u).exp()
+}
+
+lazy_static! {
+    /// short_force at evenly spaced u^2 up to the cutoff, interpolated linearly since it is needed for every interaction of the tree_pm engine.
+    static ref SHORT_FORCE: Vec<f64> = (0..=STEPS).map(|k| short_force((k as f64 / STEPS as f64).sqrt() * CUTOFF / 2.0)).collect();
+}
+
+/// Space is the simulation space the engines work in: the physical constants, the region of the boundary and, with Ewald summation, the correction table.
+///
+/// The geometric methods work in D dimensions, for the D of the bodies they are given. In 3D the region is extended from 0 to depth along z, there is no Ewald summation in 3D.
+#[derive(Copy, Clone)]
+pub struct Space {
+    pub physics: Physics,
+    /// the domain, the walls of the reflective boundary and the box of the periodic one
+    pub region: Square,
+    /// the extent of the domain along z, unused in 2D
+    pub depth: f64,
+    /// 2 or 3
+    pub dimensions: usize,
+    /// the scale r_s of the force split of the tree_pm engine: with it only the short range part of the forces is kept, and the periodic images are left to the mesh
+    pub split: Option<f64>,
+    ewald: Option<&'static Ewald>,
+}
+
+impl Space {
+    pub fn new(config: &SimulationConfig) -> Self {
+        Space {
+            depth: config.domain_depth,
+            dimensions: config.dimensions,
+            ..Space::of(config.physics, config.boundary())
+        }
+    }
+
+    /// a 2D space.
+    pub fn of(physics: Physics, region: Square) -> Self {
+        let size = region.0 - region.1;
+        let ewald = match (physics.boundary, physics.periodic_sum) {
+            (Boundary::Periodic, PeriodicSum::Ewald) => Some(Ewald::of(size.x, size.y)),
+            _ => None,
+        };
+        Space {
+            physics,
+            region,
+            depth: 0.0,
+            dimensions: 2,
+            split: None,
+            ewald,
+        }
+    }
+
+    /// the space keeping only the short range forces of the split at the scale r_s, without the Ewald correction.
+    pub fn short_range(&self, scale: f64) -> Self {
+        Space {
+            split: Some(scale),
+            ewald: None,
+            ..*self
+        }
+    }
+
+    /// the distance beyond which there are no short range forces, with a force split.
+    pub fn cutoff(&self) -> Option<f64> {
+        self.split.map(|scale| CUTOFF * scale)
+    }
+
+    /// the fraction of the force at the squared distance dist kept by the force split, erfc(r / 2r_s) + r / (r_s sqrt(pi)) exp(-r^2 / 4r_s^2), and none beyond the cutoff.
+    #[inline]
+    pub fn short_force(&self, dist: f64) -> f64 {
+        match self.split {
+            None => 1.0,
+            Some(scale) => {
+                let u = dist / (4.0 * scale * scale) * STEPS as f64 / (CUTOFF * CUTOFF / 4.0);
+                let k = u as usize;
+                if k < STEPS {
+                    let t = u - k as f64;
+                    SHORT_FORCE[k] * (1.0 - t) + SHORT_FORCE[k + 1] * t
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// the Ewald table, only with periodic boundaries and Ewald summation.
+    pub fn ewald(&self) -> Option<&'static Ewald> {
+        self.ewald
+    }
+
+    /// the domain in D dimensions, the region extended from 0 to depth along z in 3D.
+    pub fn domain<const D: usize>(&self) -> Square<D> {
+        let upper = [self.region.0.x, self.region.0.y, self.depth];
+        let lower = [self.region.1.x, self.region.1.y, 0.0];
+        Square(SVector::from_fn(|i, _| upper[i]), SVector::from_fn(|i, _| lower[i]))
+    }
+
+    /// the sides of the periodic box, zero without periodic boundaries.
+    pub fn period<const D: usize>(&self) -> SVector<f64, D> {
+        if self.physics.boundary == Boundary::Periodic {
+            let domain = self.domain::<D>();
+            domain.0 - domain.1
+        } else {
+            SVector::zeros()
+        }
+    }
+
+    /// the separation of two bodies, replaced by its nearest periodic image with periodic boundaries.
+    #[inline]
+    pub fn separation<const D: usize>(&self, delta: SVector<f64, D>) -> SVector<f64, D> {
+        if self.physics.boundary != Boundary::Periodic {
+            return delta;
+        }
+        delta.zip_map(&self.period::<D>(), |d, w| d - w * (d / w).round())
+    }
+
+    /// the acceleration a unit mass gives a body at the separation delta = body - mass, with dist its squared length, softened and with the Ewald correction of the periodic images. It points away from the mass, the body is pulled by minus it.
+    #[inline]
+    pub fn field<const D: usize>(&self, delta: SVector<f64, D>, dist: f64) -> SVector<f64, D> {
+        let mut res = delta * (self.physics.force(dist) * self.short_force(dist));
+        if let Some(ewald) = self.ewald {
+            let (x, y, _) = ewald.correction(delta[0], delta[1]);
+            res[0] += self.physics.g * x;
+            res[1] += self.physics.g * y;
+        }
+        res
+    }
+
+    /// the potential energy of two unit masses at the separation delta, softened and with the Ewald correction of the periodic images.
+    #[inline]
+    pub fn potential<const D: usize>(&self, delta: SVector<f64, D>, dist: f64) -> f64 {
+        let mut potential = self.physics.potential(dist);
+        if let Some(scale) = self.split {
+            potential *= libm::erfc(dist.sqrt() / (2.0 * scale));
+        }
+        match self.ewald {
+            None => potential,
+            Some(ewald) => potential - self.physics.g * ewald.correction(delta[0], delta[1]).2,
+        }
+    }
+
+    /// whether every body inside the region is seen by the point through the same periodic image, which the centre of mass of the region can only stand for then. Always true without periodic boundaries.
+    pub fn sees_whole<const D: usize>(&self, region: &Square<D>, point: &Point<D>) -> bool {
+        if self.physics.boundary != Boundary::Periodic {
+            return true;
+        }
+        let period = self.period::<D>();
+        let half = (region.0 - region.1) / 2.0;
+        let delta = self.separation(region.1 + half - point.coords);
+        (0..D).all(|i| delta[i].abs() + half[i] < period[i] / 2.0)
+    }
+
+    /// whether the point is closer than twice the radius to a side of the periodic box, so that it may collide with bodies on the other side.
+    pub fn near_edge<const D: usize>(&self, point: &Point<D>) -> bool {
+        let reach = 2.0 * self.physics.radius;
+        let domain = self.domain::<D>();
+        self.physics.boundary == Boundary::Periodic
+            && (0..D).any(|i| point.coords[i] - domain.1[i] < reach || domain.0[i] - point.coords[i] < reach)
+    }
+
+    /// whether the point, or any of its 3^D - 1 periodic images around the box, is within a distance of radius from the region.
+    pub fn touch<const D: usize>(&self, region: &Square<D>, point: &Point<D>, radius: f64) -> bool {
+        if self.physics.boundary != Boundary::Periodic {
+            return region.touch(point, radius);
+        }
+        let period = self.period::<D>();
+        (0..3_usize.pow(D as u32)).any(|k| {
+            let shift = SVector::<f64, D>::from_fn(|i, _| ((k / 3_usize.pow(i as u32)) % 3) as f64 - 1.0);
+            let image = Point::new(point.coords + shift.component_mul(&period), point.mass);
+            region.touch(&image, radius)
+        })
+    }
+
+    /// the root region of a tree over the points. With open boundaries the region doubles, away from the domain, until every point fits in, so that the domain stays one of the tree nodes.
+    pub fn root<const D: usize, I: IntoIterator<Item = SVector<f64, D>>>(&self, points: I) -> Square<D> {
+        let mut region = self.domain::<D>();
+        if self.physics.boundary != Boundary::Open {
+            return region;
+        }
+        let radius = self.physics.radius;
+        for coords in points.into_iter().filter(|p| p.iter().all(|x| x.is_finite())) {
+            let point = Point::new(coords, 0.0);
+            while !region.contains(&point, radius) {
+                let size = region.0 - region.1;
+                for axis in 0..D {
+                    if coords[axis] - radius <= region.1[axis] {
+                        region.1[axis] -= size[axis];
+                    } else {
+                        region.0[axis] += size[axis];
+                    }
+                }
+            }
+        }
+        region
+    }
+}
@@ -1,79 +1,78 @@
-use rand::Rng;
-use sdl2::event::Event;
-use sdl2::pixels::Color;
-
 use seq_module::*;
 
+use crate::boundary::Space;
+use crate::config::SimulationConfig;
+use crate::engine::Engine;
 use crate::geometry::SimpleBody;
-use crate::global::*;
 
 mod seq_module;
 
-fn init_universe() -> Vec<SimpleBody> {
-    let mut universe = Vec::new();
-    let mut rng = rand::thread_rng();
-    let real_width = *WIDTH / *SCALE_FACTOR;
-    let real_height = *HEIGHT / *SCALE_FACTOR;
-    for _ in 0..*SIZE {
-        universe.push(SimpleBody {
-            x: rng.gen_range(0.0, real_width),
-            y: rng.gen_range(0.0, real_height),
-            m: rng.gen_range(0.0, MASS_RANGE),
-            vx: 0.0,
-            vy: 0.0,
-            ax: 0.0,
-            ay: 0.0,
-        });
+/// BruteForceEngine computes every pairwise interaction sequentially, in O(n^2) per step, in the dimensions of the space.
+pub struct BruteForceEngine {
+    universe: Vec<SimpleBody>,
+    space: Space,
+}
+
+impl BruteForceEngine {
+    pub fn new(config: &SimulationConfig) -> Self {
+        BruteForceEngine {
+            universe: Vec::new(),
+            space: Space::new(config),
+        }
     }
-    universe
 }
 
-pub fn start_brute_force() {
-    if *BENCHMARK {
-        let mut universe = init_universe();
-        let start = std::time::SystemTime::now();
-        handle_impact(&mut universe);
-        update_state(&mut universe);
-        let end = std::time::SystemTime::now();
-        println!(
-            "Duration: {} ms",
-            end.duration_since(start).unwrap().as_millis()
-        );
-    } else {
-        let (mut event_pump, mut canvas) = crate::global::init_sdl("Brute Force");
+impl Engine for BruteForceEngine {
+    fn name(&self) -> &'static str {
+        "Brute Force"
+    }
+
+    fn init(&mut self, bodies: &[SimpleBody]) {
+        self.universe = bodies.to_vec();
+    }
+
+    fn collide(&mut self) {
+        if self.space.dimensions == 3 {
+            handle_collision::<3>(&mut self.universe, &self.space);
+        } else {
+            handle_collision::<2>(&mut self.universe, &self.space);
+        }
+    }
 
-        let mut universe = init_universe();
-        canvas.set_draw_color(Color::RGB(0, 255, 255));
-        canvas.clear();
-        canvas.present();
-        let mut i = 0;
-        let mut n = 0;
-        let mut start = std::time::SystemTime::now();
-        'running: loop {
-            n += 1;
-            canvas
-                .set_scale(*SCALE_FACTOR as f32, *SCALE_FACTOR as f32)
-                .unwrap();
-            canvas.set_draw_color(Color::RGB(255, 255, 255));
-            canvas.clear();
-            i = (i + 1) % 255;
-            canvas.set_draw_color(Color::RGB(i, 64, 255 - i));
-            let points = universe.iter().map(|x| x.to_sdl()).collect::<Vec<_>>();
-            canvas
-                .draw_points(points.as_slice())
-                .expect("unable to draw points");
-            handle_impact(&mut universe);
-            update_state(&mut universe);
-            for event in event_pump.poll_iter() {
-                match event {
-                    Event::Quit { .. } => {
-                        break 'running;
-                    }
-                    _ => {}
-                }
-            }
-            canvas.present();
-            crate::global::show_fps(&mut n, &mut start);
+    fn accelerate(&mut self) {
+        if self.space.dimensions == 3 {
+            handle_impact::<3>(&mut self.universe, &self.space);
+        } else {
+            handle_impact::<2>(&mut self.universe, &self.space);
         }
     }
+
+    fn accelerate_some(&mut self, active: &[bool]) {
+        if self.space.dimensions == 3 {
+            handle_impact_of::<3>(&mut self.universe, active, &self.space);
+        } else {
+            handle_impact_of::<2>(&mut self.universe, active, &self.space);
+        }
+    }
+
+    fn kick(&mut self, dt: f64) {
+        self.universe.iter_mut().for_each(|i| i.kick(dt));
+    }
+
+    fn drift(&mut self, dt: f64, with_acceleration: bool) {
+        self.universe.iter_mut().for_each(|i| i.drift(dt, with_acceleration));
+    }
+
+    fn confine(&mut self) {
+        let space = &self.space;
+        self.universe.iter_mut().for_each(|i| i.confine(space));
+    }
+
+    fn bodies(&self) -> Vec<SimpleBody> {
+        self.universe.clone()
+    }
+
+    fn load(&mut self, bodies: &[SimpleBody]) {
+        self.universe.copy_from_slice(bodies);
+    }
 }
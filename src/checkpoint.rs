@@ -0,0 +1,252 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use crate::config::SimulationConfig;
+use crate::geometry::SimpleBody;
+use crate::initial::{self, SeededRng};
+
+/// magic number at the start of a checkpoint file.
+pub const MAGIC: &[u8; 8] = b"NBODYCK2";
+
+/// magic number of the checkpoints of older versions, which only store 2D bodies. They can still be read.
+pub const MAGIC_2D: &[u8; 8] = b"NBODYCK1";
+
+/// Checkpoint is the full state of a run after a given step, enough to continue it bit-identically on the same engine. Two settings are the exception, as the accelerations a restart recomputes depend on more than the state: the jerk timestep criterion, whose history of accelerations is not stored, and the relative opening criterion, which opens the trees by the stored accelerations instead of those of the step before. A restart only continues from the same state there.
+///
+/// The file starts with the 8 byte magic number NBODYCK2 and the length of the configuration section (u64), followed by the section itself as key=value lines. Then come the step (u64), the state of the SeededRng (u64), the number of bodies n (u64) and x, y, z, vx, vy, vz, ax, ay, az, m of every body (f64). Everything is little endian. Checkpoints starting with NBODYCK1 leave out z, vz and az.
+///
+/// Only the settings that determine the physics are stored (see Checkpoint::apply), the others are left at their defaults when a checkpoint is read.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub step: usize,
+    pub rng: u64,
+    pub config: SimulationConfig,
+    pub bodies: Vec<SimpleBody>,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buffer = [0u8; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> io::Result<T> {
+    value
+        .parse::<T>()
+        .map_err(|_| invalid(format!("{} is not a valid value of {}", value, key)))
+}
+
+impl Checkpoint {
+    /// the state of a fresh run at step 0: the initial conditions of the configuration, and the generator they were drawn from.
+    pub fn start(config: &SimulationConfig) -> io::Result<Self> {
+        let mut rng = SeededRng::new(config.seed);
+        let bodies = initial::bodies_with(config, &mut rng)?;
+        Ok(Checkpoint {
+            step: 0,
+            rng: rng.state(),
+            config: config.clone(),
+            bodies,
+        })
+    }
+
+    /// copies the settings stored in the checkpoint (engine, dimensions, domain, seed, threads, fmm and mesh settings, physics, integrator and timesteps) into config, so that the run continues as it was started.
+    pub fn apply(&self, config: &mut SimulationConfig) {
+        config.engine = self.config.engine;
+        config.dimensions = self.config.dimensions;
+        config.domain_width = self.config.domain_width;
+        config.domain_height = self.config.domain_height;
+        config.domain_depth = self.config.domain_depth;
+        config.seed = self.config.seed;
+        config.threads = self.config.threads;
+        config.fmm_order = self.config.fmm_order;
+        config.leaf_size = self.config.leaf_size;
+        config.mesh_size = self.config.mesh_size;
+        config.physics = self.config.physics;
+        config.integrator = self.config.integrator;
+        config.timestep = self.config.timestep;
+        config.criterion = self.config.criterion;
+        config.eta = self.config.eta;
+        config.timestep_levels = self.config.timestep_levels;
+        config.size = self.bodies.len();
+    }
+
+    fn write_config<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let c = &self.config;
+        let mut section = Vec::new();
+        writeln!(section, "engine={}", c.engine)?;
+        writeln!(section, "dimensions={}", c.dimensions)?;
+        writeln!(section, "domain_width={}", c.domain_width)?;
+        writeln!(section, "domain_height={}", c.domain_height)?;
+        writeln!(section, "domain_depth={}", c.domain_depth)?;
+        writeln!(section, "seed={}", c.seed)?;
+        writeln!(section, "threads={}", c.threads)?;
+        writeln!(section, "fmm_order={}", c.fmm_order)?;
+        writeln!(section, "leaf_size={}", c.leaf_size)?;
+        writeln!(section, "mesh_size={}", c.mesh_size)?;
+        writeln!(section, "g={}", c.physics.g)?;
+        writeln!(section, "dt={}", c.physics.dt)?;
+        writeln!(section, "radius={}", c.physics.radius)?;
+        writeln!(section, "mass_range={}", c.physics.mass_range)?;
+        writeln!(section, "min_size={}", c.physics.min_size)?;
+        writeln!(section, "opening={}", c.physics.opening)?;
+        writeln!(section, "dist_scale_limit={}", c.physics.dist_scale_limit)?;
+        writeln!(section, "theta={}", c.physics.theta)?;
+        writeln!(section, "force_tolerance={}", c.physics.force_tolerance)?;
+        writeln!(section, "expansion={}", c.physics.expansion)?;
+        writeln!(section, "softening={}", c.physics.softening)?;
+        writeln!(section, "softening_length={}", c.physics.softening_length)?;
+        writeln!(section, "boundary={}", c.physics.boundary)?;
+        writeln!(section, "restitution={}", c.physics.restitution)?;
+        writeln!(section, "periodic_sum={}", c.physics.periodic_sum)?;
+        writeln!(section, "integrator={}", c.integrator)?;
+        writeln!(section, "timestep={}", c.timestep)?;
+        writeln!(section, "criterion={}", c.criterion)?;
+        writeln!(section, "eta={}", c.eta)?;
+        writeln!(section, "timestep_levels={}", c.timestep_levels)?;
+        writer.write_all(&(section.len() as u64).to_le_bytes())?;
+        writer.write_all(&section)
+    }
+
+    fn read_config<R: Read>(reader: &mut R) -> io::Result<SimulationConfig> {
+        let length = read_u64(reader)?;
+        // the section is read as it comes, so a corrupt length fails at the end of the file instead of allocating it
+        let mut section = Vec::new();
+        reader.take(length).read_to_end(&mut section)?;
+        if section.len() as u64 != length {
+            return Err(invalid(format!(
+                "the configuration section has {} of {} bytes",
+                section.len(),
+                length
+            )));
+        }
+        let section = String::from_utf8(section)
+            .map_err(|_| invalid("the configuration section is not valid utf-8".to_string()))?;
+        let mut config = SimulationConfig::default();
+        let mut canvas: (Option<f64>, Option<f64>, f64) = (None, None, config.scale);
+        for line in section.lines() {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("{} is not a key=value line", line)))?;
+            match key {
+                "engine" => config.engine = value.parse().map_err(invalid)?,
+                "dimensions" => config.dimensions = parse(key, value)?,
+                "domain_width" => config.domain_width = parse(key, value)?,
+                "domain_height" => config.domain_height = parse(key, value)?,
+                "domain_depth" => config.domain_depth = parse(key, value)?,
+                // checkpoints of older versions store the canvas the domain was derived from
+                "width" => canvas.0 = Some(parse(key, value)?),
+                "height" => canvas.1 = Some(parse(key, value)?),
+                "scale" => canvas.2 = parse(key, value)?,
+                "seed" => config.seed = parse(key, value)?,
+                "threads" => config.threads = parse(key, value)?,
+                "fmm_order" => config.fmm_order = parse(key, value)?,
+                "leaf_size" => config.leaf_size = parse(key, value)?,
+                "mesh_size" => config.mesh_size = parse(key, value)?,
+                "g" => config.physics.g = parse(key, value)?,
+                "dt" => config.physics.dt = parse(key, value)?,
+                "radius" => config.physics.radius = parse(key, value)?,
+                "mass_range" => config.physics.mass_range = parse(key, value)?,
+                "min_size" => config.physics.min_size = parse(key, value)?,
+                "opening" => config.physics.opening = value.parse().map_err(invalid)?,
+                "dist_scale_limit" => config.physics.dist_scale_limit = parse(key, value)?,
+                "theta" => config.physics.theta = parse(key, value)?,
+                "force_tolerance" => config.physics.force_tolerance = parse(key, value)?,
+                "expansion" => config.physics.expansion = value.parse().map_err(invalid)?,
+                "softening" => config.physics.softening = value.parse().map_err(invalid)?,
+                "softening_length" => config.physics.softening_length = parse(key, value)?,
+                "boundary" => config.physics.boundary = value.parse().map_err(invalid)?,
+                "restitution" => config.physics.restitution = parse(key, value)?,
+                "periodic_sum" => config.physics.periodic_sum = value.parse().map_err(invalid)?,
+                "integrator" => config.integrator = value.parse().map_err(invalid)?,
+                "timestep" => config.timestep = value.parse().map_err(invalid)?,
+                "criterion" => config.criterion = value.parse().map_err(invalid)?,
+                "eta" => config.eta = parse(key, value)?,
+                "timestep_levels" => config.timestep_levels = parse(key, value)?,
+                _ => return Err(invalid(format!("unknown key {}", key))),
+            }
+        }
+        if let Some(width) = canvas.0 {
+            config.domain_width = width / canvas.2;
+        }
+        if let Some(height) = canvas.1 {
+            config.domain_height = height / canvas.2;
+        }
+        Ok(config)
+    }
+
+    /// writes the checkpoint to the writer.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        self.write_config(&mut writer)?;
+        writer.write_all(&(self.step as u64).to_le_bytes())?;
+        writer.write_all(&self.rng.to_le_bytes())?;
+        writer.write_all(&(self.bodies.len() as u64).to_le_bytes())?;
+        for b in &self.bodies {
+            for value in [b.x, b.y, b.z, b.vx, b.vy, b.vz, b.ax, b.ay, b.az, b.m].iter() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// reads a checkpoint from the reader.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC && &magic != MAGIC_2D {
+            return Err(invalid("not a checkpoint file".to_string()));
+        }
+        let three_d = &magic == MAGIC;
+        let mut config = Self::read_config(&mut reader)?;
+        let step = read_u64(&mut reader)? as usize;
+        let rng = read_u64(&mut reader)?;
+        let count = read_u64(&mut reader)? as usize;
+        let mut bodies = Vec::new();
+        for _ in 0..count {
+            // x, y, z, vx, vy, vz, ax, ay, az and m, the old checkpoints have no z, vz and az
+            let mut values = [0.0; 10];
+            for (k, value) in values.iter_mut().enumerate() {
+                if three_d || ![2, 5, 8].contains(&k) {
+                    *value = f64::from_bits(read_u64(&mut reader)?);
+                }
+            }
+            bodies.push(SimpleBody {
+                x: values[0],
+                y: values[1],
+                z: values[2],
+                vx: values[3],
+                vy: values[4],
+                vz: values[5],
+                ax: values[6],
+                ay: values[7],
+                az: values[8],
+                m: values[9],
+            });
+        }
+        config.size = bodies.len();
+        Ok(Checkpoint {
+            step,
+            rng,
+            config,
+            bodies,
+        })
+    }
+
+    /// loads the checkpoint stored in the file at path.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// stores the checkpoint in the file at path. It is written to a temporary file next to it first, so that a crash never leaves a truncated checkpoint behind.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        self.write(BufWriter::new(File::create(&temporary)?))?;
+        fs::rename(&temporary, path)
+    }
+}
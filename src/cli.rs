@@ -0,0 +1,290 @@
+use clap::{App, Arg, ArgMatches};
+
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+use nbody::boundary::{Boundary, PeriodicSum};
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::config_file::RunFile;
+use nbody::integrator::Integrator;
+use nbody::multipole::Expansion;
+use nbody::opening::Opening;
+use nbody::softening::Softening;
+use nbody::timestep::{Criterion, Timestep};
+
+lazy_static! {
+    static ref ENGINES : Vec<&'static str> = EngineKind::ALL.iter().map(|e| e.name()).collect();
+
+    static ref INTEGRATORS : Vec<&'static str> = Integrator::ALL.iter().map(|e| e.name()).collect();
+
+    static ref TIMESTEPS : Vec<&'static str> = Timestep::ALL.iter().map(|e| e.name()).collect();
+
+    static ref CRITERIA : Vec<&'static str> = Criterion::ALL.iter().map(|e| e.name()).collect();
+
+    static ref OPENINGS : Vec<&'static str> = Opening::ALL.iter().map(|e| e.name()).collect();
+
+    static ref EXPANSIONS : Vec<&'static str> = Expansion::ALL.iter().map(|e| e.name()).collect();
+
+    static ref SOFTENINGS : Vec<&'static str> = Softening::ALL.iter().map(|e| e.name()).collect();
+
+    static ref BOUNDARIES : Vec<&'static str> = Boundary::ALL.iter().map(|e| e.name()).collect();
+
+    static ref PERIODIC_SUMS : Vec<&'static str> = PeriodicSum::ALL.iter().map(|e| e.name()).collect();
+
+    static ref MODES : Vec<&'static str> =
+        vec!["benchmark", "display", "validate"];
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("MyApp")
+        .arg(Arg::with_name("engine")
+            .short("e").value_name("ENGINE").help("render engine, required unless the configuration file selects one")
+            .possible_values(ENGINES.as_slice()))
+        .arg(Arg::with_name("config").value_name("FILE")
+            .long("config").help("read the settings from a TOML or JSON file, the flags given on the command line override them"))
+        .arg(Arg::with_name("domain_width").value_name("WIDTH")
+            .long("domain-width").help("width of the simulation space").default_value("200"))
+        .arg(Arg::with_name("domain_height").value_name("HEIGHT")
+            .long("domain-height").help("height of the simulation space").default_value("150"))
+        .arg(Arg::with_name("domain_depth").value_name("DEPTH")
+            .long("domain-depth").help("depth of the simulation space, only used in 3D").default_value("150"))
+        .arg(Arg::with_name("dimensions").value_name("D")
+            .long("dimensions").help("number of spatial dimensions, 3D runs need an engine other than openmp and the mpi ones")
+            .possible_values(&["2", "3"]).default_value("2"))
+        .arg(Arg::with_name("width")
+            .short("w").value_name("WIDTH").help("window width in pixels").default_value("800"))
+        .arg(Arg::with_name("height")
+            .short("h").value_name("HEIGHT").help("window height in pixels").default_value("600"))
+        .arg(Arg::with_name("scale")
+            .short("s").value_name("SCALE").help("pixels per unit of the simulation space when the window opens").default_value("4.0"))
+        .arg(Arg::with_name("number")
+            .short("n").value_name("NUM").help("number of bodies").default_value("2000"))
+        .arg(Arg::with_name("thread").help("thread number (for openmp/pthread), must be greater than 0")
+            .short("t").default_value("6"))
+        .arg(Arg::with_name("fmm_order").value_name("ORDER")
+            .long("fmm-order").help("order of the expansions of the fmm engine").default_value("6"))
+        .arg(Arg::with_name("leaf_size").value_name("NUM")
+            .long("leaf-size").help("largest number of bodies in a leaf of the fmm tree").default_value("16"))
+        .arg(Arg::with_name("mesh_size").value_name("NUM")
+            .long("mesh-size").help("number of cells along the longest side of the domain in the mesh of the pm engines").default_value("64"))
+        .arg(Arg::with_name("g").value_name("G")
+            .long("g").help("gravitational constant").default_value("5"))
+        .arg(Arg::with_name("dt").value_name("DT")
+            .long("dt").help("length of a step").default_value("0.001"))
+        .arg(Arg::with_name("radius").value_name("RADIUS")
+            .long("radius").help("radius of every body, closer bodies collide instead of attracting each other").default_value("0.5"))
+        .arg(Arg::with_name("mass_range").value_name("MASS")
+            .long("mass-range").help("generated bodies get a mass below this one").default_value("50"))
+        .arg(Arg::with_name("min_size").value_name("SIZE")
+            .long("min-size").help("quadtree nodes not larger than this one are not divided any further").default_value("10"))
+        .arg(Arg::with_name("opening").value_name("OPENING")
+            .long("opening").help("criterion deciding whether a tree node is approximated by its centre of mass").default_value("scale")
+            .possible_values(OPENINGS.as_slice()))
+        .arg(Arg::with_name("dist_scale_limit").value_name("LIMIT")
+            .long("dist-scale-limit").help("opening limit of the scale criterion, smaller values approximate fewer nodes").default_value("0.75"))
+        .arg(Arg::with_name("theta").value_name("THETA")
+            .long("theta").help("opening angle of the classic and bmax criteria, smaller values approximate fewer nodes").default_value("0.5"))
+        .arg(Arg::with_name("force_tolerance").value_name("TOLERANCE")
+            .long("force-tolerance").help("relative force error the relative criterion accepts from a node").default_value("0.0025"))
+        .arg(Arg::with_name("expansion").value_name("EXPANSION")
+            .long("expansion").help("order of the multipole expansion of the approximated tree nodes").default_value("monopole")
+            .possible_values(EXPANSIONS.as_slice()))
+        .arg(Arg::with_name("softening").value_name("SOFTENING")
+            .long("softening").help("how the force is softened at short distances").default_value("none")
+            .possible_values(SOFTENINGS.as_slice()))
+        .arg(Arg::with_name("softening_length").value_name("LENGTH")
+            .long("softening-length").help("softening length, the spline and wendland forces are newtonian beyond it").default_value("0.5"))
+        .arg(Arg::with_name("boundary").value_name("BOUNDARY")
+            .long("boundary").help("unbounded space, walls the bodies bounce off or a periodic box").default_value("reflective")
+            .possible_values(BOUNDARIES.as_slice()))
+        .arg(Arg::with_name("restitution").value_name("FRACTION")
+            .long("restitution").help("part of the normal speed kept when bouncing off a reflective boundary, in 0..1").default_value("0.5"))
+        .arg(Arg::with_name("periodic_sum").value_name("SUM")
+            .long("periodic-sum").help("forces of the nearest periodic image only, or of all of them with an Ewald sum").default_value("minimum-image")
+            .possible_values(PERIODIC_SUMS.as_slice()))
+        .arg(Arg::with_name("integrator").value_name("INTEGRATOR")
+            .long("integrator").help("time integration scheme").default_value("leapfrog")
+            .possible_values(INTEGRATORS.as_slice()))
+        .arg(Arg::with_name("timestep").value_name("TIMESTEP")
+            .long("timestep").help("fixed steps, adaptive substeps shared by all bodies or per-body block substeps").default_value("fixed")
+            .possible_values(TIMESTEPS.as_slice()))
+        .arg(Arg::with_name("criterion").value_name("CRITERION")
+            .long("timestep-criterion").help("criterion of the adaptive and block timesteps").default_value("acceleration")
+            .possible_values(CRITERIA.as_slice()))
+        .arg(Arg::with_name("eta").value_name("ETA")
+            .long("eta").help("accuracy parameter of the timestep criterion, must be greater than 0").default_value("0.02"))
+        .arg(Arg::with_name("timestep_levels").value_name("NUM")
+            .long("timestep-levels").help("number of times a step may be halved, at most 30").default_value("6"))
+        .arg(Arg::with_name("mode").value_name("MODE")
+            .short("m").help("running mode").possible_values(MODES.as_slice()).default_value("benchmark"))
+        .arg(Arg::with_name("fps").value_name("FPS_FLAG")
+            .short("f").help("whether to show fps").possible_values(&["yes", "no"]).default_value("yes"))
+        .arg(Arg::with_name("seed").value_name("SEED")
+            .long("seed").help("seed of the initial conditions (random if not given)"))
+        .arg(Arg::with_name("input").value_name("FILE")
+            .long("input").help("load the initial conditions from a csv or binary file instead of generating them"))
+        .arg(Arg::with_name("input_format").value_name("FORMAT")
+            .long("input-format").help("format of the input file (guessed from its extension by default)")
+            .possible_values(&["csv", "binary"]))
+        .arg(Arg::with_name("steps").value_name("STEPS")
+            .long("steps").help("number of measured steps of a benchmark run, or of steps of a validate run").default_value("1"))
+        .arg(Arg::with_name("warmup").value_name("STEPS")
+            .long("warmup").help("number of steps run before the measured ones").default_value("0"))
+        .arg(Arg::with_name("json").value_name("FILE")
+            .long("json").help("store the benchmark report in a JSON file"))
+        .arg(Arg::with_name("reference").value_name("ENGINE")
+            .long("reference").help("engine a validate run compares against").default_value("brute_force")
+            .possible_values(ENGINES.as_slice()))
+        .arg(Arg::with_name("position_tolerance").value_name("TOLERANCE")
+            .long("position-tolerance").help("largest position divergence a validate run accepts").default_value("1e-6"))
+        .arg(Arg::with_name("velocity_tolerance").value_name("TOLERANCE")
+            .long("velocity-tolerance").help("largest velocity divergence a validate run accepts").default_value("1e-6"))
+        .arg(Arg::with_name("snapshot").value_name("FILE")
+            .long("snapshot").help("write positions, velocities and accelerations to a csv or binary file"))
+        .arg(Arg::with_name("snapshot_format").value_name("FORMAT")
+            .long("snapshot-format").help("format of the snapshot file (guessed from its extension by default)")
+            .possible_values(&["csv", "binary"]))
+        .arg(Arg::with_name("snapshot_every").value_name("K")
+            .long("snapshot-every").help("write a snapshot every K steps").default_value("1"))
+        .arg(Arg::with_name("diagnostics").value_name("FILE")
+            .long("diagnostics").help("write the energy, momentum and centre of mass to a csv file"))
+        .arg(Arg::with_name("diagnostics_every").value_name("K")
+            .long("diagnostics-every").help("write the diagnostics every K steps").default_value("1"))
+        .arg(Arg::with_name("exact_potential_limit").value_name("NUM")
+            .long("exact-potential-limit").help("largest number of bodies the potential energy is computed exactly for").default_value("2000"))
+        .arg(Arg::with_name("checkpoint").value_name("FILE")
+            .long("checkpoint").help("store the state of the run in a checkpoint file, to continue it later with --restart"))
+        .arg(Arg::with_name("checkpoint_every").value_name("K")
+            .long("checkpoint-every").help("write a checkpoint every K steps and at the end of the run").default_value("1000"))
+        .arg(Arg::with_name("restart").value_name("FILE")
+            .long("restart").help("continue the run stored in a checkpoint file, with its engine, dimensions, domain, threads, fmm and mesh settings, physics, integrator and timesteps"))
+}
+
+/// the value of the flag if it was given on the command line. A value that does not parse is an error.
+fn given<T>(matches: &ArgMatches, name: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if matches.occurrences_of(name) == 0 {
+        return Ok(None);
+    }
+    match matches.value_of(name) {
+        Some(x) => x
+            .parse::<T>()
+            .map(Some)
+            .map_err(|e| format!("invalid value {} for {}: {}", x, name, e)),
+        None => Ok(None),
+    }
+}
+
+/// replaces target by the value of the flag if it was given.
+fn set<T>(matches: &ArgMatches, name: &str, target: &mut T) -> Result<(), String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(x) = given(matches, name)? {
+        *target = x;
+    }
+    Ok(())
+}
+
+/// replaces target by the value of the flag if it was given, for settings that are unset by default.
+fn set_some<T>(matches: &ArgMatches, name: &str, target: &mut Option<T>) -> Result<(), String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(x) = given(matches, name)? {
+        *target = Some(x);
+    }
+    Ok(())
+}
+
+/// parses the command line into a SimulationConfig: the defaults, overridden by the configuration file given with --config, overridden by the flags given on the command line. Returns None if clap already handled the command line (e.g. --help), and an error for values that do not parse or are out of range.
+pub fn parse() -> Result<Option<SimulationConfig>, String> {
+    let matches = match app().get_matches_safe() {
+        Ok(x) => x,
+        Err(m) => {
+            if crate::is_root() {
+                m.exit();
+            }
+            return Ok(None);
+        }
+    };
+    // a random seed unless one is given, it is printed so that the run can be reproduced.
+    let mut config = SimulationConfig {
+        seed: rand::random(),
+        ..SimulationConfig::default()
+    };
+    // a checkpoint brings its own engine
+    let mut has_engine = matches.occurrences_of("engine") > 0 || matches.is_present("restart");
+    if let Some(path) = matches.value_of("config") {
+        let file = RunFile::read(Path::new(path))?;
+        has_engine |= file.engine.is_some();
+        file.apply(&mut config)?;
+    }
+    if !has_engine {
+        return Err("no engine given, select one with -e or the engine key of the configuration file".to_string());
+    }
+    let m = &matches;
+    set(m, "engine", &mut config.engine)?;
+    set(m, "domain_width", &mut config.domain_width)?;
+    set(m, "domain_height", &mut config.domain_height)?;
+    set(m, "domain_depth", &mut config.domain_depth)?;
+    set(m, "dimensions", &mut config.dimensions)?;
+    set(m, "width", &mut config.width)?;
+    set(m, "height", &mut config.height)?;
+    set(m, "scale", &mut config.scale)?;
+    set(m, "number", &mut config.size)?;
+    set(m, "thread", &mut config.threads)?;
+    set(m, "fmm_order", &mut config.fmm_order)?;
+    set(m, "leaf_size", &mut config.leaf_size)?;
+    set(m, "mesh_size", &mut config.mesh_size)?;
+    set(m, "g", &mut config.physics.g)?;
+    set(m, "dt", &mut config.physics.dt)?;
+    set(m, "radius", &mut config.physics.radius)?;
+    set(m, "mass_range", &mut config.physics.mass_range)?;
+    set(m, "min_size", &mut config.physics.min_size)?;
+    set(m, "opening", &mut config.physics.opening)?;
+    set(m, "dist_scale_limit", &mut config.physics.dist_scale_limit)?;
+    set(m, "theta", &mut config.physics.theta)?;
+    set(m, "force_tolerance", &mut config.physics.force_tolerance)?;
+    set(m, "expansion", &mut config.physics.expansion)?;
+    set(m, "softening", &mut config.physics.softening)?;
+    set(m, "softening_length", &mut config.physics.softening_length)?;
+    set(m, "boundary", &mut config.physics.boundary)?;
+    set(m, "restitution", &mut config.physics.restitution)?;
+    set(m, "periodic_sum", &mut config.physics.periodic_sum)?;
+    set(m, "integrator", &mut config.integrator)?;
+    set(m, "timestep", &mut config.timestep)?;
+    set(m, "criterion", &mut config.criterion)?;
+    set(m, "eta", &mut config.eta)?;
+    set(m, "timestep_levels", &mut config.timestep_levels)?;
+    set(m, "mode", &mut config.mode)?;
+    if let Some(fps) = given::<String>(m, "fps")? {
+        config.fps = fps == "yes";
+    }
+    set(m, "seed", &mut config.seed)?;
+    set_some(m, "input", &mut config.input)?;
+    set_some(m, "input_format", &mut config.input_format)?;
+    set(m, "steps", &mut config.steps)?;
+    set(m, "warmup", &mut config.warmup)?;
+    set_some(m, "json", &mut config.json)?;
+    set(m, "reference", &mut config.reference)?;
+    set(m, "position_tolerance", &mut config.position_tolerance)?;
+    set(m, "velocity_tolerance", &mut config.velocity_tolerance)?;
+    set_some(m, "snapshot", &mut config.snapshot)?;
+    set_some(m, "snapshot_format", &mut config.snapshot_format)?;
+    set(m, "snapshot_every", &mut config.snapshot_every)?;
+    set_some(m, "diagnostics", &mut config.diagnostics)?;
+    set(m, "diagnostics_every", &mut config.diagnostics_every)?;
+    set(m, "exact_potential_limit", &mut config.exact_potential_limit)?;
+    set_some(m, "checkpoint", &mut config.checkpoint)?;
+    set(m, "checkpoint_every", &mut config.checkpoint_every)?;
+    set_some(m, "restart", &mut config.restart)?;
+    config.check()?;
+    Ok(Some(config))
+}
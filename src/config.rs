@@ -0,0 +1,361 @@
+use std::fmt::{Display, Error, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use nalgebra::Vector2;
+
+use crate::boundary::{Boundary, PeriodicSum};
+use crate::geometry::Square;
+use crate::global::Physics;
+use crate::initial::file::FileFormat;
+use crate::integrator::Integrator;
+use crate::timestep::{Criterion, Timestep};
+
+/// EngineKind names one of the available simulation backends.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum EngineKind {
+    Tree,
+    OpenMp,
+    PThread,
+    MpiNormal,
+    MpiOpenMp,
+    BruteForce,
+    Rayon,
+    RayonTree,
+    Fmm,
+    Pm,
+    TreePm,
+}
+
+impl EngineKind {
+    /// all engines, in the order they are listed on the command line.
+    pub const ALL: [EngineKind; 11] = [
+        EngineKind::Tree,
+        EngineKind::OpenMp,
+        EngineKind::PThread,
+        EngineKind::MpiNormal,
+        EngineKind::MpiOpenMp,
+        EngineKind::BruteForce,
+        EngineKind::Rayon,
+        EngineKind::RayonTree,
+        EngineKind::Fmm,
+        EngineKind::Pm,
+        EngineKind::TreePm,
+    ];
+
+    /// the name used to select the engine on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EngineKind::Tree => "tree",
+            EngineKind::OpenMp => "openmp",
+            EngineKind::PThread => "pthread",
+            EngineKind::MpiNormal => "mpi_normal",
+            EngineKind::MpiOpenMp => "mpi_openmp",
+            EngineKind::BruteForce => "brute_force",
+            EngineKind::Rayon => "rayon",
+            EngineKind::RayonTree => "rayon_tree",
+            EngineKind::Fmm => "fmm",
+            EngineKind::Pm => "pm",
+            EngineKind::TreePm => "tree_pm",
+        }
+    }
+
+    /// whether the engine distributes the bodies over several MPI processes.
+    pub fn is_mpi(&self) -> bool {
+        matches!(self, EngineKind::MpiNormal | EngineKind::MpiOpenMp)
+    }
+
+    /// the cargo features the engine depends on.
+    pub fn required_features(&self) -> &'static [&'static str] {
+        match self {
+            EngineKind::OpenMp => &["openmp"],
+            EngineKind::MpiNormal => &["mpi"],
+            EngineKind::MpiOpenMp => &["mpi", "openmp"],
+            _ => &[],
+        }
+    }
+
+    /// whether the engine is compiled in, i.e. all the cargo features it depends on are enabled.
+    pub fn is_available(&self) -> bool {
+        !matches!(self, EngineKind::OpenMp | EngineKind::MpiOpenMp if !cfg!(feature = "openmp"))
+            && !matches!(self, EngineKind::MpiNormal | EngineKind::MpiOpenMp if !cfg!(feature = "mpi"))
+    }
+
+    /// whether the engine can simulate 3D bodies, the others only simulate 2D ones.
+    pub fn supports_3d(&self) -> bool {
+        matches!(
+            self,
+            EngineKind::Tree
+                | EngineKind::PThread
+                | EngineKind::BruteForce
+                | EngineKind::Rayon
+                | EngineKind::RayonTree
+                | EngineKind::Pm
+                | EngineKind::TreePm
+        )
+    }
+
+    /// whether the engine honours the thread number of the configuration.
+    pub fn is_threaded(&self) -> bool {
+        matches!(self, EngineKind::OpenMp | EngineKind::PThread | EngineKind::MpiOpenMp)
+    }
+}
+
+impl Display for EngineKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for EngineKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EngineKind::ALL
+            .iter()
+            .find(|e| e.name() == s)
+            .cloned()
+            .ok_or_else(|| format!("{} is not a valid engine", s))
+    }
+}
+
+impl FromStr for FileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(FileFormat::Csv),
+            "binary" => Ok(FileFormat::Binary),
+            _ => Err(format!("{} is not a valid file format", s)),
+        }
+    }
+}
+
+/// Mode selects whether a run is timed, rendered or compared against a reference engine.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Mode {
+    Benchmark,
+    Display,
+    Validate,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "benchmark" => Ok(Mode::Benchmark),
+            "display" => Ok(Mode::Display),
+            "validate" => Ok(Mode::Validate),
+            _ => Err(format!("{} is not a valid mode", s)),
+        }
+    }
+}
+
+/// SimulationConfig holds every setting of a run. It replaces the command line backed globals, so that the simulator can be embedded as a library.
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    /// the backend to run
+    pub engine: EngineKind,
+    /// 2 for a plane of bodies, 3 for a full 3D simulation
+    pub dimensions: usize,
+    /// width of the simulation space, in units of length
+    pub domain_width: f64,
+    /// height of the simulation space, in units of length
+    pub domain_height: f64,
+    /// depth of the simulation space in 3D, in units of length
+    pub domain_depth: f64,
+    /// window width in pixels
+    pub width: f64,
+    /// window height in pixels
+    pub height: f64,
+    /// number of pixels per unit of simulation space when the window opens, the view can be zoomed from there
+    pub scale: f64,
+    /// number of bodies to generate
+    pub size: usize,
+    /// seed of the initial conditions, runs with the same seed start from the same bodies
+    pub seed: u64,
+    /// file to load the initial conditions from instead of generating them
+    pub input: Option<PathBuf>,
+    /// format of the input file, guessed from its extension if not set
+    pub input_format: Option<FileFormat>,
+    /// thread number for openmp/pthread
+    pub threads: usize,
+    /// order of the expansions of the fmm engine
+    pub fmm_order: usize,
+    /// largest number of bodies in a leaf of the fmm tree
+    pub leaf_size: usize,
+    /// number of cells along the longest side of the domain in the mesh of the pm engines
+    pub mesh_size: usize,
+    /// the physical constants and the step length
+    pub physics: Physics,
+    /// the scheme every engine is advanced in time with
+    pub integrator: Integrator,
+    /// how a step is divided into substeps
+    pub timestep: Timestep,
+    /// the criterion an adaptive or block timestep is chosen by
+    pub criterion: Criterion,
+    /// accuracy parameter of the timestep criterion, smaller values give smaller timesteps
+    pub eta: f64,
+    /// number of times a step may be halved, the smallest substep is the step divided by 2^timestep_levels
+    pub timestep_levels: u32,
+    /// number of measured steps of a benchmark run, or of steps of a validate run
+    pub steps: usize,
+    /// number of steps run before the measured ones
+    pub warmup: usize,
+    /// file to store the benchmark report in as JSON
+    pub json: Option<PathBuf>,
+    /// file to write snapshots of the bodies to
+    pub snapshot: Option<PathBuf>,
+    /// format of the snapshot file, guessed from its extension if not set
+    pub snapshot_format: Option<FileFormat>,
+    /// number of steps between two snapshots
+    pub snapshot_every: usize,
+    /// file to write the energy, momentum and centre of mass of every few steps to
+    pub diagnostics: Option<PathBuf>,
+    /// number of steps between two diagnostics
+    pub diagnostics_every: usize,
+    /// largest number of bodies the potential energy is computed exactly for, a quadtree approximates it above
+    pub exact_potential_limit: usize,
+    /// file to store checkpoints in
+    pub checkpoint: Option<PathBuf>,
+    /// number of steps between two checkpoints, a checkpoint is also written at the end of the run
+    pub checkpoint_every: usize,
+    /// checkpoint to continue a previous run from
+    pub restart: Option<PathBuf>,
+    /// benchmark, display or validate
+    pub mode: Mode,
+    /// the engine a validate run compares against
+    pub reference: EngineKind,
+    /// largest distance between the positions of a body in the two engines that a validate run accepts
+    pub position_tolerance: f64,
+    /// largest distance between the velocities of a body in the two engines that a validate run accepts
+    pub velocity_tolerance: f64,
+    /// whether to print the fps in display mode
+    pub fps: bool,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            engine: EngineKind::Tree,
+            dimensions: 2,
+            domain_width: 200.0,
+            domain_height: 150.0,
+            domain_depth: 150.0,
+            width: 800.0,
+            height: 600.0,
+            scale: 4.0,
+            size: 2000,
+            seed: 0,
+            input: None,
+            input_format: None,
+            threads: 6,
+            fmm_order: 6,
+            leaf_size: 16,
+            mesh_size: 64,
+            physics: Physics::default(),
+            integrator: Integrator::Leapfrog,
+            timestep: Timestep::Fixed,
+            criterion: Criterion::Acceleration,
+            eta: 0.02,
+            timestep_levels: 6,
+            steps: 1,
+            warmup: 0,
+            json: None,
+            snapshot: None,
+            snapshot_format: None,
+            snapshot_every: 1,
+            diagnostics: None,
+            diagnostics_every: 1,
+            exact_potential_limit: 2000,
+            checkpoint: None,
+            checkpoint_every: 1000,
+            restart: None,
+            mode: Mode::Benchmark,
+            reference: EngineKind::BruteForce,
+            position_tolerance: 1e-6,
+            velocity_tolerance: 1e-6,
+            fps: true,
+        }
+    }
+}
+
+impl SimulationConfig {
+    /// fails with a message on the first setting that is out of range.
+    pub fn check(&self) -> Result<(), String> {
+        let positive = [
+            ("domain_width", self.domain_width),
+            ("domain_height", self.domain_height),
+            ("domain_depth", self.domain_depth),
+            ("width", self.width),
+            ("height", self.height),
+            ("scale", self.scale),
+            ("g", self.physics.g),
+            ("dt", self.physics.dt),
+            ("radius", self.physics.radius),
+            ("mass_range", self.physics.mass_range),
+            ("min_size", self.physics.min_size),
+            ("dist_scale_limit", self.physics.dist_scale_limit),
+            ("theta", self.physics.theta),
+            ("force_tolerance", self.physics.force_tolerance),
+            ("softening_length", self.physics.softening_length),
+            ("eta", self.eta),
+        ];
+        for (name, value) in positive.iter() {
+            if !(value.is_finite() && *value > 0.0) {
+                return Err(format!("{} must be a number greater than 0, not {}", name, value));
+            }
+        }
+        for (name, value) in [("position_tolerance", self.position_tolerance), ("velocity_tolerance", self.velocity_tolerance)].iter() {
+            if value.is_nan() || *value < 0.0 {
+                return Err(format!("{} must not be negative, not {}", name, value));
+            }
+        }
+        let counts = [
+            ("threads", self.threads),
+            ("fmm_order", self.fmm_order),
+            ("leaf_size", self.leaf_size),
+            ("mesh_size", self.mesh_size),
+            ("steps", self.steps),
+            ("snapshot_every", self.snapshot_every),
+            ("diagnostics_every", self.diagnostics_every),
+            ("checkpoint_every", self.checkpoint_every),
+        ];
+        for (name, value) in counts.iter() {
+            if *value == 0 {
+                return Err(format!("{} must be greater than 0", name));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.physics.restitution) {
+            return Err(format!("restitution must be between 0 and 1, not {}", self.physics.restitution));
+        }
+        if self.timestep_levels > 30 {
+            return Err(format!("timestep_levels must be at most 30, not {}", self.timestep_levels));
+        }
+        if self.engine == EngineKind::Fmm && self.physics.theta >= 1.0 {
+            return Err(format!("theta must be below 1 with the fmm engine, not {}", self.physics.theta));
+        }
+        if self.dimensions != 2 && self.dimensions != 3 {
+            return Err(format!("dimensions must be 2 or 3, not {}", self.dimensions));
+        }
+        let sides = [("domain_width", self.domain_width), ("domain_height", self.domain_height), ("domain_depth", self.domain_depth)];
+        for (name, value) in sides.iter().take(self.dimensions) {
+            if *value <= 2.0 * self.physics.radius {
+                return Err(format!("{} must be larger than the diameter of the bodies {}, not {}", name, 2.0 * self.physics.radius, value));
+            }
+        }
+        if self.dimensions == 3 && self.physics.boundary == Boundary::Periodic && self.physics.periodic_sum == PeriodicSum::Ewald {
+            return Err("the ewald periodic sum is only available in 2D".to_string());
+        }
+        Ok(())
+    }
+
+    /// the region bodies are kept in, in the plane of x and y. In 3D it extends from 0 to domain_depth along z.
+    pub fn boundary(&self) -> Square {
+        Square(
+            Vector2::new(self.domain_width, self.domain_height),
+            Vector2::new(0.0, 0.0),
+        )
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::config::SimulationConfig;
+
+/// RunFile is a configuration file describing a run, in TOML or JSON. Every key is optional, a missing key keeps the value the configuration already has, an unknown key is an error. Relative paths are taken relative to the directory of the file.
+///
+/// ```toml
+/// engine = "tree"
+/// mode = "benchmark"
+/// steps = 100
+///
+/// [scenario]
+/// size = 2000
+/// seed = 42
+///
+/// [physics]
+/// g = 5.0
+///
+/// [integration]
+/// integrator = "leapfrog"
+///
+/// [output]
+/// checkpoint = "run.ck"
+///
+/// [diagnostics]
+/// file = "energy.csv"
+/// every = 10
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunFile {
+    pub engine: Option<String>,
+    pub mode: Option<String>,
+    pub threads: Option<usize>,
+    pub fmm_order: Option<usize>,
+    pub leaf_size: Option<usize>,
+    pub mesh_size: Option<usize>,
+    pub steps: Option<usize>,
+    pub warmup: Option<usize>,
+    pub fps: Option<bool>,
+    pub scenario: Scenario,
+    pub physics: PhysicsSection,
+    pub integration: Integration,
+    pub output: Output,
+    pub diagnostics: DiagnosticsSection,
+    pub validate: Validate,
+}
+
+/// the initial conditions and the simulation space.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    pub size: Option<usize>,
+    pub seed: Option<u64>,
+    pub input: Option<PathBuf>,
+    pub input_format: Option<String>,
+    pub domain_width: Option<f64>,
+    pub domain_height: Option<f64>,
+    pub domain_depth: Option<f64>,
+    pub dimensions: Option<usize>,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    pub scale: Option<f64>,
+}
+
+/// the physical constants, see global::Physics.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhysicsSection {
+    pub g: Option<f64>,
+    pub dt: Option<f64>,
+    pub radius: Option<f64>,
+    pub mass_range: Option<f64>,
+    pub min_size: Option<f64>,
+    pub opening: Option<String>,
+    pub dist_scale_limit: Option<f64>,
+    pub theta: Option<f64>,
+    pub force_tolerance: Option<f64>,
+    pub expansion: Option<String>,
+    pub softening: Option<String>,
+    pub softening_length: Option<f64>,
+    pub boundary: Option<String>,
+    pub restitution: Option<f64>,
+    pub periodic_sum: Option<String>,
+}
+
+/// the integrator and the timesteps.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Integration {
+    pub integrator: Option<String>,
+    pub timestep: Option<String>,
+    pub criterion: Option<String>,
+    pub eta: Option<f64>,
+    pub levels: Option<u32>,
+}
+
+/// the files a run writes, apart from the diagnostics.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Output {
+    pub json: Option<PathBuf>,
+    pub snapshot: Option<PathBuf>,
+    pub snapshot_format: Option<String>,
+    pub snapshot_every: Option<usize>,
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_every: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiagnosticsSection {
+    pub file: Option<PathBuf>,
+    pub every: Option<usize>,
+    pub exact_potential_limit: Option<usize>,
+}
+
+/// the reference and tolerances of a validate run.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Validate {
+    pub reference: Option<String>,
+    pub position_tolerance: Option<f64>,
+    pub velocity_tolerance: Option<f64>,
+}
+
+fn set<T>(target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+/// parses the named value, if there is one, into target.
+fn set_parsed<T>(target: &mut T, key: &str, value: Option<String>) -> Result<(), String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = value {
+        *target = value.parse().map_err(|e| format!("{}: {}", key, e))?;
+    }
+    Ok(())
+}
+
+impl RunFile {
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// reads the file at path, a .json file is parsed as JSON, anything else as TOML. Relative paths in it are resolved against the directory of the file.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+        let is_json = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| x.eq_ignore_ascii_case("json"));
+        let mut file = if is_json {
+            Self::from_json(&text)
+        } else {
+            Self::from_toml(&text)
+        }
+        .map_err(|e| format!("invalid configuration file {}: {}", path.display(), e))?;
+        if let Some(base) = path.parent() {
+            file.resolve(base);
+        }
+        Ok(file)
+    }
+
+    /// makes the relative paths of the file relative to base.
+    fn resolve(&mut self, base: &Path) {
+        let paths = [
+            &mut self.scenario.input,
+            &mut self.output.json,
+            &mut self.output.snapshot,
+            &mut self.output.checkpoint,
+            &mut self.diagnostics.file,
+        ];
+        for path in paths {
+            if let Some(path) = path.as_mut().filter(|x| x.is_relative()) {
+                *path = base.join(&*path);
+            }
+        }
+    }
+
+    /// copies every setting of the file into config. Fails on a value that does not parse, the ranges are checked by SimulationConfig::check.
+    pub fn apply(self, config: &mut SimulationConfig) -> Result<(), String> {
+        set_parsed(&mut config.engine, "engine", self.engine)?;
+        set_parsed(&mut config.mode, "mode", self.mode)?;
+        set(&mut config.threads, self.threads);
+        set(&mut config.fmm_order, self.fmm_order);
+        set(&mut config.leaf_size, self.leaf_size);
+        set(&mut config.mesh_size, self.mesh_size);
+        set(&mut config.steps, self.steps);
+        set(&mut config.warmup, self.warmup);
+        set(&mut config.fps, self.fps);
+
+        let s = self.scenario;
+        set(&mut config.size, s.size);
+        set(&mut config.seed, s.seed);
+        if s.input.is_some() {
+            config.input = s.input;
+        }
+        if let Some(format) = s.input_format {
+            config.input_format = Some(format.parse().map_err(|e| format!("scenario.input_format: {}", e))?);
+        }
+        set(&mut config.domain_width, s.domain_width);
+        set(&mut config.domain_height, s.domain_height);
+        set(&mut config.domain_depth, s.domain_depth);
+        set(&mut config.dimensions, s.dimensions);
+        set(&mut config.width, s.width);
+        set(&mut config.height, s.height);
+        set(&mut config.scale, s.scale);
+
+        let p = self.physics;
+        set(&mut config.physics.g, p.g);
+        set(&mut config.physics.dt, p.dt);
+        set(&mut config.physics.radius, p.radius);
+        set(&mut config.physics.mass_range, p.mass_range);
+        set(&mut config.physics.min_size, p.min_size);
+        set_parsed(&mut config.physics.opening, "physics.opening", p.opening)?;
+        set(&mut config.physics.dist_scale_limit, p.dist_scale_limit);
+        set(&mut config.physics.theta, p.theta);
+        set(&mut config.physics.force_tolerance, p.force_tolerance);
+        set_parsed(&mut config.physics.expansion, "physics.expansion", p.expansion)?;
+        set_parsed(&mut config.physics.softening, "physics.softening", p.softening)?;
+        set(&mut config.physics.softening_length, p.softening_length);
+        set_parsed(&mut config.physics.boundary, "physics.boundary", p.boundary)?;
+        set(&mut config.physics.restitution, p.restitution);
+        set_parsed(&mut config.physics.periodic_sum, "physics.periodic_sum", p.periodic_sum)?;
+
+        let i = self.integration;
+        set_parsed(&mut config.integrator, "integration.integrator", i.integrator)?;
+        set_parsed(&mut config.timestep, "integration.timestep", i.timestep)?;
+        set_parsed(&mut config.criterion, "integration.criterion", i.criterion)?;
+        set(&mut config.eta, i.eta);
+        set(&mut config.timestep_levels, i.levels);
+
+        let o = self.output;
+        if o.json.is_some() {
+            config.json = o.json;
+        }
+        if o.snapshot.is_some() {
+            config.snapshot = o.snapshot;
+        }
+        if let Some(format) = o.snapshot_format {
+            config.snapshot_format = Some(format.parse().map_err(|e| format!("output.snapshot_format: {}", e))?);
+        }
+        set(&mut config.snapshot_every, o.snapshot_every);
+        if o.checkpoint.is_some() {
+            config.checkpoint = o.checkpoint;
+        }
+        set(&mut config.checkpoint_every, o.checkpoint_every);
+
+        let d = self.diagnostics;
+        if d.file.is_some() {
+            config.diagnostics = d.file;
+        }
+        set(&mut config.diagnostics_every, d.every);
+        set(&mut config.exact_potential_limit, d.exact_potential_limit);
+
+        let v = self.validate;
+        set_parsed(&mut config.reference, "validate.reference", v.reference)?;
+        set(&mut config.position_tolerance, v.position_tolerance);
+        set(&mut config.velocity_tolerance, v.velocity_tolerance);
+        Ok(())
+    }
+}
+
+/// the configuration described by the file at path, on top of the defaults.
+pub fn load(path: &Path) -> Result<SimulationConfig, String> {
+    let mut config = SimulationConfig::default();
+    RunFile::read(path)?.apply(&mut config)?;
+    config.check()?;
+    Ok(config)
+}
@@ -0,0 +1,186 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::boundary::Space;
+use crate::geometry::{Point, SimpleBody};
+use crate::quad_tree::tree::Tree;
+
+/// Diagnostics are the conserved quantities of a body set. Angular momentum is taken about the origin of the simulation space, angular_momentum is its z component, the only one in 2D. The z components are 0 in 2D.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct Diagnostics {
+    pub kinetic: f64,
+    pub potential: f64,
+    pub momentum_x: f64,
+    pub momentum_y: f64,
+    pub momentum_z: f64,
+    pub angular_momentum_x: f64,
+    pub angular_momentum_y: f64,
+    pub angular_momentum: f64,
+    pub center_x: f64,
+    pub center_y: f64,
+    pub center_z: f64,
+}
+
+impl Diagnostics {
+    /// measures the bodies in the given space. The potential energy is summed over all pairs for at most exact_limit bodies, above that it is approximated with a quadtree.
+    pub fn measure(bodies: &[SimpleBody], space: &Space, exact_limit: usize) -> Self {
+        let mut res = Diagnostics::default();
+        let mut mass = 0.0;
+        for b in bodies {
+            res.kinetic += 0.5 * b.m * (b.vx * b.vx + b.vy * b.vy + b.vz * b.vz);
+            res.momentum_x += b.m * b.vx;
+            res.momentum_y += b.m * b.vy;
+            res.momentum_z += b.m * b.vz;
+            res.angular_momentum_x += b.m * (b.y * b.vz - b.z * b.vy);
+            res.angular_momentum_y += b.m * (b.z * b.vx - b.x * b.vz);
+            res.angular_momentum += b.m * (b.x * b.vy - b.y * b.vx);
+            res.center_x += b.m * b.x;
+            res.center_y += b.m * b.y;
+            res.center_z += b.m * b.z;
+            mass += b.m;
+        }
+        if mass > 0.0 {
+            res.center_x /= mass;
+            res.center_y /= mass;
+            res.center_z /= mass;
+        }
+        res.potential = if bodies.len() <= exact_limit {
+            exact_potential(bodies, space)
+        } else {
+            tree_potential(bodies, space)
+        };
+        res
+    }
+
+    pub fn energy(&self) -> f64 {
+        self.kinetic + self.potential
+    }
+}
+
+/// the potential energy summed over all pairs of bodies, softened and with the periodic images like the forces.
+pub fn exact_potential(bodies: &[SimpleBody], space: &Space) -> f64 {
+    if space.dimensions == 3 {
+        exact_potential_in::<3>(bodies, space)
+    } else {
+        exact_potential_in::<2>(bodies, space)
+    }
+}
+
+fn exact_potential_in<const D: usize>(bodies: &[SimpleBody], space: &Space) -> f64 {
+    let mut potential = 0.0;
+    for i in 0..bodies.len() {
+        for j in i + 1..bodies.len() {
+            let delta = space.separation(bodies[i].position::<D>() - bodies[j].position::<D>());
+            let dist = delta.norm_squared();
+            if dist > 0.0 {
+                potential += space.potential(delta, dist) * bodies[i].m * bodies[j].m;
+            }
+        }
+    }
+    potential
+}
+
+/// the potential energy approximated with a quadtree, or an octree in 3D, as the engines approximate the forces.
+pub fn tree_potential(bodies: &[SimpleBody], space: &Space) -> f64 {
+    if space.dimensions == 3 {
+        tree_potential_in::<3>(bodies, space)
+    } else {
+        tree_potential_in::<2>(bodies, space)
+    }
+}
+
+fn tree_potential_in<const D: usize>(bodies: &[SimpleBody], space: &Space) -> f64 {
+    let points = bodies.iter().map(|b| Point::<D>::new(b.position(), b.m)).collect::<Vec<_>>();
+    let tree = Tree::new(&points, *space);
+    // every pair is seen from both sides
+    points.iter().map(|p| tree.potential(p)).sum::<f64>() / 2.0
+}
+
+/// DiagnosticsWriter writes a csv time series of the diagnostics, one row per step, and remembers the first and last energy to report the drift. The z components are only written in 3D.
+pub struct DiagnosticsWriter {
+    writer: BufWriter<File>,
+    every: usize,
+    three_d: bool,
+    first: Option<f64>,
+    last: f64,
+}
+
+impl DiagnosticsWriter {
+    /// creates the file at path, the diagnostics are written for every step that is a multiple of every.
+    pub fn create(path: &Path, every: usize, dimensions: usize) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let three_d = dimensions == 3;
+        if three_d {
+            writeln!(
+                writer,
+                "step,time,kinetic,potential,energy,momentum_x,momentum_y,momentum_z,angular_momentum_x,angular_momentum_y,angular_momentum_z,center_x,center_y,center_z"
+            )?;
+        } else {
+            writeln!(
+                writer,
+                "step,time,kinetic,potential,energy,momentum_x,momentum_y,angular_momentum,center_x,center_y"
+            )?;
+        }
+        Ok(DiagnosticsWriter {
+            writer,
+            every: every.max(1),
+            three_d,
+            first: None,
+            last: 0.0,
+        })
+    }
+
+    /// whether diagnostics are due after the given step.
+    pub fn is_due(&self, step: usize) -> bool {
+        step.is_multiple_of(self.every)
+    }
+
+    pub fn write(&mut self, step: usize, time: f64, d: &Diagnostics) -> io::Result<()> {
+        self.first.get_or_insert(d.energy());
+        self.last = d.energy();
+        if self.three_d {
+            return writeln!(
+                self.writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                step,
+                time,
+                d.kinetic,
+                d.potential,
+                d.energy(),
+                d.momentum_x,
+                d.momentum_y,
+                d.momentum_z,
+                d.angular_momentum_x,
+                d.angular_momentum_y,
+                d.angular_momentum,
+                d.center_x,
+                d.center_y,
+                d.center_z
+            );
+        }
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{},{},{},{},{}",
+            step,
+            time,
+            d.kinetic,
+            d.potential,
+            d.energy(),
+            d.momentum_x,
+            d.momentum_y,
+            d.angular_momentum,
+            d.center_x,
+            d.center_y
+        )
+    }
+
+    /// the change of the total energy relative to the first written value.
+    pub fn drift(&self) -> Option<f64> {
+        self.first.map(|first| (self.last - first) / first.abs())
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,504 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[cfg(feature = "display")]
+use sdl2::event::Event;
+#[cfg(feature = "display")]
+use sdl2::keyboard::Keycode;
+#[cfg(feature = "display")]
+use sdl2::mouse::MouseButton;
+#[cfg(feature = "display")]
+use sdl2::pixels::Color;
+#[cfg(feature = "display")]
+use sdl2::rect::{Point, Rect};
+#[cfg(feature = "display")]
+use sdl2::render::Canvas;
+#[cfg(feature = "display")]
+use sdl2::video::Window;
+
+use crate::benchmark::Report;
+use crate::boundary::Space;
+use crate::checkpoint::Checkpoint;
+use crate::config::{EngineKind, Mode, SimulationConfig};
+use crate::diagnostics::{Diagnostics, DiagnosticsWriter};
+use crate::engine::{new_engine, Engine};
+use crate::geometry::SimpleBody;
+use crate::initial::file::FileFormat;
+use crate::snapshot::Snapshots;
+use crate::timestep::Stepper;
+use crate::validate::{compare, measure_forces};
+
+#[cfg(feature = "display")]
+fn show_fps(frame_count: &mut usize, start_time: &mut Instant) {
+    const FPS_THRESHOLD_MILLIS: u128 = 1000;
+    const MILLIS_PER_SECOND: f64 = 1000.0;
+
+    let current_time = Instant::now();
+    let elapsed_millis = current_time.duration_since(*start_time).as_millis();
+
+    if elapsed_millis >= FPS_THRESHOLD_MILLIS {
+        let fps = (*frame_count as f64 / elapsed_millis as f64) * MILLIS_PER_SECOND;
+        println!("FPS: {:.2}", fps);
+
+        *start_time = current_time;
+        *frame_count = 0;
+    }
+}
+
+/// Initializes SDL and returns an EventPump and Canvas<Window> tuple.
+#[cfg(feature = "display")]
+fn init_sdl(title: &str, config: &SimulationConfig) -> (sdl2::EventPump, Canvas<Window>) {
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+
+    let window = video_subsystem
+        .window(
+            &format!("nbody {}", title),
+            config.width as u32,
+            config.height as u32,
+        )
+        .position_centered()
+        .resizable()
+        .build()
+        .unwrap();
+
+    let canvas = window.into_canvas().build().unwrap();
+    let event_pump = sdl_context.event_pump().unwrap();
+
+    (event_pump, canvas)
+}
+
+/// Camera is the view of the display mode over the simulation space: the point of the space at the centre of the window, the number of pixels per unit of length and, in 3D, the direction the space is looked at from. It only changes what is drawn, never the physics.
+#[cfg(feature = "display")]
+struct Camera {
+    centre: (f64, f64, f64),
+    zoom: f64,
+    /// the rotations around the vertical and the horizontal axis of the window, always 0 in 2D
+    yaw: f64,
+    pitch: f64,
+    home: ((f64, f64, f64), f64),
+    rotates: bool,
+    /// the last known position of the mouse in the window, the wheel zooms around it
+    mouse: (f64, f64),
+}
+
+#[cfg(feature = "display")]
+impl Camera {
+    const PAN_PIXELS: f64 = 50.0;
+    const ZOOM_STEP: f64 = 1.25;
+    /// radians per pixel of a drag with the right button
+    const ROTATE_STEP: f64 = 0.01;
+
+    /// a camera over the centre of the domain, at the scale of the configuration.
+    fn new(config: &SimulationConfig) -> Self {
+        let rotates = config.dimensions == 3;
+        let depth = if rotates { config.domain_depth } else { 0.0 };
+        let home = ((config.domain_width / 2.0, config.domain_height / 2.0, depth / 2.0), config.scale);
+        Camera {
+            centre: home.0,
+            zoom: home.1,
+            yaw: 0.0,
+            pitch: 0.0,
+            home,
+            rotates,
+            mouse: (config.width / 2.0, config.height / 2.0),
+        }
+    }
+
+    /// the directions of the space along the right and the bottom of the window.
+    fn axes(&self) -> ((f64, f64, f64), (f64, f64, f64)) {
+        let (sy, cy) = self.yaw.sin_cos();
+        let (sp, cp) = self.pitch.sin_cos();
+        ((cy, 0.0, sy), (sy * sp, cp, -cy * sp))
+    }
+
+    /// the pixel of a window of the given size that shows the point (x, y, z) of the space.
+    fn to_screen(&self, size: (u32, u32), x: f64, y: f64, z: f64) -> (f64, f64) {
+        let (right, down) = self.axes();
+        let d = (x - self.centre.0, y - self.centre.1, z - self.centre.2);
+        let dot = |a: (f64, f64, f64)| a.0 * d.0 + a.1 * d.1 + a.2 * d.2;
+        (
+            dot(right) * self.zoom + size.0 as f64 / 2.0,
+            dot(down) * self.zoom + size.1 as f64 / 2.0,
+        )
+    }
+
+    /// the point of the plane through the centre facing the window shown at the pixel (px, py) of a window of the given size.
+    fn to_space(&self, size: (u32, u32), px: f64, py: f64) -> (f64, f64, f64) {
+        let (right, down) = self.axes();
+        let (u, v) = ((px - size.0 as f64 / 2.0) / self.zoom, (py - size.1 as f64 / 2.0) / self.zoom);
+        (
+            self.centre.0 + u * right.0 + v * down.0,
+            self.centre.1 + u * right.1 + v * down.1,
+            self.centre.2 + u * right.2 + v * down.2,
+        )
+    }
+
+    /// moves the view by the given number of pixels.
+    fn pan(&mut self, dx: f64, dy: f64) {
+        let (right, down) = self.axes();
+        let (u, v) = (dx / self.zoom, dy / self.zoom);
+        self.centre = (
+            self.centre.0 - u * right.0 - v * down.0,
+            self.centre.1 - u * right.1 - v * down.1,
+            self.centre.2 - u * right.2 - v * down.2,
+        );
+    }
+
+    /// multiplies the zoom by factor, keeping the point under the pixel (px, py) in place.
+    fn zoom_at(&mut self, size: (u32, u32), px: f64, py: f64, factor: f64) {
+        let point = self.to_space(size, px, py);
+        self.zoom *= factor;
+        let shift = self.to_space(size, px, py);
+        self.centre = (
+            self.centre.0 + point.0 - shift.0,
+            self.centre.1 + point.1 - shift.1,
+            self.centre.2 + point.2 - shift.2,
+        );
+    }
+
+    /// turns the view of a 3D space around its centre.
+    fn rotate(&mut self, dx: f64, dy: f64) {
+        if self.rotates {
+            self.yaw += dx * Self::ROTATE_STEP;
+            self.pitch = (self.pitch + dy * Self::ROTATE_STEP).clamp(-std::f64::consts::FRAC_PI_2, std::f64::consts::FRAC_PI_2);
+        }
+    }
+
+    /// moves the camera after an input event: arrows or a drag with the left button pan, a drag with the right button rotates a 3D space, the wheel and +/- zoom, and 0 goes back to the initial view.
+    fn handle(&mut self, size: (u32, u32), event: &Event) {
+        let centre = (size.0 as f64 / 2.0, size.1 as f64 / 2.0);
+        match event {
+            Event::KeyDown { keycode: Some(key), .. } => match *key {
+                Keycode::Left => self.pan(Self::PAN_PIXELS, 0.0),
+                Keycode::Right => self.pan(-Self::PAN_PIXELS, 0.0),
+                Keycode::Up => self.pan(0.0, Self::PAN_PIXELS),
+                Keycode::Down => self.pan(0.0, -Self::PAN_PIXELS),
+                Keycode::Plus | Keycode::Equals | Keycode::KpPlus => self.zoom_at(size, centre.0, centre.1, Self::ZOOM_STEP),
+                Keycode::Minus | Keycode::KpMinus => self.zoom_at(size, centre.0, centre.1, 1.0 / Self::ZOOM_STEP),
+                Keycode::Num0 | Keycode::Kp0 => {
+                    self.centre = self.home.0;
+                    self.zoom = self.home.1;
+                    self.yaw = 0.0;
+                    self.pitch = 0.0;
+                }
+                _ => {}
+            },
+            Event::MouseMotion { mousestate, x, y, xrel, yrel, .. } => {
+                self.mouse = (*x as f64, *y as f64);
+                if mousestate.is_mouse_button_pressed(MouseButton::Left) {
+                    self.pan(*xrel as f64, *yrel as f64)
+                } else if mousestate.is_mouse_button_pressed(MouseButton::Right) {
+                    self.rotate(*xrel as f64, *yrel as f64)
+                }
+            }
+            Event::MouseWheel { y, .. } => self.zoom_at(size, self.mouse.0, self.mouse.1, Self::ZOOM_STEP.powi(*y)),
+            _ => {}
+        }
+    }
+
+    /// the squares the bodies are drawn as, one unit of length wide but at least a pixel.
+    fn bodies(&self, size: (u32, u32), bodies: &[SimpleBody]) -> Vec<Rect> {
+        let side = self.zoom.max(1.0);
+        bodies
+            .iter()
+            .map(|b| {
+                let (px, py) = self.to_screen(size, b.x, b.y, b.z);
+                Rect::new((px - side / 2.0) as i32, (py - side / 2.0) as i32, side as u32, side as u32)
+            })
+            .collect()
+    }
+
+    /// the edges of the outline of the domain, a rectangle in 2D and a box in 3D.
+    fn domain(&self, size: (u32, u32), config: &SimulationConfig) -> Vec<(Point, Point)> {
+        let depth = if self.rotates { config.domain_depth } else { 0.0 };
+        let corner = |i: usize| {
+            let pick = |bit: usize, length: f64| if i & bit == 0 { 0.0 } else { length };
+            let (px, py) = self.to_screen(size, pick(1, config.domain_width), pick(2, config.domain_height), pick(4, depth));
+            Point::new(px as i32, py as i32)
+        };
+        let bits: &[usize] = if self.rotates { &[1, 2, 4] } else { &[1, 2] };
+        let corners = if self.rotates { 8 } else { 4 };
+        let mut edges = Vec::new();
+        for i in 0..corners {
+            for &bit in bits {
+                if i & bit == 0 {
+                    edges.push((corner(i), corner(i | bit)));
+                }
+            }
+        }
+        edges
+    }
+}
+
+/// Output writes the snapshots, diagnostics and checkpoints of a run. Only the root process writes anything.
+struct Output {
+    snapshots: Option<Snapshots>,
+    diagnostics: Option<DiagnosticsWriter>,
+    space: Space,
+    exact_limit: usize,
+    checkpoint: Option<Checkpoint>,
+    checkpoint_path: Option<PathBuf>,
+    checkpoint_every: usize,
+}
+
+impl Output {
+    fn new(engine: &dyn Engine, config: &SimulationConfig, start: &Checkpoint) -> Result<Self, String> {
+        let root = engine.is_root();
+        let snapshots = match config.snapshot.as_ref() {
+            Some(path) if root => {
+                let format = config
+                    .snapshot_format
+                    .unwrap_or_else(|| FileFormat::from_path(path));
+                let snapshots = Snapshots::create(path, format, config.snapshot_every, config.dimensions)
+                    .map_err(|e| format!("unable to create {}: {}", path.display(), e))?;
+                Some(snapshots)
+            }
+            _ => None,
+        };
+        let diagnostics = match config.diagnostics.as_ref() {
+            Some(path) if root => {
+                let diagnostics = DiagnosticsWriter::create(path, config.diagnostics_every, config.dimensions)
+                    .map_err(|e| format!("unable to create {}: {}", path.display(), e))?;
+                Some(diagnostics)
+            }
+            _ => None,
+        };
+        let checkpoint_path = config.checkpoint.clone().filter(|_| root);
+        let checkpoint = checkpoint_path.as_ref().map(|_| Checkpoint {
+            step: start.step,
+            rng: start.rng,
+            config: config.clone(),
+            bodies: Vec::new(),
+        });
+        Ok(Output {
+            snapshots,
+            diagnostics,
+            space: Space::new(config),
+            exact_limit: config.exact_potential_limit,
+            checkpoint,
+            checkpoint_path,
+            checkpoint_every: config.checkpoint_every.max(1),
+        })
+    }
+
+    fn save(&mut self, engine: &dyn Engine, step: usize) -> Result<(), String> {
+        if let (Some(checkpoint), Some(path)) = (self.checkpoint.as_mut(), self.checkpoint_path.as_ref()) {
+            checkpoint.step = step;
+            checkpoint.bodies = engine.bodies();
+            checkpoint
+                .save(path)
+                .map_err(|e| format!("unable to write checkpoint {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// whether a snapshot or diagnostics are due after the given step.
+    fn is_observed(&self, step: usize) -> bool {
+        self.snapshots.as_ref().is_some_and(|x| x.is_due(step))
+            || self.diagnostics.as_ref().is_some_and(|x| x.is_due(step))
+    }
+
+    /// writes the snapshot and diagnostics of the bodies that are due after the given step.
+    fn observe(&mut self, step: usize, bodies: &[SimpleBody]) -> Result<(), String> {
+        let time = step as f64 * self.space.physics.dt;
+        if let Some(snapshots) = self.snapshots.as_mut().filter(|x| x.is_due(step)) {
+            snapshots
+                .write(step, time, bodies)
+                .map_err(|e| format!("unable to write snapshot: {}", e))?;
+        }
+        if let Some(diagnostics) = self.diagnostics.as_mut().filter(|x| x.is_due(step)) {
+            let measured = Diagnostics::measure(bodies, &self.space, self.exact_limit);
+            diagnostics
+                .write(step, time, &measured)
+                .map_err(|e| format!("unable to write diagnostics: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// writes a snapshot, diagnostics and a checkpoint of the engine after the given step if they are due.
+    fn record(&mut self, engine: &dyn Engine, step: usize) -> Result<(), String> {
+        if self.is_observed(step) {
+            self.observe(step, &engine.bodies())?;
+        }
+        if step.is_multiple_of(self.checkpoint_every) {
+            self.save(engine, step)?;
+        }
+        Ok(())
+    }
+
+    /// writes the final checkpoint, reports the energy drift and flushes the snapshots and diagnostics.
+    fn finish(&mut self, engine: &dyn Engine, step: usize) -> Result<(), String> {
+        if !step.is_multiple_of(self.checkpoint_every) {
+            self.save(engine, step)?;
+        }
+        if let Some(diagnostics) = self.diagnostics.as_mut() {
+            if let Some(drift) = diagnostics.drift() {
+                println!("Energy drift: {:e}", drift);
+            }
+            diagnostics
+                .finish()
+                .map_err(|e| format!("unable to write diagnostics: {}", e))?;
+        }
+        match self.snapshots.as_mut() {
+            Some(snapshots) => snapshots
+                .finish()
+                .map_err(|e| format!("unable to write snapshot: {}", e)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// runs config.warmup steps, then times config.steps steps of the engine one by one and reports the statistics. The output is not part of the measured time. Returns the last step.
+fn benchmark(
+    engine: &mut dyn Engine,
+    config: &SimulationConfig,
+    stepper: &mut Stepper,
+    output: &mut Output,
+    first: usize,
+) -> Result<usize, String> {
+    let last = first + config.warmup + config.steps;
+    let mut times = Vec::with_capacity(config.steps);
+    for step in first + 1..=last {
+        let start = Instant::now();
+        stepper.step(engine, config.physics.dt);
+        engine.sync_finished(step == last);
+        if step > first + config.warmup {
+            times.push(start.elapsed());
+        }
+        output.record(engine, step)?;
+    }
+    if engine.is_root() {
+        let report = Report::new(config.engine.name(), config.size, config.threads, config.warmup, &times);
+        report.print();
+        if let Some(path) = config.json.as_ref() {
+            report
+                .save_json(path)
+                .map_err(|e| format!("unable to write {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(last)
+}
+
+/// reports the force errors of the engine against the brute force one, then compares it against config.reference for config.steps steps and fails if they diverge beyond the tolerances.
+fn validate(engine: &mut dyn Engine, config: &SimulationConfig, stepper: &Stepper, start: &Checkpoint) -> Result<(), String> {
+    let exact = SimulationConfig {
+        engine: EngineKind::BruteForce,
+        ..config.clone()
+    };
+    let mut exact = new_engine(&exact)?;
+    let errors = measure_forces(engine, exact.as_mut(), &start.bodies);
+    let reference = SimulationConfig {
+        engine: config.reference,
+        ..config.clone()
+    };
+    let mut reference = new_engine(&reference)?;
+    let divergence = compare(engine, reference.as_mut(), stepper, &start.bodies, config.steps, config.physics.dt);
+    if !engine.is_root() {
+        return Ok(());
+    }
+    println!("Force errors against brute_force: {}", errors);
+    println!("Divergence after {} steps: {}", config.steps, divergence);
+    if divergence.within(config) {
+        println!("Validation: passed");
+        Ok(())
+    } else {
+        Err(format!(
+            "Validation: failed, {} diverges from {} beyond the tolerances (position {:e}, velocity {:e})",
+            config.engine, config.reference, config.position_tolerance, config.velocity_tolerance
+        ))
+    }
+}
+
+/// steps the engine until the root process decides to stop, without rendering anything. Returns the last step.
+fn follow(engine: &mut dyn Engine, config: &SimulationConfig, stepper: &mut Stepper, first: usize) -> usize {
+    let mut step = first;
+    loop {
+        stepper.step(engine, config.physics.dt);
+        step += 1;
+        if engine.sync_finished(false) {
+            break;
+        }
+    }
+    step
+}
+
+/// renders the bodies with SDL after every step until the window is closed. Returns the last step.
+#[cfg(feature = "display")]
+fn display(
+    engine: &mut dyn Engine,
+    config: &SimulationConfig,
+    stepper: &mut Stepper,
+    output: &mut Output,
+    first: usize,
+) -> Result<usize, String> {
+    let (mut event_pump, mut canvas) = init_sdl(engine.name(), config);
+
+    canvas.set_draw_color(Color::RGB(0, 255, 255));
+    canvas.clear();
+    canvas.present();
+    let mut i = 0;
+    let mut n = 0;
+    let mut step = first;
+    let mut start = Instant::now();
+    let mut camera = Camera::new(config);
+    loop {
+        n += 1;
+        let size = canvas.output_size()?;
+        canvas.set_draw_color(Color::RGB(255, 255, 255));
+        canvas.clear();
+        canvas.set_draw_color(Color::RGB(192, 192, 192));
+        for (from, to) in camera.domain(size, config) {
+            canvas.draw_line(from, to).expect("unable to draw the domain");
+        }
+        i = (i + 1) % 255;
+        canvas.set_draw_color(Color::RGB(i, 64, 255 - i));
+        let squares = camera.bodies(size, &engine.bodies());
+        canvas
+            .fill_rects(squares.as_slice())
+            .expect("unable to draw points");
+        canvas.present();
+        stepper.step(engine, config.physics.dt);
+        step += 1;
+        output.record(engine, step)?;
+
+        let mut finished = false;
+        for event in event_pump.poll_iter() {
+            if let Event::Quit { .. } = event {
+                finished = true;
+            }
+            camera.handle(size, &event);
+        }
+        if engine.sync_finished(finished) {
+            break;
+        }
+        if config.fps {
+            show_fps(&mut n, &mut start);
+        }
+    }
+    Ok(step)
+}
+
+/// initializes the engine from the given state (see Checkpoint::start for a fresh run) and runs it in the mode selected by the configuration. The display mode is only available with the display feature, a validate run writes no output.
+pub fn run(engine: &mut dyn Engine, config: &SimulationConfig, start: &Checkpoint) -> Result<(), String> {
+    if config.mode == Mode::Display && !cfg!(feature = "display") {
+        return Err("the display mode requires the cargo feature display".to_string());
+    }
+    let mut stepper = Stepper::new(config)?;
+    if config.mode == Mode::Validate {
+        return validate(engine, config, &stepper, start);
+    }
+    stepper.init(engine, &start.bodies, config.physics.dt);
+    let mut output = Output::new(engine, config, start)?;
+    if output.is_observed(start.step) {
+        output.observe(start.step, &start.bodies)?;
+    }
+    let last = match config.mode {
+        Mode::Benchmark => benchmark(engine, config, &mut stepper, &mut output, start.step)?,
+        #[cfg(feature = "display")]
+        Mode::Display if engine.is_root() => display(engine, config, &mut stepper, &mut output, start.step)?,
+        Mode::Display => follow(engine, config, &mut stepper, start.step),
+        Mode::Validate => unreachable!(),
+    };
+    output.finish(engine, last)
+}
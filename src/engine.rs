@@ -0,0 +1,131 @@
+use crate::brute_force::BruteForceEngine;
+use crate::boundary::Boundary;
+use crate::config::{EngineKind, SimulationConfig};
+use crate::fmm::FmmEngine;
+use crate::geometry::SimpleBody;
+#[cfg(feature = "mpi")]
+use crate::mpi_eng::MpiEngine;
+#[cfg(feature = "openmp")]
+use crate::openmp::OpenMpEngine;
+use crate::pm::PmEngine;
+use crate::pthread::ThreadTreeEngine;
+use crate::rayon_eng::RayonEngine;
+use crate::seq::TreeEngine;
+
+/// Engine is the common interface every backend (tree, brute force, openmp, rayon, pthread, mpi, fmm, pm) implements, so that a single driver can own body generation, rendering and timing.
+///
+/// An engine only provides the primitives of a step, the Integrator combines them. Under MPI every process takes part in collide and accelerate, the other primitives only act on the root process.
+pub trait Engine {
+    /// human readable name of the engine, used for the window title
+    fn name(&self) -> &'static str;
+
+    /// (re)initializes the engine state from the given body set.
+    fn init(&mut self, bodies: &[SimpleBody]);
+
+    /// applies the velocity changes of the collisions between the bodies.
+    fn collide(&mut self);
+
+    /// computes the acceleration of every body at the current positions.
+    fn accelerate(&mut self);
+
+    /// computes the accelerations of the active bodies only, the others keep theirs. Engines that cannot leave bodies out compute all of them.
+    fn accelerate_some(&mut self, active: &[bool]) {
+        let _ = active;
+        self.accelerate();
+    }
+
+    /// v = v + a * dt
+    fn kick(&mut self, dt: f64);
+
+    /// v = v + a * dt[i] for every body i.
+    fn kick_each(&mut self, dt: &[f64]) {
+        let mut bodies = self.bodies();
+        for (b, dt) in bodies.iter_mut().zip(dt.iter()) {
+            b.kick(*dt);
+        }
+        self.load(&bodies);
+    }
+
+    /// x = x + v * dt, plus a * dt^2 / 2 if with_acceleration is set.
+    fn drift(&mut self, dt: f64, with_acceleration: bool);
+
+    /// applies the boundary condition: bounces the bodies off the walls, wraps them around the periodic box or, in open space, leaves them alone.
+    fn confine(&mut self);
+
+    /// reads back the current positions, velocities and accelerations of all bodies.
+    fn bodies(&self) -> Vec<SimpleBody>;
+
+    /// replaces the positions, velocities and accelerations of the bodies by those read back with bodies, which may have been changed in between.
+    fn load(&mut self, bodies: &[SimpleBody]);
+
+    /// whether this process owns rendering and output. Only false on MPI child processes.
+    fn is_root(&self) -> bool {
+        true
+    }
+
+    /// agrees with the other processes (if any) on whether the run is finished. The value of the root process wins.
+    fn sync_finished(&mut self, finished: bool) -> bool {
+        finished
+    }
+
+    /// agrees with the other processes (if any) on a value. The value of the root process wins.
+    fn sync_value(&mut self, value: f64) -> f64 {
+        value
+    }
+}
+
+/// constructs the engine selected by the configuration, or fails if the engine is not compiled in or cannot simulate the dimensions of the configuration.
+pub fn new_engine(config: &SimulationConfig) -> Result<Box<dyn Engine>, String> {
+    if config.dimensions == 3 {
+        return match config.engine {
+            EngineKind::Tree => Ok(Box::new(TreeEngine::<3>::new(config))),
+            EngineKind::BruteForce => Ok(Box::new(BruteForceEngine::new(config))),
+            EngineKind::Rayon => Ok(Box::new(RayonEngine::new(config))),
+            EngineKind::RayonTree => Ok(Box::new(ThreadTreeEngine::<3>::new(config, true))),
+            EngineKind::PThread => Ok(Box::new(ThreadTreeEngine::<3>::new(config, false))),
+            EngineKind::Pm | EngineKind::TreePm if config.physics.boundary == Boundary::Open => {
+                Err(format!("engine {} needs a reflective or periodic boundary, its mesh covers the domain", config.engine))
+            }
+            EngineKind::Pm => Ok(Box::new(PmEngine::<3>::new(config, false))),
+            EngineKind::TreePm => Ok(Box::new(PmEngine::<3>::new(config, true))),
+            e => Err(format!(
+                "engine {} only simulates 2D bodies, 3D runs need one of {}",
+                e,
+                EngineKind::ALL
+                    .iter()
+                    .filter(|e| e.supports_3d())
+                    .map(|e| e.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        };
+    }
+    match config.engine {
+        EngineKind::Tree => Ok(Box::new(TreeEngine::<2>::new(config))),
+        EngineKind::BruteForce => Ok(Box::new(BruteForceEngine::new(config))),
+        #[cfg(feature = "openmp")]
+        EngineKind::OpenMp => Ok(Box::new(OpenMpEngine::new(config))),
+        EngineKind::Rayon => Ok(Box::new(RayonEngine::new(config))),
+        EngineKind::RayonTree => Ok(Box::new(ThreadTreeEngine::<2>::new(config, true))),
+        EngineKind::PThread => Ok(Box::new(ThreadTreeEngine::<2>::new(config, false))),
+        EngineKind::Fmm if config.physics.boundary == Boundary::Periodic => {
+            Err("engine fmm does not support the periodic boundary".to_string())
+        }
+        EngineKind::Fmm => Ok(Box::new(FmmEngine::new(config))),
+        EngineKind::Pm | EngineKind::TreePm if config.physics.boundary == Boundary::Open => {
+            Err(format!("engine {} needs a reflective or periodic boundary, its mesh covers the domain", config.engine))
+        }
+        EngineKind::Pm => Ok(Box::new(PmEngine::<2>::new(config, false))),
+        EngineKind::TreePm => Ok(Box::new(PmEngine::<2>::new(config, true))),
+        #[cfg(feature = "mpi")]
+        EngineKind::MpiNormal => Ok(Box::new(MpiEngine::new(config, false))),
+        #[cfg(all(feature = "mpi", feature = "openmp"))]
+        EngineKind::MpiOpenMp => Ok(Box::new(MpiEngine::new(config, true))),
+        #[allow(unreachable_patterns)]
+        e => Err(format!(
+            "engine {} requires the cargo feature(s) {}",
+            e,
+            e.required_features().join(", ")
+        )),
+    }
+}
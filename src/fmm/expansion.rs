@@ -0,0 +1,131 @@
+use nalgebra::Vector2;
+
+/// the number of coefficients of an expansion of order p, one for every multi-index (a, b) with a + b <= p.
+pub fn size(p: usize) -> usize {
+    (p + 1) * (p + 2) / 2
+}
+
+/// the position of the coefficient of the multi-index (a, b), the coefficients are ordered by total order.
+#[inline]
+pub fn index(a: usize, b: usize) -> usize {
+    let n = a + b;
+    n * (n + 1) / 2 + b
+}
+
+/// d^n / n! = x^a * y^b / (a! * b!) for every multi-index n = (a, b) up to order p.
+pub fn powers(d: &Vector2<f64>, p: usize) -> Vec<f64> {
+    let mut x = vec![1.0; p + 1];
+    let mut y = vec![1.0; p + 1];
+    for k in 1..=p {
+        x[k] = x[k - 1] * d.x / k as f64;
+        y[k] = y[k - 1] * d.y / k as f64;
+    }
+    let mut res = vec![0.0; size(p)];
+    for n in 0..=p {
+        for b in 0..=n {
+            res[index(n - b, b)] = x[n - b] * y[b];
+        }
+    }
+    res
+}
+
+/// the derivatives D_n of 1 / |r| for every multi-index n up to order p, by the recurrence |n| r^2 D_n = -(2 |n| - 1) sum_i n_i r_i D_(n - e_i) - (|n| - 1) sum_i n_i (n_i - 1) D_(n - 2 e_i).
+pub fn derivatives(r: &Vector2<f64>, p: usize) -> Vec<f64> {
+    let r2 = r.norm_squared();
+    let mut res = vec![0.0; size(p)];
+    res[0] = 1.0 / r2.sqrt();
+    for n in 1..=p {
+        let order = n as f64;
+        for b in 0..=n {
+            let a = n - b;
+            let mut sum = 0.0;
+            if a >= 1 {
+                sum -= (2.0 * order - 1.0) * a as f64 * r.x * res[index(a - 1, b)];
+            }
+            if b >= 1 {
+                sum -= (2.0 * order - 1.0) * b as f64 * r.y * res[index(a, b - 1)];
+            }
+            if a >= 2 {
+                sum -= (order - 1.0) * (a * (a - 1)) as f64 * res[index(a - 2, b)];
+            }
+            if b >= 2 {
+                sum -= (order - 1.0) * (b * (b - 1)) as f64 * res[index(a, b - 2)];
+            }
+            res[index(a, b)] = sum / (order * r2);
+        }
+    }
+    res
+}
+
+/// adds a body of the given mass at the offset d from the centre to the multipole moments M_n = sum m d^n / n!.
+pub fn p2m(multipole: &mut [f64], d: &Vector2<f64>, mass: f64, p: usize) {
+    for (m, t) in multipole.iter_mut().zip(powers(d, p)) {
+        *m += mass * t;
+    }
+}
+
+/// adds the moments of a child to those of its parent, d being the centre of the child minus the centre of the parent.
+pub fn m2m(parent: &mut [f64], child: &[f64], d: &Vector2<f64>, p: usize) {
+    let t = powers(d, p);
+    for n in 0..=p {
+        for b in 0..=n {
+            let a = n - b;
+            let mut sum = 0.0;
+            for j in 0..=b {
+                for i in 0..=a {
+                    sum += child[index(i, j)] * t[index(a - i, b - j)];
+                }
+            }
+            parent[index(a, b)] += sum;
+        }
+    }
+}
+
+/// adds the potential of a multipole expansion to a local expansion, L_k = -sum_n (-1)^|n| M_n D_(n + k), for a unit gravitational constant. derivatives are those of 1 / |r| at the centre of the local expansion minus the centre of the multipole one, or at the opposite if reverse is set.
+pub fn m2l(local: &mut [f64], multipole: &[f64], derivatives: &[f64], reverse: bool, p: usize) {
+    for k in 0..=p {
+        for kb in 0..=k {
+            let ka = k - kb;
+            let mut sum = 0.0;
+            for n in 0..=p - k {
+                // D_m(-r) = (-1)^|m| D_m(r)
+                let sign = if (if reverse { k } else { n }) % 2 == 0 { 1.0 } else { -1.0 };
+                for nb in 0..=n {
+                    sum += sign * multipole[index(n - nb, nb)] * derivatives[index(n - nb + ka, nb + kb)];
+                }
+            }
+            local[index(ka, kb)] -= sum;
+        }
+    }
+}
+
+/// adds a local expansion of a parent to the one of its child, d being the centre of the child minus the centre of the parent.
+pub fn l2l(child: &mut [f64], parent: &[f64], d: &Vector2<f64>, p: usize) {
+    let t = powers(d, p);
+    for k in 0..=p {
+        for kb in 0..=k {
+            let ka = k - kb;
+            let mut sum = 0.0;
+            for n in 0..=p - k {
+                for nb in 0..=n {
+                    sum += parent[index(ka + n - nb, kb + nb)] * t[index(n - nb, nb)];
+                }
+            }
+            child[index(ka, kb)] += sum;
+        }
+    }
+}
+
+/// the acceleration -grad L at the offset d from the centre of a local expansion.
+pub fn l2p(local: &[f64], d: &Vector2<f64>, p: usize) -> Vector2<f64> {
+    let t = powers(d, p);
+    let mut res = Vector2::zeros();
+    for k in 0..p {
+        for kb in 0..=k {
+            let ka = k - kb;
+            res.x -= local[index(ka + 1, kb)] * t[index(ka, kb)];
+            res.y -= local[index(ka, kb + 1)] * t[index(ka, kb)];
+        }
+    }
+    res
+}
@@ -0,0 +1,76 @@
+use tree::Tree;
+
+use crate::boundary::Space;
+use crate::config::SimulationConfig;
+use crate::engine::Engine;
+use crate::geometry::SimpleBody;
+
+mod expansion;
+mod tree;
+
+/// FmmEngine is the Fast Multipole Method engine for 2D runs: the cells of an adaptive quadtree exchange Cartesian Taylor expansions of the 1 / r potential through M2M, M2L and L2L translations, in O(n) per step. The complex expansions of the 2D logarithmic potential do not apply, since the bodies attract each other with 1 / r^2 forces within the plane. Pairs of cells too close for the opening angle theta are summed directly with the brute force kernels.
+pub struct FmmEngine {
+    universe: Vec<SimpleBody>,
+    space: Space,
+    order: usize,
+    leaf_size: usize,
+}
+
+impl FmmEngine {
+    pub fn new(config: &SimulationConfig) -> Self {
+        FmmEngine {
+            universe: Vec::new(),
+            space: Space::new(config),
+            order: config.fmm_order,
+            leaf_size: config.leaf_size,
+        }
+    }
+}
+
+impl Engine for FmmEngine {
+    fn name(&self) -> &'static str {
+        "FMM"
+    }
+
+    fn init(&mut self, bodies: &[SimpleBody]) {
+        self.universe = bodies.to_vec();
+    }
+
+    fn collide(&mut self) {
+        let tree = Tree::new(&self.universe, self.leaf_size);
+        let changes = tree.collisions(&self.universe, &self.space);
+        for (i, dv) in self.universe.iter_mut().zip(changes) {
+            let v = i.velocity::<2>() + dv;
+            i.set_velocity(&v);
+        }
+    }
+
+    fn accelerate(&mut self) {
+        let tree = Tree::new(&self.universe, self.leaf_size);
+        let accelerations = tree.accelerations(&self.space, self.order, self.space.physics.theta);
+        for (i, a) in self.universe.iter_mut().zip(accelerations.iter()) {
+            i.set_acceleration(a);
+        }
+    }
+
+    fn kick(&mut self, dt: f64) {
+        self.universe.iter_mut().for_each(|i| i.kick(dt));
+    }
+
+    fn drift(&mut self, dt: f64, with_acceleration: bool) {
+        self.universe.iter_mut().for_each(|i| i.drift(dt, with_acceleration));
+    }
+
+    fn confine(&mut self) {
+        let space = &self.space;
+        self.universe.iter_mut().for_each(|i| i.confine(space));
+    }
+
+    fn bodies(&self) -> Vec<SimpleBody> {
+        self.universe.clone()
+    }
+
+    fn load(&mut self, bodies: &[SimpleBody]) {
+        self.universe.copy_from_slice(bodies);
+    }
+}
@@ -0,0 +1,301 @@
+use nalgebra::Vector2;
+
+use super::expansion::*;
+use crate::boundary::Space;
+use crate::geometry::SimpleBody;
+use crate::softening::Softening;
+
+/// cells this deep are not divided any further, so that coincident bodies end up in one leaf instead of being split forever.
+const MAX_DEPTH: usize = 48;
+
+/// Cell is a node of the tree, holding the bodies order[start..end].
+struct Cell {
+    /// the centre of mass, which the expansions of the cell are about
+    centre: Vector2<f64>,
+    /// the largest distance of a body of the cell from the centre
+    radius: f64,
+    start: usize,
+    end: usize,
+    children: Vec<usize>,
+}
+
+/// Tree is the adaptive quadtree of the FMM engine, a flat list of cells in which every parent comes before its children.
+pub struct Tree {
+    cells: Vec<Cell>,
+    /// the indices of the bodies, sorted so that the bodies of every cell are contiguous
+    order: Vec<usize>,
+    positions: Vec<Vector2<f64>>,
+    masses: Vec<f64>,
+}
+
+impl Tree {
+    /// sorts the bodies into cells of at most leaf_size bodies.
+    pub fn new(bodies: &[SimpleBody], leaf_size: usize) -> Self {
+        let mut tree = Tree {
+            cells: Vec::new(),
+            order: (0..bodies.len()).collect(),
+            positions: bodies.iter().map(|b| b.position::<2>()).collect(),
+            masses: bodies.iter().map(|b| b.m).collect(),
+        };
+        if bodies.is_empty() {
+            return tree;
+        }
+        let mut lower = tree.positions[0];
+        let mut upper = lower;
+        for x in tree.positions.iter() {
+            lower = lower.inf(x);
+            upper = upper.sup(x);
+        }
+        tree.split(0, bodies.len(), lower, upper, leaf_size, 0);
+        let order = &tree.order;
+        tree.positions = order.iter().map(|&i| bodies[i].position::<2>()).collect();
+        tree.masses = order.iter().map(|&i| bodies[i].m).collect();
+        tree
+    }
+
+    /// creates the cell of the bodies order[start..end] within the box from lower to upper, and its descendants. Returns the index of the cell.
+    fn split(&mut self, start: usize, end: usize, lower: Vector2<f64>, upper: Vector2<f64>, leaf_size: usize, depth: usize) -> usize {
+        let index = self.cells.len();
+        self.cells.push(Cell {
+            centre: Vector2::zeros(),
+            radius: 0.0,
+            start,
+            end,
+            children: Vec::new(),
+        });
+        let mid = (lower + upper) / 2.0;
+        if end - start > leaf_size && depth < MAX_DEPTH {
+            let quadrant = |x: &Vector2<f64>| (x.x > mid.x) as usize + 2 * (x.y > mid.y) as usize;
+            let positions = &self.positions;
+            self.order[start..end].sort_by_key(|&i| quadrant(&positions[i]));
+            let mut bounds = [start; 5];
+            for q in 0..4 {
+                bounds[q + 1] = bounds[q] + self.order[bounds[q]..end].iter().take_while(|&&i| quadrant(&positions[i]) == q).count();
+            }
+            for (q, range) in bounds.windows(2).enumerate() {
+                let (first, last) = (range[0], range[1]);
+                if last > first {
+                    let (mut l, mut u) = (lower, mid);
+                    if q & 1 == 1 {
+                        l.x = mid.x;
+                        u.x = upper.x;
+                    }
+                    if q & 2 == 2 {
+                        l.y = mid.y;
+                        u.y = upper.y;
+                    }
+                    let child = self.split(first, last, l, u, leaf_size, depth + 1);
+                    self.cells[index].children.push(child);
+                }
+            }
+        }
+        let (mut mass, mut centre) = (0.0, Vector2::zeros());
+        for &i in self.order[start..end].iter() {
+            mass += self.masses[i];
+            centre += self.positions[i] * self.masses[i];
+        }
+        centre = if mass > 0.0 { centre / mass } else { mid };
+        let radius = self.order[start..end]
+            .iter()
+            .map(|&i| (self.positions[i] - centre).norm())
+            .fold(0.0, f64::max);
+        let cell = &mut self.cells[index];
+        cell.centre = centre;
+        cell.radius = radius;
+        index
+    }
+
+    /// the accelerations of the bodies in their original order: the expansions of order p of every cell are formed upwards, the pairs of cells are traversed from the root, those accepted with the opening angle theta interact through their expansions and the others are divided or summed directly, and the local expansions are passed down to the bodies.
+    pub fn accelerations(&self, space: &Space, p: usize, theta: f64) -> Vec<Vector2<f64>> {
+        let mut res = vec![Vector2::zeros(); self.order.len()];
+        if self.cells.is_empty() {
+            return res;
+        }
+        let physics = &space.physics;
+        let mut reach = 2.0 * physics.radius;
+        if physics.softening != Softening::None {
+            reach = reach.max(physics.softening_length);
+        }
+        let size = size(p);
+        let mut pass = Pass {
+            tree: self,
+            space,
+            p,
+            theta,
+            reach,
+            multipoles: vec![0.0; self.cells.len() * size],
+            locals: vec![0.0; self.cells.len() * size],
+            accelerations: vec![Vector2::zeros(); self.order.len()],
+        };
+        // the children come after their parent, so the reverse order visits them first
+        for c in (0..self.cells.len()).rev() {
+            let cell = &self.cells[c];
+            let (before, after) = pass.multipoles.split_at_mut((c + 1) * size);
+            let multipole = &mut before[c * size..];
+            if cell.children.is_empty() {
+                for k in cell.start..cell.end {
+                    p2m(multipole, &(self.positions[k] - cell.centre), self.masses[k], p);
+                }
+            }
+            for &child in cell.children.iter() {
+                let offset = (child - c - 1) * size;
+                m2m(multipole, &after[offset..offset + size], &(self.cells[child].centre - cell.centre), p);
+            }
+        }
+        pass.interact(0, 0);
+        for c in 0..self.cells.len() {
+            let cell = &self.cells[c];
+            let (before, after) = pass.locals.split_at_mut((c + 1) * size);
+            let local = &before[c * size..];
+            for &child in cell.children.iter() {
+                let offset = (child - c - 1) * size;
+                l2l(&mut after[offset..offset + size], local, &(self.cells[child].centre - cell.centre), p);
+            }
+            if cell.children.is_empty() {
+                for k in cell.start..cell.end {
+                    pass.accelerations[k] += l2p(local, &(self.positions[k] - cell.centre), p) * physics.g;
+                }
+            }
+        }
+        for (k, a) in pass.accelerations.into_iter().enumerate() {
+            res[self.order[k]] = a;
+        }
+        res
+    }
+
+    /// the velocity changes of the bodies in their original order from the collisions of every pair closer than twice the radius, taken with the velocities from before the collisions.
+    pub fn collisions(&self, bodies: &[SimpleBody], space: &Space) -> Vec<Vector2<f64>> {
+        let mut changes = vec![Vector2::zeros(); self.order.len()];
+        if !self.cells.is_empty() {
+            self.touch(0, 0, bodies, space, &mut changes);
+        }
+        let mut res = vec![Vector2::zeros(); self.order.len()];
+        for (k, v) in changes.into_iter().enumerate() {
+            res[self.order[k]] = v;
+        }
+        res
+    }
+
+    /// the collisions between the bodies of the cells a and b, or within a if both are the same.
+    fn touch(&self, a: usize, b: usize, bodies: &[SimpleBody], space: &Space, changes: &mut [Vector2<f64>]) {
+        let (ca, cb) = (&self.cells[a], &self.cells[b]);
+        let reach = 2.0 * space.physics.radius;
+        if a != b && (ca.centre - cb.centre).norm() > ca.radius + cb.radius + reach {
+            return;
+        }
+        let reach2 = reach * reach;
+        match (ca.children.is_empty(), cb.children.is_empty()) {
+            (true, true) => {
+                for i in ca.start..ca.end {
+                    let first = if a == b { i + 1 } else { cb.start };
+                    for j in first..cb.end {
+                        let delta = self.positions[i] - self.positions[j];
+                        let dist = delta.norm_squared();
+                        if dist <= reach2 && dist >= f64::EPSILON {
+                            let (bi, bj) = (&bodies[self.order[i]], &bodies[self.order[j]]);
+                            let dot = delta.dot(&(bi.velocity::<2>() - bj.velocity::<2>()));
+                            let scale = 2.0 / (bi.m + bj.m) * dot / dist;
+                            changes[i] -= delta * scale * bj.m;
+                            changes[j] += delta * scale * bi.m;
+                        }
+                    }
+                }
+            }
+            _ if a == b => {
+                for (k, &x) in ca.children.iter().enumerate() {
+                    for &y in ca.children[k..].iter() {
+                        self.touch(x, y, bodies, space, changes);
+                    }
+                }
+            }
+            (false, leaf) if leaf || ca.radius >= cb.radius => {
+                for &x in ca.children.iter() {
+                    self.touch(x, b, bodies, space, changes);
+                }
+            }
+            _ => {
+                for &y in cb.children.iter() {
+                    self.touch(a, y, bodies, space, changes);
+                }
+            }
+        }
+    }
+}
+
+/// Pass holds the expansions and accelerations of one evaluation of the forces, in the sorted order of the tree.
+struct Pass<'a> {
+    tree: &'a Tree,
+    space: &'a Space,
+    p: usize,
+    theta: f64,
+    /// cells closer than reach may hold colliding or softened pairs, so they are never approximated
+    reach: f64,
+    multipoles: Vec<f64>,
+    locals: Vec<f64>,
+    accelerations: Vec<Vector2<f64>>,
+}
+
+impl<'a> Pass<'a> {
+    /// the interactions between the cells a and b, or within a if both are the same.
+    fn interact(&mut self, a: usize, b: usize) {
+        let tree = self.tree;
+        let (ca, cb) = (&tree.cells[a], &tree.cells[b]);
+        if a != b {
+            let r = cb.centre - ca.centre;
+            let d = r.norm();
+            if ca.radius + cb.radius < self.theta * d && d - ca.radius - cb.radius > self.reach {
+                self.translate(a, b, &r);
+                return;
+            }
+        }
+        match (ca.children.is_empty(), cb.children.is_empty()) {
+            (true, true) => self.direct(a, b),
+            _ if a == b => {
+                for (k, &x) in ca.children.iter().enumerate() {
+                    for &y in ca.children[k..].iter() {
+                        self.interact(x, y);
+                    }
+                }
+            }
+            (false, leaf) if leaf || ca.radius >= cb.radius => {
+                for &x in ca.children.iter() {
+                    self.interact(x, b);
+                }
+            }
+            _ => {
+                for &y in cb.children.iter() {
+                    self.interact(a, y);
+                }
+            }
+        }
+    }
+
+    /// the M2L translations both ways between the cells a and b, r being the centre of b minus the centre of a.
+    fn translate(&mut self, a: usize, b: usize, r: &Vector2<f64>) {
+        let p = self.p;
+        let size = size(p);
+        let derivatives = derivatives(r, p);
+        let (ma, mb) = (a * size..(a + 1) * size, b * size..(b + 1) * size);
+        m2l(&mut self.locals[mb.clone()], &self.multipoles[ma.clone()], &derivatives, false, p);
+        m2l(&mut self.locals[ma], &self.multipoles[mb], &derivatives, true, p);
+    }
+
+    /// sums the pairs of bodies of the leaves a and b directly, with the kernels of the brute force engine.
+    fn direct(&mut self, a: usize, b: usize) {
+        let tree = self.tree;
+        let (ca, cb) = (&tree.cells[a], &tree.cells[b]);
+        let radius = self.space.physics.radius;
+        for i in ca.start..ca.end {
+            let first = if a == b { i + 1 } else { cb.start };
+            for j in first..cb.end {
+                let delta = tree.positions[i] - tree.positions[j];
+                let dist = delta.norm_squared();
+                if dist > radius * radius * 4.0 {
+                    let field = self.space.field(delta, dist);
+                    self.accelerations[i] -= field * tree.masses[j];
+                    self.accelerations[j] += field * tree.masses[i];
+                }
+            }
+        }
+    }
+}
@@ -1,109 +1,191 @@
-use std::f64::EPSILON;
-use std::sync::Arc;
-
-use nalgebra::Vector2;
+use nalgebra::SVector;
 
+use crate::boundary::{Boundary, Space};
 use crate::geometry::Point;
-use crate::global::*;
-use crate::quad_tree::node::*;
-
-pub struct Body {
-    /// An Arc (atomic reference counting) smart pointer to a QuadNode
-    node: Arc<QuadNode>,
-    pub position: Point,
-    pub velocity: Vector2<f64>,
-    pub acceleration: Vector2<f64>,
+use crate::quad_tree::tree::Tree;
+
+/// Body is a body of a Barnes-Hut engine in D dimensions.
+pub struct Body<const D: usize = 2> {
+    pub position: Point<D>,
+    pub velocity: SVector<f64, D>,
+    pub acceleration: SVector<f64, D>,
 }
 
-impl Body {
-    /// Sets the node field to a new QuadNode that is "ready" for insertion into the quadtree, using the current position field.
-    pub fn make_ready(&mut self) {
-        self.node = make_ready(self.position.clone(), self.node.clone())
+impl<const D: usize> Body<D> {
+    /// Performs collision detection with the bodies of the tree, built at the current positions, and updates the velocity field accordingly. velocities holds the velocities of all bodies from before the collisions.
+    pub fn collision_detect(&mut self, tree: &Tree<D>, velocities: &[SVector<f64, D>]) {
+        self.velocity += tree.collision(&self.position, &self.velocity, velocities);
     }
 
-    /// Performs collision detection using the position and node fields, and updates the velocity field accordingly.
-    pub fn collision_detect(&mut self) {
-        let impact = collision_detect(&self.position, self.node.clone());
-        self.velocity.x += impact.x;
-        self.velocity.y += impact.y;
+    /// Calculates the gravitational impact on the body based on its position field and the tree, and updates the acceleration field accordingly. The previous acceleration is what the relative opening criterion measures the error against.
+    pub fn gravity_impact(&mut self, tree: &Tree<D>) {
+        self.acceleration = tree.impact(&self.position, self.acceleration.norm()) / self.position.mass;
     }
 
-    /// Calculates the gravitational impact on the body based on its position field and the root quadtree, and updates the acceleration field accordingly.
-    pub fn gravity_impact(&mut self, root: Arc<QuadNode>) {
-        let impact = get_impact(&self.position, root);
-        self.acceleration.x = impact.0 / self.position.mass;
-        self.acceleration.y = impact.1 / self.position.mass;
+    /// Updates the position field based on the velocity field and the timestep dt, plus a * dt^2 / 2 if with_acceleration is set.
+    pub fn update_position(&mut self, dt: f64, with_acceleration: bool) {
+        let c = if with_acceleration { 0.5 * dt * dt } else { 0.0 };
+        self.position.coords += self.velocity * dt + self.acceleration * c;
     }
 
-    /// Updates the position field based on the velocity field and a constant ALPHA.
-    pub fn update_position(&mut self) {
-        self.position.x += self.velocity.x * ALPHA;
-        self.position.y += self.velocity.y * ALPHA;
+    /// v = a * t
+    pub fn update_velocity(&mut self, dt: f64) {
+        self.velocity += self.acceleration * dt;
     }
 
-    /// v = a * t
-    pub fn update_velocity(&mut self) {
-        self.velocity.x += self.acceleration.x * ALPHA;
-        self.velocity.y += self.acceleration.y * ALPHA;
+    /// Constructs a new Body object at the given position with the given mass.
+    pub fn new(coords: SVector<f64, D>, mass: f64) -> Body<D> {
+        Body {
+            position: Point::new(coords, mass),
+            velocity: SVector::zeros(),
+            acceleration: SVector::zeros(),
+        }
     }
 
-    /// Converts the position field to an sdl2::rect::Point object, which is used for rendering in SDL2.
-    pub fn geometric(&self) -> sdl2::rect::Point {
-        sdl2::rect::Point::new(self.position.x as i32, self.position.y as i32)
+    /// Constructs a new Body object carrying the full state of a SimpleBody.
+    pub fn from_simple(body: &SimpleBody) -> Body<D> {
+        let mut res = Body::new(body.position(), body.m);
+        res.velocity = body.velocity();
+        res.acceleration = body.acceleration();
+        res
     }
 
-    /// Constructs a new Body object with the given x, y, mass, and root quadtree.
-    pub fn new(x: f64, y: f64, mass: f64, root: Arc<QuadNode>) -> Body {
-        let position = Point { x, y, mass };
-        Body {
-            node: insert(root, position.clone()),
-            position,
-            velocity: Vector2::new(0.0, 0.0),
-            acceleration: Vector2::new(0.0, 0.0),
-        }
+    /// Reads back the state of the Body object as a SimpleBody.
+    pub fn to_simple(&self) -> SimpleBody {
+        let mut res = SimpleBody {
+            m: self.position.mass,
+            ..SimpleBody::default()
+        };
+        res.set_position(&self.position.coords);
+        res.set_velocity(&self.velocity);
+        res.set_acceleration(&self.acceleration);
+        res
     }
 
-    /// Reinserts the Body object into the quadtree, updating the node field based on the current position field.
-    pub fn reinsert(&mut self, root: Arc<QuadNode>) {
-        self.node = insert(root, self.position.clone());
+    /// replaces the position, velocity and acceleration of the body by those of the SimpleBody. The tree has to be rebuilt before it is used again.
+    pub fn load(&mut self, body: &SimpleBody) {
+        self.position.coords = body.position();
+        self.velocity = body.velocity();
+        self.acceleration = body.acceleration();
     }
 
-    /// Checks if the Body object has crossed the boundaries of the simulation window, and if so, updates its position and velocity fields accordingly.
-    pub fn check_boundary(&mut self) {
-        let real_width = *WIDTH / *SCALE_FACTOR;
-        let real_height = *HEIGHT / *SCALE_FACTOR;
-        if self.position.x + RADIUS >= real_width {
-            self.position.x = real_width - RADIUS - EPSILON;
-            self.velocity.x = -self.velocity.x * 0.5;
-        }
-        if self.position.x - RADIUS <= 0.0 {
-            self.position.x = RADIUS + EPSILON;
-            self.velocity.x = -self.velocity.x * 0.5;
-        }
-        if self.position.y + RADIUS >= real_height {
-            self.position.y = real_height - RADIUS - EPSILON;
-            self.velocity.y = -self.velocity.y * 0.5;
-        }
-        if self.position.y - RADIUS <= 0.0 {
-            self.position.y = RADIUS + EPSILON;
-            self.velocity.y = -self.velocity.y * 0.5;
-        }
+    /// Keeps the Body object inside the simulation space, as SimpleBody::confine does.
+    pub fn check_boundary(&mut self, space: &Space) {
+        let mut b = self.to_simple();
+        b.confine(space);
+        self.position.coords = b.position();
+        self.velocity = b.velocity();
     }
 }
 
-/// SimpleBody has fields for x, y, m (mass), vx (velocity x), vy (velocity y), ax (acceleration x), and ay (acceleration y). It has a single method to_sdl() which converts the x and y fields to an sdl2::rect::Point object, which is used for rendering in SDL2.
+/// SimpleBody has fields for x, y, z, m (mass), vx, vy, vz (velocity) and ax, ay, az (acceleration). It is the plain representation of a body that engines are initialized from and read back into. In 2D, z, vz and az stay 0.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
 pub struct SimpleBody {
     pub x: f64,
     pub y: f64,
+    pub z: f64,
     pub m: f64,
     pub vx: f64,
     pub vy: f64,
+    pub vz: f64,
     pub ax: f64,
     pub ay: f64,
+    pub az: f64,
+}
+
+/// the first D of the three values.
+fn first<const D: usize>(values: [f64; 3]) -> SVector<f64, D> {
+    SVector::from_fn(|i, _| values[i])
+}
+
+/// the three values with the first D replaced by those of v.
+fn replaced<const D: usize>(mut values: [f64; 3], v: &SVector<f64, D>) -> [f64; 3] {
+    values[..D].copy_from_slice(v.as_slice());
+    values
 }
 
 impl SimpleBody {
-    pub fn to_sdl(&self) -> sdl2::rect::Point {
-        sdl2::rect::Point::new(self.x as i32, self.y as i32)
+    /// the position in D dimensions, x and y in 2D and x, y and z in 3D.
+    pub fn position<const D: usize>(&self) -> SVector<f64, D> {
+        first([self.x, self.y, self.z])
+    }
+
+    /// the velocity in D dimensions.
+    pub fn velocity<const D: usize>(&self) -> SVector<f64, D> {
+        first([self.vx, self.vy, self.vz])
+    }
+
+    /// the acceleration in D dimensions.
+    pub fn acceleration<const D: usize>(&self) -> SVector<f64, D> {
+        first([self.ax, self.ay, self.az])
+    }
+
+    pub fn set_position<const D: usize>(&mut self, position: &SVector<f64, D>) {
+        [self.x, self.y, self.z] = replaced([self.x, self.y, self.z], position);
+    }
+
+    pub fn set_velocity<const D: usize>(&mut self, velocity: &SVector<f64, D>) {
+        [self.vx, self.vy, self.vz] = replaced([self.vx, self.vy, self.vz], velocity);
+    }
+
+    pub fn set_acceleration<const D: usize>(&mut self, acceleration: &SVector<f64, D>) {
+        [self.ax, self.ay, self.az] = replaced([self.ax, self.ay, self.az], acceleration);
+    }
+
+    /// v = v + a * t
+    pub fn kick(&mut self, dt: f64) {
+        self.vx += self.ax * dt;
+        self.vy += self.ay * dt;
+        self.vz += self.az * dt;
+    }
+
+    /// x = x + v * t, plus a * t^2 / 2 if with_acceleration is set.
+    pub fn drift(&mut self, dt: f64, with_acceleration: bool) {
+        let c = if with_acceleration { 0.5 * dt * dt } else { 0.0 };
+        self.x += self.vx * dt + self.ax * c;
+        self.y += self.vy * dt + self.ay * c;
+        self.z += self.vz * dt + self.az * c;
+    }
+
+    /// moves a body whose velocity became NaN back into the simulation space, then applies the boundary: a reflective one bounces the body of the given radius off the edge, keeping restitution of its speed, a periodic one brings it back in on the other side and an open one does nothing.
+    pub fn confine(&mut self, space: &Space) {
+        let radius = space.physics.radius;
+        let restitution = space.physics.restitution;
+        let axes = [
+            (&mut self.x, &mut self.vx, space.region.0.x),
+            (&mut self.y, &mut self.vy, space.region.0.y),
+            (&mut self.z, &mut self.vz, space.depth),
+        ];
+        for (x, v, side) in axes.into_iter().take(space.dimensions) {
+            if v.is_nan() {
+                *v = 0.0;
+                *x = 0.618 * side;
+            }
+            match space.physics.boundary {
+                Boundary::Open => {}
+                Boundary::Reflective => {
+                    if *x + radius >= side {
+                        *x = side - radius - f64::EPSILON;
+                        *v *= -restitution;
+                    }
+                    if *x - radius <= 0.0 {
+                        *x = radius + f64::EPSILON;
+                        *v *= -restitution;
+                    }
+                }
+                Boundary::Periodic => *x = wrap(*x, side),
+            }
+        }
+    }
+}
+
+/// x brought back into 0..length.
+fn wrap(x: f64, length: f64) -> f64 {
+    let x = x.rem_euclid(length);
+    // rem_euclid rounds tiny negative values up to length itself
+    if x >= length {
+        x - length
+    } else {
+        x
     }
 }
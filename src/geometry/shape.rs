@@ -1,115 +1,96 @@
 use std::fmt::{Debug, Error, Formatter};
 use std::hash::{Hash, Hasher};
 
-use nalgebra::Vector2;
+use nalgebra::SVector;
 // use num::Float;
 
-use crate::global::RADIUS;
-
-/// Square represents a rectangle with sides parallel to the x and y axes, defined by its two opposite corners, self.0 and self.1, both of type Vector2<f64> from the nalgebra library
+/// Square represents a box with sides parallel to the axes of a D dimensional space, a rectangle in 2D and a cuboid in 3D, defined by its two opposite corners: self.0 is the upper one and self.1 the lower one, both of type SVector<f64, D> from the nalgebra library
 #[derive(Copy, Clone)]
-pub struct Square(pub Vector2<f64>, pub Vector2<f64>);
+pub struct Square<const D: usize = 2>(pub SVector<f64, D>, pub SVector<f64, D>);
+
+/// the box of a 3D space.
+pub type Cube = Square<3>;
 
-impl Debug for Square {
+impl<const D: usize> Debug for Square<D> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        write!(
-            f,
-            "{:?}",
-            [((self.0).x, (self.0).y), ((self.1).x, (self.1).y)]
-        )
+        write!(f, "{:?}", [self.0.as_slice(), self.1.as_slice()])
     }
 }
 
-impl Square {
-    /// checks whether a given Point is completely contained within the Square.
-    pub(crate) fn contains(&self, x: &Point) -> bool {
-        self.0.x > x.x + RADIUS
-            && self.0.y > x.y + RADIUS
-            && self.1.x < x.x - RADIUS
-            && self.1.y < x.y - RADIUS
+impl<const D: usize> Square<D> {
+    /// checks whether a body of the given radius at Point is completely contained within the Square.
+    pub(crate) fn contains(&self, x: &Point<D>, radius: f64) -> bool {
+        (0..D).all(|i| self.0[i] > x.coords[i] + radius && self.1[i] < x.coords[i] - radius)
     }
 
-    /// checks whether a given Point is within a distance of RADIUS from the Square.
-    // pub fn touch(&self, x: &Point) -> bool {
-    //     static DIST: f64 = RADIUS * RADIUS;
-    //     (self.0.x - x.x) * (self.0.x - x.x) <= DIST
-    //         || (self.1.x - x.x) * (self.1.x - x.x) <= DIST
-    //         || (self.0.y - x.y) * (self.0.y - x.y) <= DIST
-    //         || (self.1.y - x.y) * (self.1.y - x.y) <= DIST
-    // }
-
-    /// checks whether a given Point is within a distance of RADIUS from the Square.
-    // pub fn can_touch(&self, x: &Point) -> bool {
-    //     static DIST: f64 = 9.0 * RADIUS * RADIUS;
-    //     let mid = (self.0 + self.1) / 2.0;
-    //     (mid.x - x.x) * (mid.x - x.x) <= DIST
-    //         || (mid.y - x.y) * (mid.y - x.y) <= DIST
-    // }
-
-    // Rewrite previous two methods Replaced touch method in Square with more efficient implementations that use vector operations and take advantage of Rust's SIMD features.
-
-    /// checks whether a given Point is within a distance of RADIUS from the Square.
-    pub fn touch(&self, point: &Point) -> bool {
-        let dist = RADIUS * RADIUS;
-        let p = point.coords();
-        let d = |i: usize| (p[i] - self.0[i]).max(self.1[i] - p[i]).max(0.0);
-        let dx = d(0);
-        let dy = d(1);
-        dx * dx + dy * dy <= dist
+    /// checks whether a given Point is within a distance of radius from the Square.
+    pub fn touch(&self, point: &Point<D>, radius: f64) -> bool {
+        let p = &point.coords;
+        let d = SVector::<f64, D>::from_fn(|i, _| (p[i] - self.0[i]).max(self.1[i] - p[i]).max(0.0));
+        d.norm_squared() <= radius * radius
     }
 
-    /// checks whether a given Point is within a distance of 3 * RADIUS from the Square.
-    pub fn can_touch(&self, point: &Point) -> bool {
-        let dist = 9.0 * RADIUS * RADIUS;
+    /// checks whether a given Point is within a distance of 3 * radius from the centre of the Square.
+    pub fn can_touch(&self, point: &Point<D>, radius: f64) -> bool {
         let mid = (self.0 + self.1) / 2.0;
-        let dx = point.x - mid.x;
-        let dy = point.y - mid.y;
-        dx * dx + dy * dy <= dist
+        (point.coords - mid).norm_squared() <= 9.0 * radius * radius
+    }
+
+    /// the 2^D boxes the Square is divided into by its centre. Bit i of the index selects the upper half along axis i.
+    pub fn split(&self) -> Vec<Square<D>> {
+        let centre = (self.0 + self.1) / 2.0;
+        (0..1 << D)
+            .map(|k| {
+                let mut upper = centre;
+                let mut lower = self.1;
+                for axis in (0..D).filter(|axis| k & (1 << axis) != 0) {
+                    upper[axis] = self.0[axis];
+                    lower[axis] = centre[axis];
+                }
+                Square(upper, lower)
+            })
+            .collect()
     }
 }
 
-/// Point represents a point in two-dimensional space, defined by its x and y coordinates and a mass.
+/// Point represents a point in a D dimensional space, defined by its coordinates and a mass.
 #[derive(PartialEq, Copy, Clone)]
-pub struct Point {
-    pub x: f64,
-    pub y: f64,
+pub struct Point<const D: usize = 2> {
+    pub coords: SVector<f64, D>,
     pub mass: f64,
 }
 
-impl Debug for Point {
+/// a point of a 3D space.
+pub type Point3 = Point<3>;
+
+impl<const D: usize> Debug for Point<D> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        write!(f, "{:?}", (self.x, self.y))
+        write!(f, "{:?}", self.coords.as_slice())
     }
 }
 
 /// Point implements the Eq trait, which allows it to be compared for equality with other Points.
-impl Eq for Point {}
+impl<const D: usize> Eq for Point<D> {}
 
 /// Point implements the Hash trait, which allows it to be used as a key in a HashMap. ??? Not sure
-impl Hash for Point {
+impl<const D: usize> Hash for Point<D> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         // (self.x.integer_decode(), self.y.integer_decode(), self.mass.integer_decode()).hash(state);
-        (self.x.to_bits(), self.y.to_bits(), self.mass.to_bits()).hash(state);
+        for x in self.coords.iter() {
+            x.to_bits().hash(state);
+        }
+        self.mass.to_bits().hash(state);
         // Changed the hashing function for Point to use to_bits instead of integer_decode, which is simpler and more efficient.
     }
 }
 
-impl Point {
-    /// return a new Vector2<f64> containing the point's coordinates
-    pub fn coords(&self) -> Vector2<f64> {
-        Vector2::new(self.x, self.y)
-    }
-
-    /// return a new Point with the same coordinates as the given Vector2<f64>
-    pub fn update_by(&mut self, data: &Vector2<f64>) {
-        self.x += data.x;
-        self.y += data.y;
+impl<const D: usize> Point<D> {
+    pub fn new(coords: SVector<f64, D>, mass: f64) -> Self {
+        Point { coords, mass }
     }
 }
 
-/// check whether two Points are within a distance of 2 * RADIUS from each other.
-pub fn check(p: &Point, q: &Point) -> bool {
-    let a = p.x - q.x;
-    let b = p.y - q.y;
-    a * a + b * b < 4.0 * RADIUS * RADIUS
+/// check whether two Points are within a distance of 2 * radius from each other.
+pub fn check<const D: usize>(p: &Point<D>, q: &Point<D>, radius: f64) -> bool {
+    (p.coords - q.coords).norm_squared() < 4.0 * radius * radius
 }
@@ -0,0 +1,194 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use crate::geometry::SimpleBody;
+
+/// magic number at the start of the binary format.
+pub const MAGIC: &[u8; 8] = b"NBODYIC1";
+
+/// magic number at the start of the binary format of 3D bodies.
+pub const MAGIC_3D: &[u8; 8] = b"NBODYIC3";
+
+/// FileFormat is one of the supported on-disk formats of initial conditions.
+///
+/// csv: a header line naming the columns x, y, z, vx, vy, vz and m (in any order, z, vx, vy and vz may be omitted), then one body per line. Empty lines and lines starting with # are ignored.
+///
+/// binary: the 8 byte magic number NBODYIC1, the number of bodies as a little endian u64, then x, y, vx, vy, m of every body as little endian f64. 3D bodies start with NBODYIC3 instead and store x, y, z, vx, vy, vz, m.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum FileFormat {
+    Csv,
+    Binary,
+}
+
+impl FileFormat {
+    /// guesses the format from the extension of the path, everything but .csv is read as binary.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => FileFormat::Csv,
+            _ => FileFormat::Binary,
+        }
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn check_body(body: &SimpleBody, index: usize) -> io::Result<()> {
+    let values = [body.x, body.y, body.z, body.vx, body.vy, body.vz, body.m];
+    if values.iter().any(|x| !x.is_finite()) {
+        return Err(invalid(format!("body {} has a non-finite value", index)));
+    }
+    if body.m <= 0.0 {
+        return Err(invalid(format!("body {} has a non-positive mass", index)));
+    }
+    Ok(())
+}
+
+/// a body at rest made of the values x, y, z, vx, vy, vz and m.
+fn body(values: [f64; 7]) -> SimpleBody {
+    SimpleBody {
+        x: values[0],
+        y: values[1],
+        z: values[2],
+        m: values[6],
+        vx: values[3],
+        vy: values[4],
+        vz: values[5],
+        ax: 0.0,
+        ay: 0.0,
+        az: 0.0,
+    }
+}
+
+/// reads bodies in the csv format from the reader.
+pub fn read_csv<R: BufRead>(reader: R) -> io::Result<Vec<SimpleBody>> {
+    let mut columns: Option<[Option<usize>; 7]> = None;
+    let mut bodies = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split(',').map(|x| x.trim()).collect::<Vec<_>>();
+        match columns {
+            None => {
+                let find = |name: &str| fields.iter().position(|x| x.eq_ignore_ascii_case(name));
+                let found = [find("x"), find("y"), find("z"), find("vx"), find("vy"), find("vz"), find("m")];
+                for (index, name) in [(0, "x"), (1, "y"), (6, "m")].iter() {
+                    if found[*index].is_none() {
+                        return Err(invalid(format!("csv header has no column {}", name)));
+                    }
+                }
+                columns = Some(found);
+            }
+            Some(columns) => {
+                let mut values = [0.0; 7];
+                for (value, column) in values.iter_mut().zip(columns.iter()) {
+                    if let Some(column) = column {
+                        let field = fields.get(*column).ok_or_else(|| {
+                            invalid(format!("line {}: expected at least {} fields", number + 1, column + 1))
+                        })?;
+                        *value = field.parse::<f64>().map_err(|_| {
+                            invalid(format!("line {}: {} is not a number", number + 1, field))
+                        })?;
+                    }
+                }
+                let b = body(values);
+                check_body(&b, bodies.len())?;
+                bodies.push(b);
+            }
+        }
+    }
+    Ok(bodies)
+}
+
+/// writes bodies in the csv format to the writer, with the z and vz columns in 3D.
+pub fn write_csv<W: Write>(mut writer: W, bodies: &[SimpleBody], dimensions: usize) -> io::Result<()> {
+    if dimensions == 3 {
+        writeln!(writer, "x,y,z,vx,vy,vz,m")?;
+        for b in bodies {
+            writeln!(writer, "{},{},{},{},{},{},{}", b.x, b.y, b.z, b.vx, b.vy, b.vz, b.m)?;
+        }
+    } else {
+        writeln!(writer, "x,y,vx,vy,m")?;
+        for b in bodies {
+            writeln!(writer, "{},{},{},{},{}", b.x, b.y, b.vx, b.vy, b.m)?;
+        }
+    }
+    writer.flush()
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> io::Result<f64> {
+    let mut buffer = [0u8; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(f64::from_le_bytes(buffer))
+}
+
+/// reads bodies in the binary format from the reader.
+pub fn read_binary<R: Read>(mut reader: R) -> io::Result<Vec<SimpleBody>> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    let three_d = if &magic == MAGIC {
+        false
+    } else if &magic == MAGIC_3D {
+        true
+    } else {
+        return Err(invalid("not a binary initial condition file".to_string()));
+    };
+    let mut count = [0u8; 8];
+    reader.read_exact(&mut count)?;
+    let count = u64::from_le_bytes(count) as usize;
+    let mut bodies = Vec::new();
+    for i in 0..count {
+        // z and vz are not stored in 2D
+        let mut values = [0.0; 7];
+        for (k, value) in values.iter_mut().enumerate() {
+            if three_d || (k != 2 && k != 5) {
+                *value = read_f64(&mut reader)?;
+            }
+        }
+        let b = body(values);
+        check_body(&b, i)?;
+        bodies.push(b);
+    }
+    Ok(bodies)
+}
+
+/// writes bodies in the binary format to the writer, as 3D bodies in 3D.
+pub fn write_binary<W: Write>(mut writer: W, bodies: &[SimpleBody], dimensions: usize) -> io::Result<()> {
+    writer.write_all(if dimensions == 3 { MAGIC_3D } else { MAGIC })?;
+    writer.write_all(&(bodies.len() as u64).to_le_bytes())?;
+    for b in bodies {
+        if dimensions == 3 {
+            for value in [b.x, b.y, b.z, b.vx, b.vy, b.vz, b.m].iter() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        } else {
+            for value in [b.x, b.y, b.vx, b.vy, b.m].iter() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// loads the bodies stored in the file at path.
+pub fn load(path: &Path, format: FileFormat) -> io::Result<Vec<SimpleBody>> {
+    let file = BufReader::new(File::open(path)?);
+    match format {
+        FileFormat::Csv => read_csv(file),
+        FileFormat::Binary => read_binary(file),
+    }
+}
+
+/// stores the bodies in a file at path, so that they can be loaded again. 2D files leave z and vz out.
+pub fn save(path: &Path, format: FileFormat, bodies: &[SimpleBody], dimensions: usize) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    match format {
+        FileFormat::Csv => write_csv(file, bodies, dimensions),
+        FileFormat::Binary => write_binary(file, bodies, dimensions),
+    }
+}
@@ -0,0 +1,110 @@
+use std::io;
+
+use rand::{Error, Rng, RngCore};
+
+use crate::config::SimulationConfig;
+use crate::geometry::SimpleBody;
+use crate::initial::file::FileFormat;
+
+pub mod file;
+
+/// SeededRng is a SplitMix64 generator. Unlike thread_rng it is fully determined by its seed, so that every engine (and every run) started from the same seed sees exactly the same bodies. Its whole state is a single u64.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng { state: seed }
+    }
+
+    /// the current state, SeededRng::new(rng.state()) continues the sequence of rng.
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+}
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// generates config.size bodies uniformly distributed inside the simulation space, with mass in 0..config.physics.mass_range and at rest. The z coordinate is only drawn in 3D, so 2D bodies do not depend on domain_depth.
+pub fn uniform<R: Rng>(config: &SimulationConfig, rng: &mut R) -> Vec<SimpleBody> {
+    let radius = config.physics.radius;
+    let mut bodies = Vec::with_capacity(config.size);
+    for _ in 0..config.size {
+        bodies.push(SimpleBody {
+            x: rng.gen_range(radius + f64::EPSILON, config.domain_width - radius),
+            y: rng.gen_range(radius + f64::EPSILON, config.domain_height - radius),
+            z: if config.dimensions == 3 {
+                rng.gen_range(radius + f64::EPSILON, config.domain_depth - radius)
+            } else {
+                0.0
+            },
+            m: rng.gen_range(0.0, config.physics.mass_range),
+            vx: 0.0,
+            vy: 0.0,
+            vz: 0.0,
+            ax: 0.0,
+            ay: 0.0,
+            az: 0.0,
+        });
+    }
+    bodies
+}
+
+/// generates the initial conditions of a run, determined by config.seed only.
+pub fn generate(config: &SimulationConfig) -> Vec<SimpleBody> {
+    let mut rng = SeededRng::new(config.seed);
+    uniform(config, &mut rng)
+}
+
+/// the initial conditions of a run: the bodies of config.input if it is set, otherwise config.size bodies generated from config.seed.
+pub fn bodies(config: &SimulationConfig) -> io::Result<Vec<SimpleBody>> {
+    bodies_with(config, &mut SeededRng::new(config.seed))
+}
+
+/// like bodies, but draws the generated bodies from rng, which is left untouched when the bodies are loaded from a file. A 2D run fails on a file of bodies that leave the plane.
+pub fn bodies_with(config: &SimulationConfig, rng: &mut SeededRng) -> io::Result<Vec<SimpleBody>> {
+    match config.input.as_ref() {
+        Some(path) => {
+            let format = config
+                .input_format
+                .unwrap_or_else(|| FileFormat::from_path(path));
+            let bodies = file::load(path, format)?;
+            if config.dimensions == 2 {
+                if let Some(i) = bodies.iter().position(|b| b.z != 0.0 || b.vz != 0.0) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("body {} is not in the plane z = 0, 3D bodies need 3 dimensions", i),
+                    ));
+                }
+            }
+            Ok(bodies)
+        }
+        None => Ok(uniform(config, rng)),
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt::{Display, Error, Formatter};
+use std::str::FromStr;
+
+use crate::engine::Engine;
+use crate::geometry::SimpleBody;
+
+/// Integrator advances an engine in time by combining its primitives. Every scheme applies the collisions once at the start of a step and the boundary at its end.
+///
+/// Between two steps the accelerations always belong to the current positions, so the first force evaluation of a step is free.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Integrator {
+    /// semi-implicit (symplectic) Euler: kick by dt, then drift by dt. First order.
+    Euler,
+    /// leapfrog in kick-drift-kick form. Second order and symplectic.
+    Leapfrog,
+    /// velocity Verlet: x + v * dt + a * dt^2 / 2, then v + (a + a') * dt / 2. Second order and symplectic.
+    Verlet,
+    /// classical fourth order Runge-Kutta, with three extra force evaluations per step. Not symplectic.
+    Rk4,
+}
+
+impl Integrator {
+    /// all integrators, in the order they are listed on the command line.
+    pub const ALL: [Integrator; 4] = [
+        Integrator::Euler,
+        Integrator::Leapfrog,
+        Integrator::Verlet,
+        Integrator::Rk4,
+    ];
+
+    /// the name used to select the integrator on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Integrator::Euler => "euler",
+            Integrator::Leapfrog => "leapfrog",
+            Integrator::Verlet => "verlet",
+            Integrator::Rk4 => "rk4",
+        }
+    }
+
+    /// initializes the engine with the bodies and computes their accelerations.
+    pub fn init(&self, engine: &mut dyn Engine, bodies: &[SimpleBody]) {
+        engine.init(bodies);
+        engine.accelerate();
+    }
+
+    /// advances the engine by one step of length dt.
+    pub fn step(&self, engine: &mut dyn Engine, dt: f64) {
+        engine.collide();
+        match self {
+            Integrator::Euler => {
+                engine.kick(dt);
+                engine.drift(dt, false);
+                engine.confine();
+                engine.accelerate();
+            }
+            Integrator::Leapfrog => {
+                engine.kick(0.5 * dt);
+                engine.drift(dt, false);
+                engine.confine();
+                engine.accelerate();
+                engine.kick(0.5 * dt);
+            }
+            Integrator::Verlet => {
+                engine.drift(dt, true);
+                engine.kick(0.5 * dt);
+                engine.confine();
+                engine.accelerate();
+                engine.kick(0.5 * dt);
+            }
+            Integrator::Rk4 => rk4(engine, dt),
+        }
+    }
+}
+
+/// the state of stage k, x0 + v * h and v0 + a * h for every body.
+fn stage(start: &[SimpleBody], velocity: &[(f64, f64, f64)], acceleration: &[(f64, f64, f64)], h: f64) -> Vec<SimpleBody> {
+    start
+        .iter()
+        .zip(velocity.iter().zip(acceleration.iter()))
+        .map(|(b, (v, a))| SimpleBody {
+            x: b.x + v.0 * h,
+            y: b.y + v.1 * h,
+            z: b.z + v.2 * h,
+            vx: b.vx + a.0 * h,
+            vy: b.vy + a.1 * h,
+            vz: b.vz + a.2 * h,
+            ..*b
+        })
+        .collect()
+}
+
+fn velocities(bodies: &[SimpleBody]) -> Vec<(f64, f64, f64)> {
+    bodies.iter().map(|b| (b.vx, b.vy, b.vz)).collect()
+}
+
+fn accelerations(bodies: &[SimpleBody]) -> Vec<(f64, f64, f64)> {
+    bodies.iter().map(|b| (b.ax, b.ay, b.az)).collect()
+}
+
+/// (k1 + 2 * k2 + 2 * k3 + k4) * dt / 6
+fn weighted(k1: (f64, f64, f64), k2: (f64, f64, f64), k3: (f64, f64, f64), k4: (f64, f64, f64), dt: f64) -> (f64, f64, f64) {
+    (
+        (k1.0 + 2.0 * k2.0 + 2.0 * k3.0 + k4.0) * dt / 6.0,
+        (k1.1 + 2.0 * k2.1 + 2.0 * k3.1 + k4.1) * dt / 6.0,
+        (k1.2 + 2.0 * k2.2 + 2.0 * k3.2 + k4.2) * dt / 6.0,
+    )
+}
+
+/// the stages are combined on the root process only, the other MPI processes read back no bodies and merely take part in the force evaluations.
+fn rk4(engine: &mut dyn Engine, dt: f64) {
+    let start = engine.bodies();
+    let k1 = (velocities(&start), accelerations(&start));
+    engine.load(&stage(&start, &k1.0, &k1.1, 0.5 * dt));
+    engine.accelerate();
+    let s2 = engine.bodies();
+    let k2 = (velocities(&s2), accelerations(&s2));
+    engine.load(&stage(&start, &k2.0, &k2.1, 0.5 * dt));
+    engine.accelerate();
+    let s3 = engine.bodies();
+    let k3 = (velocities(&s3), accelerations(&s3));
+    engine.load(&stage(&start, &k3.0, &k3.1, dt));
+    engine.accelerate();
+    let s4 = engine.bodies();
+    let k4 = (velocities(&s4), accelerations(&s4));
+    let end = start
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            let dx = weighted(k1.0[i], k2.0[i], k3.0[i], k4.0[i], dt);
+            let dv = weighted(k1.1[i], k2.1[i], k3.1[i], k4.1[i], dt);
+            SimpleBody {
+                x: b.x + dx.0,
+                y: b.y + dx.1,
+                z: b.z + dx.2,
+                vx: b.vx + dv.0,
+                vy: b.vy + dv.1,
+                vz: b.vz + dv.2,
+                ..*b
+            }
+        })
+        .collect::<Vec<_>>();
+    engine.load(&end);
+    engine.confine();
+    engine.accelerate();
+}
+
+impl Display for Integrator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Integrator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Integrator::ALL
+            .iter()
+            .find(|e| e.name() == s)
+            .cloned()
+            .ok_or_else(|| format!("{} is not a valid integrator", s))
+    }
+}
@@ -0,0 +1,52 @@
+#![recursion_limit = "512"]
+
+#[cfg(feature = "openmp")]
+#[macro_use]
+extern crate cpp;
+#[macro_use]
+extern crate lazy_static;
+
+pub use crate::brute_force::BruteForceEngine;
+pub use crate::config::{EngineKind, Mode, SimulationConfig};
+pub use crate::engine::{new_engine, Engine};
+pub use crate::fmm::FmmEngine;
+pub use crate::geometry::SimpleBody;
+pub use crate::integrator::Integrator;
+#[cfg(feature = "mpi")]
+pub use crate::mpi_eng::MpiEngine;
+#[cfg(feature = "openmp")]
+pub use crate::openmp::OpenMpEngine;
+pub use crate::pm::PmEngine;
+pub use crate::pthread::ThreadTreeEngine;
+pub use crate::rayon_eng::RayonEngine;
+pub use crate::seq::TreeEngine;
+
+pub mod benchmark;
+pub mod boundary;
+mod brute_force;
+pub mod checkpoint;
+pub mod config;
+pub mod config_file;
+pub mod diagnostics;
+pub mod driver;
+pub mod engine;
+mod fmm;
+pub mod geometry;
+pub mod global;
+pub mod initial;
+pub mod integrator;
+#[cfg(feature = "mpi")]
+pub mod mpi_eng;
+pub mod multipole;
+#[cfg(feature = "openmp")]
+mod openmp;
+pub mod opening;
+mod pm;
+mod pthread;
+pub mod quad_tree;
+mod rayon_eng;
+mod seq;
+pub mod snapshot;
+pub mod softening;
+pub mod timestep;
+pub mod validate;
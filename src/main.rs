@@ -1,37 +1,79 @@
-#![recursion_limit = "512"]
-
-#[macro_use]
-extern crate cpp;
 #[macro_use]
 extern crate lazy_static;
 
+use std::fs;
+use std::io;
+use std::path::Path;
 use std::process::exit;
 
+#[cfg(feature = "mpi")]
 use mpi::traits::Communicator;
 
-use global::MATCHES;
+use nbody::boundary::Boundary;
+use nbody::checkpoint::Checkpoint;
+use nbody::config::{EngineKind, Mode, SimulationConfig};
+use nbody::driver;
+use nbody::engine::new_engine;
+#[cfg(feature = "mpi")]
+use nbody::mpi_eng::{broadcast_bytes, ROOT, WORLD};
+use nbody::opening::Opening;
+use nbody::timestep::Timestep;
+
+mod cli;
+
+/// number of processes in the MPI world, 1 without the mpi feature.
+#[cfg(feature = "mpi")]
+fn world_size() -> usize {
+    WORLD.size() as usize
+}
+
+#[cfg(not(feature = "mpi"))]
+fn world_size() -> usize {
+    1
+}
 
-use crate::brute_force::start_brute_force;
-use crate::mpi_eng::{start_mpi_child, start_mpi_root};
-use crate::openmp::start_openmp;
-use crate::pthread::start_thread_tree;
-use crate::rayon_eng::start_rayon;
-use crate::seq::start_tree;
+/// whether this is the root process of the MPI world, always true without the mpi feature.
+#[cfg(feature = "mpi")]
+fn is_root() -> bool {
+    WORLD.rank() == ROOT
+}
 
-mod brute_force;
-pub mod geometry;
-pub mod global;
-mod mpi_eng;
-mod openmp;
-mod pthread;
-pub mod quad_tree;
-mod rayon_eng;
-mod seq;
+#[cfg(not(feature = "mpi"))]
+fn is_root() -> bool {
+    true
+}
 
-fn check_thread() {
+/// reads the checkpoint file on the root process and hands its content to every other process.
+#[cfg(feature = "mpi")]
+fn read_restart(path: &Path) -> io::Result<Vec<u8>> {
+    let result = if is_root() { fs::read(path) } else { Ok(Vec::new()) };
+    // the other processes wait for the broadcast even if the root process fails, they then receive nothing
+    let mut bytes = result.as_ref().cloned().unwrap_or_default();
+    broadcast_bytes(&mut bytes);
+    result.map(|_| bytes)
+}
+
+#[cfg(not(feature = "mpi"))]
+fn read_restart(path: &Path) -> io::Result<Vec<u8>> {
+    fs::read(path)
+}
+
+/// the state the run starts from: the checkpoint given with --restart, whose settings replace those of the command line, or the initial conditions of the configuration.
+fn start(config: &mut SimulationConfig) -> io::Result<Checkpoint> {
+    match config.restart.clone() {
+        Some(path) => {
+            let checkpoint = Checkpoint::read(read_restart(&path)?.as_slice())?;
+            checkpoint.apply(config);
+            Ok(checkpoint)
+        }
+        None => Checkpoint::start(config),
+    }
+}
+
+fn check_thread(config: &SimulationConfig) {
     check_mpi();
-    if *global::THREAD > *global::SIZE {
-        if global::WORLD.rank() == global::ROOT {
+    if config.threads > config.size {
+        if is_root() {
             eprintln!("it is not reasonable to have more threads than bodies")
         }
         exit(0);
@@ -39,84 +81,113 @@ fn check_thread() {
 }
 
 fn check_mpi() {
-    if global::WORLD.size() > 1 {
-        if global::WORLD.rank() == global::ROOT {
+    if world_size() > 1 {
+        if is_root() {
             eprintln!("you should not use this engine with multiprocess");
         }
         exit(0);
     }
 }
 
+fn check_world(config: &SimulationConfig) {
+    if world_size() > config.size {
+        if is_root() {
+            println!("it is not reasonable to have more processes than bodies")
+        }
+        exit(0);
+    }
+}
+
 pub fn main() {
-    let engine = MATCHES.as_ref().and_then(|m| m.value_of("engine"));
-    engine.iter().for_each(|e| {
-        if global::WORLD.rank() == global::ROOT {
-            println!("World Size: {}", global::WORLD.size());
-            println!("Engine: {}", e);
-            println!("Scale Factor: {}", *global::SCALE_FACTOR);
-            println!("Canvas: {}x{}", *global::WIDTH, *global::HEIGHT);
-            println!("Size: {}", *global::SIZE);
-            if *e == "openmp" || *e == "pthread" || *e == "mpi_openmp" {
-                println!("Thread: {}", *global::THREAD);
-            }
-            if e.contains("mpi") {
-                println!("Process: {}", global::WORLD.size());
+    let mut config = match cli::parse() {
+        Ok(Some(config)) => config,
+        Ok(None) => return,
+        Err(message) => {
+            if is_root() {
+                eprintln!("{}", message);
             }
+            exit(1);
         }
-    });
-    match engine {
-        Some("tree") => {
-            check_mpi();
-            start_tree();
-        }
-        Some("brute_force") => {
-            check_mpi();
-            start_brute_force();
-        }
-        Some("openmp") => {
-            check_thread();
-            start_openmp();
-        }
-        Some("rayon") => {
-            check_mpi();
-            start_rayon();
-        }
-        Some("rayon_tree") => {
-            check_mpi();
-            start_thread_tree(true);
-        }
-        Some("pthread") => {
-            check_thread();
-            start_thread_tree(false);
-        }
-        Some("mpi_normal") => {
-            let world_size = global::WORLD.size() as usize;
-            if world_size > *global::SIZE {
-                if global::WORLD.rank() == global::ROOT {
-                    println!("it is not reasonable to have more processes than bodies")
-                }
-            } else {
-                if global::WORLD.rank() == global::ROOT {
-                    start_mpi_root(false)
-                } else {
-                    start_mpi_child(false)
-                }
+    };
+    let start = match start(&mut config) {
+        Ok(start) => start,
+        Err(err) => {
+            if is_root() {
+                eprintln!("unable to load the initial conditions: {}", err);
             }
+            exit(1);
         }
-        Some("mpi_openmp") => {
-            let world_size = global::WORLD.size() as usize;
-            if world_size > *global::SIZE {
-                if global::WORLD.rank() == global::ROOT {
-                    println!("it is not reasonable to have more processes than bodies")
-                }
-            } else {
-                if global::WORLD.rank() == global::ROOT {
-                    start_mpi_root(true)
-                } else {
-                    start_mpi_child(true)
-                }
-            }
+    };
+    config.size = start.bodies.len();
+    let e = config.engine;
+    if is_root() {
+        println!("World Size: {}", world_size());
+        println!("Engine: {}", e);
+        if config.dimensions == 3 {
+            println!("Domain: {}x{}x{} (3D)", config.domain_width, config.domain_height, config.domain_depth);
+        } else {
+            println!("Domain: {}x{}", config.domain_width, config.domain_height);
+        }
+        if config.mode == Mode::Display {
+            println!("Window: {}x{}, scale {}", config.width, config.height, config.scale);
+        }
+        println!("Size: {}", config.size);
+        let p = &config.physics;
+        println!(
+            "Physics: g {}, dt {}, radius {}, mass range {}, min size {}, softening {} ({})",
+            p.g, p.dt, p.radius, p.mass_range, p.min_size, p.softening, p.softening_length
+        );
+        match p.opening {
+            Opening::Scale => println!("Opening: scale, limit {}, {}", p.dist_scale_limit, p.expansion),
+            Opening::Classic | Opening::Bmax => println!("Opening: {}, theta {}, {}", p.opening, p.theta, p.expansion),
+            Opening::Relative => println!("Opening: relative, tolerance {}, theta {}, {}", p.force_tolerance, p.theta, p.expansion),
+        }
+        match p.boundary {
+            Boundary::Reflective => println!("Boundary: reflective, restitution {}", p.restitution),
+            Boundary::Periodic => println!("Boundary: periodic, {}", p.periodic_sum),
+            Boundary::Open => println!("Boundary: open"),
+        }
+        println!("Integrator: {}", config.integrator);
+        match config.timestep {
+            Timestep::Fixed => println!("Timestep: fixed"),
+            t => println!(
+                "Timestep: {} ({}, eta {}, {} levels)",
+                t, config.criterion, config.eta, config.timestep_levels
+            ),
+        }
+        match (config.restart.as_ref(), config.input.as_ref()) {
+            (Some(path), _) => println!("Restart: {} (step {})", path.display(), start.step),
+            (None, Some(path)) => println!("Input: {}", path.display()),
+            (None, None) => println!("Seed: {}", config.seed),
+        }
+        if config.mode == Mode::Validate {
+            println!("Reference: {}", config.reference);
+        }
+        if e == EngineKind::Fmm {
+            println!("FMM: order {}, leaf size {}", config.fmm_order, config.leaf_size);
+        }
+        if e == EngineKind::Pm || e == EngineKind::TreePm {
+            println!("Mesh: {} cells", config.mesh_size);
+        }
+        if e.is_threaded() {
+            println!("Thread: {}", config.threads);
+        }
+        if e.is_mpi() {
+            println!("Process: {}", world_size());
+        }
+    }
+    if e.is_mpi() {
+        check_world(&config);
+    } else if e.is_threaded() {
+        check_thread(&config);
+    } else {
+        check_mpi();
+    }
+    let result = new_engine(&config).and_then(|mut engine| driver::run(engine.as_mut(), &config, &start));
+    if let Err(message) = result {
+        if is_root() {
+            eprintln!("{}", message);
         }
-        _ => (),
+        exit(1);
     }
 }
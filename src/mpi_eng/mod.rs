@@ -1,148 +1,174 @@
-use mpi::topology::Communicator;
+use mpi::environment::*;
+use mpi::topology::{Communicator, Process, SystemCommunicator};
 use mpi::traits::Root;
-use sdl2::event::Event;
-use sdl2::pixels::Color;
 
 use mpi_module::*;
 
-use crate::global;
+use crate::boundary::Space;
+use crate::config::SimulationConfig;
+use crate::engine::Engine;
+use crate::geometry::SimpleBody;
+#[cfg(feature = "openmp")]
 use crate::openmp::cpp_module::setup;
 
 mod mpi_module;
 
-fn normal_procedure(s: usize, t: usize, flag: bool, g_data: &mut GlobalData, with_openmp: bool) {
-    g_data.broadcast();
-    if with_openmp {
-        g_data.update_all_openmp(s, t);
-    } else {
-        g_data.update_all(s, t);
-    }
-    if flag {
-        g_data.gather(s, t + 1);
-        //println!("{} will send {} data", global::WORLD.rank(), t - s + 1)
-    } else {
-        g_data.gather(s, t);
-        //println!("{} will send {} data", global::WORLD.rank(), t - s)
-    }
+pub const ROOT: i32 = 0;
+
+// MPI is only initialized once an MPI engine (or the world) is touched, so that the other engines do not depend on it.
+lazy_static! {
+    /// An MPI universe, initialized by calling the initialize() function of the mpi crate.
+    static ref UNIVERSE : Universe = initialize().unwrap();
+
+    ///  An MPI communicator for the entire universe.
+    pub static ref WORLD : SystemCommunicator = UNIVERSE.world();
+
+    pub static ref ROOT_PROC : Process<'static, SystemCommunicator> =  WORLD.process_at_rank(ROOT);
+}
+
+/// copies the bytes of the root process to every other process.
+pub fn broadcast_bytes(bytes: &mut Vec<u8>) {
+    let mut length = bytes.len() as u64;
+    ROOT_PROC.broadcast_into(&mut length);
+    bytes.resize(length as usize, 0);
+    ROOT_PROC.broadcast_into(bytes.as_mut_slice());
+}
+
+/// splits the bodies into one block per process. All blocks have the same length, so the last ones may end in the padding bodies.
+fn block_distribution(global_size: usize, world_size: usize) -> (Vec<usize>, Vec<usize>) {
+    let block_size = global_size.div_ceil(world_size);
+    let starts = (0..world_size).map(|i| i * block_size).collect::<Vec<_>>();
+    let ends = starts.iter().map(|s| s + block_size).collect();
+    (starts, ends)
+}
+
+/// MpiEngine distributes the bodies in blocks over all processes of the MPI world. Every process computes the collisions and accelerations of its own block, the root process gathers the results, advances all bodies and broadcasts them again before the next force evaluation.
+///
+/// with_openmp is only honoured with the openmp feature, new_engine never constructs it otherwise.
+pub struct MpiEngine {
+    g_data: Option<GlobalData>,
+    s: usize,
+    t: usize,
+    space: Space,
+    with_openmp: bool,
 }
 
-fn block_distribution(
-    global_size: usize,
-    world_size: usize,
-) -> (Vec<usize>, Vec<usize>, Vec<bool>) {
-    let block_size = if global_size % world_size > 0 {
-        global_size / world_size + 1
-    } else {
-        global_size / world_size
-    };
-    let mut counter = 0;
-    let mut i = 0;
-    let mut starts = Vec::new();
-    let mut ends = Vec::new();
-    let mut flags = Vec::new();
-    while counter < block_size * world_size {
-        starts.push(counter);
-        let (length, flag) = chunk_size(global_size, world_size, i);
-        ends.push(counter + length);
-        flags.push(flag);
-        counter += block_size;
-        i += 1;
+impl MpiEngine {
+    pub fn new(config: &SimulationConfig, with_openmp: bool) -> Self {
+        #[cfg(feature = "openmp")]
+        if with_openmp {
+            setup(config.threads);
+        }
+        MpiEngine {
+            g_data: None,
+            s: 0,
+            t: 0,
+            space: Space::new(config),
+            with_openmp,
+        }
     }
-    (starts, ends, flags)
 }
 
-fn benchmark_mode(with_openmp: bool) {
-    let mut g_data = GlobalData::new();
-    let world_size = global::WORLD.size() as usize;
-    let (starts, ends, flags) = block_distribution(*global::SIZE, world_size);
-
-    let mut s = 0;
-    let mut t = 0;
-    let mut flag = false;
-
-    global::ROOT_PROC.scatter_into_root(starts.as_slice(), &mut s);
-    global::ROOT_PROC.scatter_into_root(ends.as_slice(), &mut t);
-    global::ROOT_PROC.scatter_into_root(flags.as_slice(), &mut flag);
-    let mut finished = true;
-    let start = std::time::SystemTime::now();
-    normal_procedure(s, t, flag, &mut g_data, with_openmp);
-    global::ROOT_PROC.broadcast_into(&mut finished);
-    let end = std::time::SystemTime::now();
-    println!(
-        "Duration: {} ms",
-        end.duration_since(start).unwrap().as_millis()
-    );
+impl MpiEngine {
+    /// applies f to every body on the root process, the other processes only receive the bodies with the next broadcast.
+    fn each<F: FnMut(&mut SimpleBody)>(&mut self, f: F) {
+        if !self.is_root() {
+            return;
+        }
+        if let Some(g_data) = self.g_data.as_mut() {
+            g_data.each(f);
+        }
+    }
 }
 
-pub fn start_mpi_root(with_openmp: bool) {
-    if with_openmp {
-        setup();
+impl Engine for MpiEngine {
+    fn name(&self) -> &'static str {
+        "MPI"
     }
-    if *global::BENCHMARK {
-        return benchmark_mode(with_openmp);
+
+    /// every process has to call init with a body set of the same size, only the one of the root process is used.
+    fn init(&mut self, bodies: &[SimpleBody]) {
+        self.g_data = Some(GlobalData::new(bodies, self.space));
+        if self.is_root() {
+            let world_size = WORLD.size() as usize;
+            let (starts, ends) = block_distribution(bodies.len(), world_size);
+            ROOT_PROC.scatter_into_root(starts.as_slice(), &mut self.s);
+            ROOT_PROC.scatter_into_root(ends.as_slice(), &mut self.t);
+        } else {
+            ROOT_PROC.scatter_into(&mut self.s);
+            ROOT_PROC.scatter_into(&mut self.t);
+        }
     }
-    
-    let (mut event_pump, mut canvas) = crate::global::init_sdl("MPI");
-
-    let mut g_data = GlobalData::new();
-    let world_size = global::WORLD.size() as usize;
-    let (starts, ends, flags) = block_distribution(*global::SIZE, world_size);
-
-    let mut s = 0;
-    let mut t = 0;
-    let mut flag = false;
-
-    global::ROOT_PROC.scatter_into_root(starts.as_slice(), &mut s);
-    global::ROOT_PROC.scatter_into_root(ends.as_slice(), &mut t);
-    global::ROOT_PROC.scatter_into_root(flags.as_slice(), &mut flag);
-    canvas.set_draw_color(Color::RGB(0, 255, 255));
-    canvas.clear();
-    canvas.present();
-    let mut i = 0;
-    let mut n = 0;
-    let mut start = std::time::SystemTime::now();
-    let mut finished = false;
-    'running: loop {
-        n += 1;
-        canvas
-            .set_scale(*global::SCALE_FACTOR as f32, *global::SCALE_FACTOR as f32)
-            .unwrap();
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-        canvas.clear();
-        i = (i + 1) % 255;
-        canvas.set_draw_color(Color::RGB(i, 64, 255 - i));
-        let points = g_data.to_sdl(&starts, &ends);
-        canvas
-            .draw_points(points.as_slice())
-            .expect("unable to draw points");
-        normal_procedure(s, t, flag, &mut g_data, with_openmp);
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } => {
-                    finished = true;
-                    global::ROOT_PROC.broadcast_into(&mut finished);
-                    break 'running;
-                }
-                _ => {}
+
+    fn collide(&mut self) {
+        let (s, t) = (self.s, self.t);
+        let g_data = self.g_data.as_mut().expect("engine is not initialized");
+        g_data.broadcast_positions();
+        g_data.broadcast_velocities();
+        if self.with_openmp {
+            #[cfg(feature = "openmp")]
+            g_data.collide_openmp(s, t);
+        } else {
+            g_data.collide(s, t);
+        }
+        g_data.gather_velocities(s, t);
+    }
+
+    fn accelerate(&mut self) {
+        let (s, t) = (self.s, self.t);
+        let g_data = self.g_data.as_mut().expect("engine is not initialized");
+        g_data.broadcast_positions();
+        if self.with_openmp {
+            #[cfg(feature = "openmp")]
+            g_data.accelerate_openmp(s, t);
+        } else {
+            g_data.accelerate(s, t);
+        }
+        g_data.gather_accelerations(s, t);
+    }
+
+    fn kick(&mut self, dt: f64) {
+        self.each(|b| b.kick(dt));
+    }
+
+    fn drift(&mut self, dt: f64, with_acceleration: bool) {
+        self.each(|b| b.drift(dt, with_acceleration));
+    }
+
+    fn confine(&mut self) {
+        let space = self.space;
+        self.each(|b| b.confine(&space));
+    }
+
+    /// only meaningful on the root process, which holds the gathered state. The other processes read back no bodies.
+    fn bodies(&self) -> Vec<SimpleBody> {
+        match self.g_data.as_ref() {
+            Some(g_data) if self.is_root() => g_data.to_simple(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn load(&mut self, bodies: &[SimpleBody]) {
+        if let Some(g_data) = self.g_data.as_mut() {
+            for (i, b) in bodies.iter().enumerate() {
+                g_data.set(i, b);
             }
         }
-        canvas.present();
-        global::show_fps(&mut n, &mut start);
-        global::ROOT_PROC.broadcast_into(&mut finished);
     }
-}
 
-pub fn start_mpi_child(with_openmp: bool) {
-    let mut g_data = GlobalData::new();
-    let mut s = 0;
-    let mut t = 0;
-    let mut flag = false;
-    let mut finished = false;
-    global::ROOT_PROC.scatter_into(&mut s);
-    global::ROOT_PROC.scatter_into(&mut t);
-    global::ROOT_PROC.scatter_into(&mut flag);
-    while !finished {
-        normal_procedure(s, t, flag, &mut g_data, with_openmp);
-        global::ROOT_PROC.broadcast_into(&mut finished);
+    fn is_root(&self) -> bool {
+        WORLD.rank() == ROOT
+    }
+
+    fn sync_finished(&mut self, finished: bool) -> bool {
+        let mut finished = finished;
+        ROOT_PROC.broadcast_into(&mut finished);
+        finished
+    }
+
+    fn sync_value(&mut self, value: f64) -> f64 {
+        let mut value = value;
+        ROOT_PROC.broadcast_into(&mut value);
+        value
     }
 }
@@ -1,9 +1,10 @@
-use std::f64::EPSILON;
-
 use mpi::traits::*;
-use rand::Rng;
+use nalgebra::Vector2;
 
-use crate::global::*;
+use crate::boundary::Space;
+use crate::geometry::SimpleBody;
+use crate::mpi_eng::{ROOT, ROOT_PROC, WORLD};
+#[cfg(feature = "openmp")]
 use crate::openmp::cpp_module::*;
 
 pub struct GlobalData {
@@ -14,13 +15,16 @@ pub struct GlobalData {
     gax: Vec<f64>,
     gay: Vec<f64>,
     m: Vec<f64>,
+    size: usize,
+    space: Space,
 }
 
 
 impl GlobalData {
-    pub fn new() -> Self {
+    pub fn new(bodies: &[SimpleBody], space: Space) -> Self {
         let world_size = WORLD.size() as usize;
-        let size = world_size * if *SIZE % world_size > 0 { *SIZE / world_size + 1 } else { *SIZE / world_size };
+        let real_size = bodies.len();
+        let size = world_size * real_size.div_ceil(world_size);
 
         let mut res = GlobalData {
             gx: Vec::with_capacity(size),
@@ -30,6 +34,8 @@ impl GlobalData {
             gax: Vec::with_capacity(size),
             gay: Vec::with_capacity(size),
             m: Vec::with_capacity(size),
+            size: real_size,
+            space,
         };
         if WORLD.rank() != ROOT {
             res.m.resize(size, 0.0);
@@ -41,156 +47,157 @@ impl GlobalData {
             res.gay.resize(size, 0.0);
             res.m.resize(size, 0.0);
         } else {
-            let mut rng = rand::thread_rng();
-            for _ in 0..size {
-                let real_width = *WIDTH / *SCALE_FACTOR;
-                let real_height = *HEIGHT / *SCALE_FACTOR;
-                res.gx.push(rng.gen_range(0.0, real_width - RADIUS));
-                res.gy.push(rng.gen_range(0.0, real_height - RADIUS));
-                res.m.push(rng.gen_range(0.0, MASS_RANGE));
-                res.gax.push(0.0);
-                res.gay.push(0.0);
-                res.gvx.push(0.0);
-                res.gvy.push(0.0);
+            for b in bodies {
+                res.gx.push(b.x);
+                res.gy.push(b.y);
+                res.m.push(b.m);
+                res.gax.push(b.ax);
+                res.gay.push(b.ay);
+                res.gvx.push(b.vx);
+                res.gvy.push(b.vy);
             }
+            // the padding bodies are never updated, they only keep the blocks of every process equally sized
+            res.m.resize(size, 0.0);
+            res.gx.resize(size, 0.0);
+            res.gy.resize(size, 0.0);
+            res.gvx.resize(size, 0.0);
+            res.gvy.resize(size, 0.0);
+            res.gax.resize(size, 0.0);
+            res.gay.resize(size, 0.0);
         }
         ROOT_PROC.broadcast_into(res.m.as_mut_slice());
         res
     }
-    pub fn broadcast(&mut self) {
+    pub fn broadcast_positions(&mut self) {
         ROOT_PROC.broadcast_into(self.gx.as_mut_slice());
         ROOT_PROC.broadcast_into(self.gy.as_mut_slice());
+    }
+    pub fn broadcast_velocities(&mut self) {
         ROOT_PROC.broadcast_into(self.gvx.as_mut_slice());
         ROOT_PROC.broadcast_into(self.gvy.as_mut_slice());
     }
-    fn update_impact(&mut self, k: usize, x_buffer: &mut Vec<f64>, y_buffer: &mut Vec<f64>, iter: usize) {
+    fn collision_impact(&self, k: usize) -> (f64, f64) {
+        let mut res = (0.0, 0.0);
+        for i in 0..self.size {
+            if i == k { continue; }
+            let delta = self.space.separation(Vector2::new(self.gx[k] - self.gx[i], self.gy[k] - self.gy[i]));
+            let (delta_x, delta_y) = (delta.x, delta.y);
+            let dist_squared = delta_x * delta_x + delta_y * delta_y;
+            if dist_squared <= 4.0 * self.space.physics.radius * self.space.physics.radius {
+                let dot = delta_x * (self.gvx[k] - self.gvx[i]) + delta_y * (self.gvy[k] - self.gvy[i]);
+                let scale = 2.0 * self.m[i] / (self.m[i] + self.m[k]) * dot / dist_squared;
+                res.0 -= scale * delta_x;
+                res.1 -= scale * delta_y;
+            }
+        }
+        res
+    }
+    fn gravity_impact(&mut self, k: usize) {
         let mut ax_acc = 0.0;
         let mut ay_acc = 0.0;
-        for i in 0..*SIZE {
-            if i == k { continue; } else {
-                let dist_squared = (self.gx[k] - self.gx[i]) * (self.gx[k] - self.gx[i]) + (self.gy[k] - self.gy[i]) * (self.gy[k] - self.gy[i]);
-                if dist_squared > 4.0 * RADIUS * RADIUS {
-                    let scale = G * self.m[i] / dist_squared / dist_squared.sqrt();
-                    ax_acc += scale * (self.gx[i] - self.gx[k]);
-                    ay_acc += scale * (self.gy[i] - self.gy[k]);
-                } else {
-                    let delta_x = self.gx[k] - self.gx[i];
-                    let delta_y = self.gy[k] - self.gy[i];
-                    let dot = delta_x * (self.gvx[k] - self.gvx[i]) + delta_y * (self.gvy[k] - self.gvy[i]);
-                    let scale = 2.0 * self.m[i] / (self.m[i] + self.m[k]) * dot / dist_squared;
-                    x_buffer[iter] -= scale * delta_x;
-                    y_buffer[iter] -= scale * delta_y;
-                }
+        for i in 0..self.size {
+            if i == k { continue; }
+            let delta = self.space.separation(Vector2::new(self.gx[k] - self.gx[i], self.gy[k] - self.gy[i]));
+            let dist_squared = delta.x * delta.x + delta.y * delta.y;
+            if dist_squared > 4.0 * self.space.physics.radius * self.space.physics.radius {
+                let field = self.space.field(delta, dist_squared);
+                ax_acc -= field.x * self.m[i];
+                ay_acc -= field.y * self.m[i];
             }
         }
         self.gax[k] = ax_acc;
         self.gay[k] = ay_acc;
     }
-    fn update_state(&mut self, i: usize) {
-        let rw: f64 = *WIDTH / *SCALE_FACTOR;
-        let rh: f64 = *HEIGHT / *SCALE_FACTOR;
-        if self.gvx[i].is_nan() {
-            self.gvx[i] = 0.0;
-            self.gx[i] = 0.618 * *WIDTH / *SCALE_FACTOR;
-        }
-        if self.gvy[i].is_nan() {
-            self.gvy[i] = 0.0;
-            self.gy[i] = 0.618 * *HEIGHT / *SCALE_FACTOR;
-        }
-        self.gx[i] += self.gvx[i] * ALPHA + 0.5 * self.gax[i] * ALPHA * ALPHA;
-        self.gy[i] += self.gvy[i] * ALPHA + 0.5 * self.gay[i] * ALPHA * ALPHA;
-        self.gvx[i] += self.gax[i] * ALPHA;
-        self.gvy[i] += self.gay[i] * ALPHA;
-        if self.gx[i] + RADIUS >= rw {
-            self.gx[i] = rw - RADIUS - EPSILON;
-            self.gvx[i] = -0.5 * self.gvx[i];
-        }
-        if self.gx[i] - RADIUS <= 0.0 {
-            self.gx[i] = RADIUS + EPSILON;
-            self.gvx[i] = -0.5 * self.gvx[i];
-        }
-        if self.gy[i] + RADIUS >= rh {
-            self.gy[i] = rh - RADIUS - EPSILON;
-            self.gvy[i] = -0.5 * self.gvy[i];
-        }
-        if self.gy[i] - RADIUS <= 0.0 {
-            self.gy[i] = RADIUS + EPSILON;
-            self.gvy[i] = -0.5 * self.gvy[i];
+    /// applies the collisions to the velocities of the bodies s..t, the padding bodies are skipped.
+    pub fn collide(&mut self, s: usize, t: usize) {
+        let t = t.min(self.size);
+        let impact = (s..t).map(|i| self.collision_impact(i)).collect::<Vec<_>>();
+        for (i, (x, y)) in (s..t).zip(impact) {
+            self.gvx[i] += x;
+            self.gvy[i] += y;
         }
     }
-    pub fn update_all_openmp(&mut self, s: usize, t: usize) {
-        handle_collision(self.m.as_slice(),
-                         self.gvx.as_mut_slice(),
-                         self.gvy.as_mut_slice(),
-                         self.gx.as_mut_slice(),
-                         self.gy.as_mut_slice(), s, t);
-        update_acc(self.m.as_slice(),
-                   self.gx.as_mut_slice(),
-                   self.gy.as_mut_slice(),
-                   self.gax.as_mut_slice(),
-                   self.gay.as_mut_slice(), s, t);
-        update_state(self.gx.as_mut_slice(),
-                     self.gy.as_mut_slice(),
-                     self.gax.as_mut_slice(),
-                     self.gay.as_mut_slice(),
-                     self.gvx.as_mut_slice(),
-                     self.gvy.as_mut_slice(), s, t);
-    }
-    pub fn update_all(&mut self, s: usize, t: usize) {
-        let mut x_buffer = Vec::new();
-        let mut y_buffer = Vec::new();
-        let mut k = 0;
-        x_buffer.resize(t - s, 0.0);
-        y_buffer.resize(t - s, 0.0);
+    /// computes the accelerations of the bodies s..t, the padding bodies are skipped.
+    pub fn accelerate(&mut self, s: usize, t: usize) {
+        let t = t.min(self.size);
         for i in s..t {
-            self.update_impact(i, &mut x_buffer, &mut y_buffer, k);
-            k += 1;
+            self.gravity_impact(i);
         }
-        k = 0;
-        for i in s..t {
-            self.gvx[i] += x_buffer[k];
-            self.gvy[i] += y_buffer[k];
-            self.update_state(i);
-            k += 1;
+    }
+    /// the arrays of the bodies for the openmp kernels, without the padding bodies.
+    #[cfg(feature = "openmp")]
+    fn arrays(&mut self) -> Arrays<'_> {
+        let n = self.size;
+        Arrays {
+            mass: &self.m[..n],
+            x: &self.gx[..n],
+            y: &self.gy[..n],
+            vx: &mut self.gvx[..n],
+            vy: &mut self.gvy[..n],
+            ax: &mut self.gax[..n],
+            ay: &mut self.gay[..n],
+            space: &self.space,
         }
     }
-    pub fn to_sdl(&self, starts: &Vec<usize>, ends: &Vec<usize>) -> Vec<sdl2::rect::Point> {
-        let mut a = Vec::new();
-        for i in 0..starts.len() {
-            for j in starts[i]..ends[i] {
-                a.push(sdl2::rect::Point::new(self.gx[j] as i32, self.gy[j] as i32))
-            }
+    #[cfg(feature = "openmp")]
+    pub fn collide_openmp(&mut self, s: usize, t: usize) {
+        let t = t.min(self.size);
+        handle_collision(&mut self.arrays(), s, t);
+    }
+    #[cfg(feature = "openmp")]
+    pub fn accelerate_openmp(&mut self, s: usize, t: usize) {
+        let t = t.min(self.size);
+        update_acc(&mut self.arrays(), s, t);
+    }
+    pub fn gather_velocities(&mut self, s: usize, t: usize) {
+        gather(&mut self.gvx, s, t);
+        gather(&mut self.gvy, s, t);
+    }
+    pub fn gather_accelerations(&mut self, s: usize, t: usize) {
+        gather(&mut self.gax, s, t);
+        gather(&mut self.gay, s, t);
+    }
+    /// applies f to every body, only meaningful on the root process.
+    pub fn each<F: FnMut(&mut SimpleBody)>(&mut self, mut f: F) {
+        for i in 0..self.size {
+            let mut b = self.body(i);
+            f(&mut b);
+            self.set(i, &b);
         }
-        a
     }
-
-    pub fn gather(&mut self, s: usize, t: usize) {
-        let mut buffer = Vec::with_capacity(t - s);
-        buffer.resize(t - s, 0.0);
-        if WORLD.rank() == ROOT {
-            buffer.copy_from_slice(self.gx[s..t].as_ref());
-            ROOT_PROC.gather_into_root(buffer.as_slice(), self.gx.as_mut_slice());
-            buffer.copy_from_slice(self.gy[s..t].as_ref());
-            ROOT_PROC.gather_into_root(buffer.as_slice(), self.gy.as_mut_slice());
-            buffer.copy_from_slice(self.gvx[s..t].as_ref());
-            ROOT_PROC.gather_into_root(buffer.as_slice(), self.gvx.as_mut_slice());
-            buffer.copy_from_slice(self.gvy[s..t].as_ref());
-            ROOT_PROC.gather_into_root(buffer.as_slice(), self.gvy.as_mut_slice());
-        } else {
-            buffer.copy_from_slice(self.gx[s..t].as_ref());
-            ROOT_PROC.gather_into(buffer.as_slice());
-            buffer.copy_from_slice(self.gy[s..t].as_ref());
-            ROOT_PROC.gather_into(buffer.as_slice());
-            buffer.copy_from_slice(self.gvx[s..t].as_ref());
-            ROOT_PROC.gather_into(buffer.as_slice());
-            buffer.copy_from_slice(self.gvy[s..t].as_ref());
-            ROOT_PROC.gather_into(buffer.as_slice());
+    fn body(&self, i: usize) -> SimpleBody {
+        SimpleBody {
+            x: self.gx[i],
+            y: self.gy[i],
+            m: self.m[i],
+            vx: self.gvx[i],
+            vy: self.gvy[i],
+            ax: self.gax[i],
+            ay: self.gay[i],
+            z: 0.0,
+            vz: 0.0,
+            az: 0.0,
         }
     }
+    pub fn set(&mut self, i: usize, b: &SimpleBody) {
+        self.gx[i] = b.x;
+        self.gy[i] = b.y;
+        self.gvx[i] = b.vx;
+        self.gvy[i] = b.vy;
+        self.gax[i] = b.ax;
+        self.gay[i] = b.ay;
+    }
+    pub fn to_simple(&self) -> Vec<SimpleBody> {
+        (0..self.size).map(|i| self.body(i)).collect()
+    }
 }
 
-pub fn chunk_size(total: usize, group: usize, kth: usize) -> (usize, bool) {
-    let a = total - kth;
-    let flag = kth >= total % group && total % group > 0;
-    if a % group > 0 { (a / group + 1, flag) } else { (a / group, flag) }
-}
\ No newline at end of file
+/// collects the block s..t of every process into values on the root process. All blocks have the same length, the padding bodies fill up the last ones.
+fn gather(values: &mut [f64], s: usize, t: usize) {
+    let buffer = values[s..t].to_vec();
+    if WORLD.rank() == ROOT {
+        ROOT_PROC.gather_into_root(buffer.as_slice(), values);
+    } else {
+        ROOT_PROC.gather_into(buffer.as_slice());
+    }
+}
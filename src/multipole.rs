@@ -0,0 +1,141 @@
+use std::fmt::{Display, Error, Formatter};
+use std::ops::AddAssign;
+use std::str::FromStr;
+
+use nalgebra::{SMatrix, SVector};
+
+/// Expansion is the order of the multipole expansion a tree node is approximated with once the opening criterion accepts it. Higher orders cost a few more operations per node but stay accurate at larger opening angles.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Expansion {
+    /// the mass at the centre of mass only.
+    Monopole,
+    /// the monopole and the quadrupole moment about the centre of mass, the dipole vanishes there.
+    Quadrupole,
+    /// the quadrupole expansion and the octupole moment.
+    Octupole,
+}
+
+impl Expansion {
+    pub const ALL: [Expansion; 3] = [Expansion::Monopole, Expansion::Quadrupole, Expansion::Octupole];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Expansion::Monopole => "monopole",
+            Expansion::Quadrupole => "quadrupole",
+            Expansion::Octupole => "octupole",
+        }
+    }
+}
+
+impl Display for Expansion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Expansion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Expansion::ALL
+            .iter()
+            .find(|e| e.name() == s)
+            .cloned()
+            .ok_or_else(|| format!("{} is not a valid expansion", s))
+    }
+}
+
+/// Moments are the sums of m * x_i * x_j and m * x_i * x_j * x_k over the bodies of a tree node, about the origin so that bodies can be added one by one and the moments of the children summed. The moments about the centre of mass are derived from them together with the mass and the centre of mass.
+#[derive(Debug, Copy, Clone)]
+pub struct Moments<const D: usize> {
+    second: SMatrix<f64, D, D>,
+    /// third[i] holds the sums of m * x_i * x_j * x_k
+    third: [SMatrix<f64, D, D>; D],
+}
+
+impl<const D: usize> Default for Moments<D> {
+    fn default() -> Self {
+        Moments {
+            second: SMatrix::zeros(),
+            third: [SMatrix::zeros(); D],
+        }
+    }
+}
+
+impl<const D: usize> AddAssign for Moments<D> {
+    fn add_assign(&mut self, other: Self) {
+        self.second += other.second;
+        for (a, b) in self.third.iter_mut().zip(other.third.iter()) {
+            *a += b;
+        }
+    }
+}
+
+impl<const D: usize> Moments<D> {
+    /// adds a body of mass m at x, only the moments the expansion needs are kept.
+    pub fn add(&mut self, x: &SVector<f64, D>, m: f64, expansion: Expansion) {
+        if expansion == Expansion::Monopole {
+            return;
+        }
+        let outer = x * x.transpose() * m;
+        self.second += outer;
+        if expansion == Expansion::Octupole {
+            for i in 0..D {
+                self.third[i] += outer * x[i];
+            }
+        }
+    }
+
+    /// the moments about the centre of mass c of the total mass m: the second one and, for the octupole, the third one.
+    fn central(&self, m: f64, c: &SVector<f64, D>, expansion: Expansion) -> (SMatrix<f64, D, D>, [SMatrix<f64, D, D>; D]) {
+        let second = self.second - c * c.transpose() * m;
+        let mut third = [SMatrix::zeros(); D];
+        if expansion == Expansion::Octupole {
+            for (i, res) in third.iter_mut().enumerate() {
+                *res = SMatrix::from_fn(|j, k| {
+                    self.third[i][(j, k)] - c[i] * self.second[(j, k)] - c[j] * self.second[(i, k)] - c[k] * self.second[(i, j)]
+                        + 2.0 * m * c[i] * c[j] * c[k]
+                });
+            }
+        }
+        (second, third)
+    }
+
+    /// the acceleration at r from the centre of mass c of the mass m beyond the monopole one, for a unit gravitational constant.
+    pub fn field(&self, m: f64, c: &SVector<f64, D>, r: &SVector<f64, D>, expansion: Expansion) -> SVector<f64, D> {
+        if expansion == Expansion::Monopole {
+            return SVector::zeros();
+        }
+        let (second, third) = self.central(m, c, expansion);
+        let r2 = r.norm_squared();
+        let inv5 = 1.0 / (r2 * r2 * r2.sqrt());
+        let inv7 = inv5 / r2;
+        let pr = second * r;
+        let mut res = pr * (3.0 * inv5) + r * ((1.5 * second.trace() - 7.5 * r.dot(&pr) / r2) * inv5);
+        if expansion == Expansion::Octupole {
+            let t = SVector::<f64, D>::from_fn(|i, _| r.dot(&(third[i] * r)));
+            let v = SVector::<f64, D>::from_fn(|i, _| third[i].trace());
+            let (trrr, vr) = (r.dot(&t), v.dot(r));
+            res += (t * 15.0 - r * (6.0 * vr) - v * (3.0 * r2)) * (0.5 * inv7);
+            res -= r * (3.5 * (5.0 * trrr - 3.0 * r2 * vr) * inv7 / r2);
+        }
+        res
+    }
+
+    /// the potential at r from the centre of mass c of the mass m beyond the monopole one, for a unit gravitational constant.
+    pub fn potential(&self, m: f64, c: &SVector<f64, D>, r: &SVector<f64, D>, expansion: Expansion) -> f64 {
+        if expansion == Expansion::Monopole {
+            return 0.0;
+        }
+        let (second, third) = self.central(m, c, expansion);
+        let r2 = r.norm_squared();
+        let inv5 = 1.0 / (r2 * r2 * r2.sqrt());
+        let mut res = -(3.0 * r.dot(&(second * r)) - r2 * second.trace()) * 0.5 * inv5;
+        if expansion == Expansion::Octupole {
+            let trrr = (0..D).map(|i| r[i] * r.dot(&(third[i] * r))).sum::<f64>();
+            let vr = (0..D).map(|i| r[i] * third[i].trace()).sum::<f64>();
+            res -= (5.0 * trrr - 3.0 * r2 * vr) * 0.5 * inv5 / r2;
+        }
+        res
+    }
+}
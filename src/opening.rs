@@ -0,0 +1,84 @@
+use std::fmt::{Display, Error, Formatter};
+use std::str::FromStr;
+
+use nalgebra::SVector;
+
+use crate::geometry::Square;
+use crate::global::Physics;
+
+/// Opening is the multipole acceptance criterion of the Barnes-Hut trees: it decides whether a node is far enough from a body to be approximated by its centre of mass, or has to be opened and its children visited.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Opening {
+    /// size^2 / (2 * distance^2) below dist_scale_limit, where size is the diagonal of the node.
+    Scale,
+    /// the classic criterion of Barnes and Hut, the longest side of the node over the distance to its centre of mass below theta.
+    Classic,
+    /// the criterion of Salmon and Warren, the distance from the centre of mass to the farthest corner of the node over the distance to the body below theta.
+    Bmax,
+    /// the relative criterion of Gadget, the estimated error G * M * l^2 / r^4 of the approximation below force_tolerance times the last acceleration of the body. The body must also lie outside the node enlarged by a fifth, and classic is used for bodies without an acceleration yet.
+    Relative,
+}
+
+impl Opening {
+    pub const ALL: [Opening; 4] = [Opening::Scale, Opening::Classic, Opening::Bmax, Opening::Relative];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Opening::Scale => "scale",
+            Opening::Classic => "classic",
+            Opening::Bmax => "bmax",
+            Opening::Relative => "relative",
+        }
+    }
+
+    /// whether the node of the given region, mass and centre of mass can be approximated for a body at the separation delta from the centre of mass. acceleration is the magnitude of the last acceleration of the body, 0 if it has none.
+    pub fn accepts<const D: usize>(
+        &self,
+        physics: &Physics,
+        region: &Square<D>,
+        center: &SVector<f64, D>,
+        mass: f64,
+        delta: &SVector<f64, D>,
+        acceleration: f64,
+    ) -> bool {
+        let range = region.0 - region.1;
+        let dist = delta.norm_squared();
+        let theta2 = physics.theta * physics.theta;
+        match self {
+            Opening::Scale => range.norm_squared() / dist / 2.0 < physics.dist_scale_limit,
+            Opening::Relative if acceleration > 0.0 => {
+                // the body as seen from the centre of the node
+                let offset = *center - delta - (region.0 + region.1) / 2.0;
+                let outside = (0..D).any(|i| offset[i].abs() > 0.6 * range[i]);
+                let side = range.max();
+                outside && physics.g * mass * side * side < physics.force_tolerance * acceleration * dist * dist
+            }
+            Opening::Classic | Opening::Relative => {
+                let side = range.max();
+                side * side < theta2 * dist
+            }
+            Opening::Bmax => {
+                let bmax = SVector::<f64, D>::from_fn(|i, _| (region.0[i] - center[i]).max(center[i] - region.1[i]));
+                bmax.norm_squared() < theta2 * dist
+            }
+        }
+    }
+}
+
+impl Display for Opening {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Opening {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Opening::ALL
+            .iter()
+            .find(|e| e.name() == s)
+            .cloned()
+            .ok_or_else(|| format!("{} is not a valid opening criterion", s))
+    }
+}
@@ -1,35 +1,78 @@
-use std::f64::EPSILON;
-
 use cpp;
 
-use crate::global::*;
+use crate::boundary::Space;
 
 cpp! {{
+#include <algorithm>
 #include <cmath>
 #include <omp.h>
 #include <vector>
-#define dist_squared(i, j)  ((x_pos[(i)] - x_pos[(j)]) * (x_pos[(i)] - x_pos[(j)]) + (y_pos[(i)] - y_pos[(j)]) * (y_pos[(i)] - y_pos[(j)]))
+// the nearest periodic image of the separation d, period is 0 without periodic boundaries, see Space::separation
+static double nearest(double d, double period) {
+    return period > 0 ? d - period * round(d / period) : d;
+}
+#define delta_x(i, j) (nearest(x_pos[(i)] - x_pos[(j)], period_x))
+#define delta_y(i, j) (nearest(y_pos[(i)] - y_pos[(j)], period_y))
+#define dist_squared(i, j)  (delta_x(i, j) * delta_x(i, j) + delta_y(i, j) * delta_y(i, j))
 #define check(i, j) (dist_squared(i, j) <= 4.0 * (radius) * (radius))
 }}
 
 cpp! {{
-#define cross(i, j) (((x_pos[(i)] - x_pos[(j)]) * (vx[(i)] - vx[(j)])) + ((y_pos[(i)] - y_pos[(j)]) * (vy[(i)] - vy[(j)])))
+#define cross(i, j) ((delta_x(i, j) * (vx[(i)] - vx[(j)])) + (delta_y(i, j) * (vy[(i)] - vy[(j)])))
 #define coefficient(i, j) (2.0 * mass[(j)] / (mass[(i)] + mass[(j)]))
 }}
 
 cpp! {{
-#define update_vx(i, j) (impact_x[(i) - from] -= coefficient(i, j) * cross(i, j) / dist_squared(i, j) * (x_pos[(i)] - x_pos[(j)]))
-#define update_vy(i, j) (impact_y[(i) - from] -= coefficient(i, j) * cross(i, j) / dist_squared(i, j) * (y_pos[(i)] - y_pos[(j)]))
+#define update_vx(i, j) (impact_x[(i) - from] -= coefficient(i, j) * cross(i, j) / dist_squared(i, j) * delta_x(i, j))
+#define update_vy(i, j) (impact_y[(i) - from] -= coefficient(i, j) * cross(i, j) / dist_squared(i, j) * delta_y(i, j))
 #define update_v(i, j) ((update_vx((i), (j))), (update_vy((i), (j))))
 }}
 
 cpp! {{
-#define scale(i, j)  (g * mass[(j)] / (dist_squared((i), (j)) * sqrt(dist_squared((i), (j)))))
-#define update_a(i, j) ((ax[i] = scale(i, j) * (x_pos[j] - x_pos[i])), (ay[i] = scale(i, j) * (y_pos[j] - y_pos[i])))
+// 1 / r^3 softened with length h, the kinds are the codes of Softening, see Softening::force
+static double softened(double r2, int kind, double h) {
+    if (kind == 1) {
+        double s = r2 + h * h;
+        return 1.0 / (s * sqrt(s));
+    }
+    if (kind == 0 || r2 >= h * h) return 1.0 / (r2 * sqrt(r2));
+    double u = sqrt(r2) / h;
+    double f;
+    if (kind == 2) {
+        f = u < 0.5 ? 32.0 / 3.0 + u * u * (32.0 * u - 38.4)
+                    : 64.0 / 3.0 - 48.0 * u + 38.4 * u * u - 32.0 / 3.0 * u * u * u - 1.0 / 15.0 / (u * u * u);
+    } else {
+        f = 14.0 + u * u * (-84.0 + u * (140.0 + u * (-90.0 + 21.0 * u)));
+    }
+    return f / (h * h * h);
+}
+
+// the Ewald field correction at the nearest image separation (dx, dy), interpolated like Ewald::correction
+static void ewald_correction(const double *table, size_t n, double step_x, double step_y,
+                             double dx, double dy, double *cx, double *cy) {
+    double u = fabs(dx) / step_x;
+    double v = fabs(dy) / step_y;
+    size_t i = std::min((size_t) u, n - 1);
+    size_t j = std::min((size_t) v, n - 1);
+    double fu = u - i;
+    double fv = v - j;
+    double res[2];
+    for (size_t k = 0; k < 2; ++k) {
+        res[k] = (1.0 - fu) * ((1.0 - fv) * table[3 * (i * (n + 1) + j) + k] + fv * table[3 * (i * (n + 1) + j + 1) + k])
+               + fu * ((1.0 - fv) * table[3 * ((i + 1) * (n + 1) + j) + k] + fv * table[3 * ((i + 1) * (n + 1) + j + 1) + k]);
+    }
+    *cx = dx < 0.0 ? -res[0] : res[0];
+    *cy = dy < 0.0 ? -res[1] : res[1];
+}
+}}
+
+cpp! {{
+#define scale(i, j)  (g * mass[(j)] * softened(dist_squared((i), (j)), softening, softening_length))
+#define update_a(i, j) ((ax[i] -= scale(i, j) * delta_x(i, j)), (ay[i] -= scale(i, j) * delta_y(i, j)))
 }}
 
-pub fn setup() {
-    let thn = *THREAD as i32;
+pub fn setup(threads: usize) {
+    let thn = threads as i32;
     unsafe {
         cpp!([thn as "int"] -> () as "void" {
             omp_set_num_threads(thn);
@@ -38,26 +81,36 @@ pub fn setup() {
 }
 
 
-pub fn handle_collision(mass: &[f64],
-                        vx: &mut [f64],
-                        vy: &mut [f64],
-                        x_pos: &mut [f64],
-                        y_pos: &mut [f64],
-                        from: usize,
-                        to: usize,
-) {
+/// Arrays are the bodies the kernels work on as a structure of arrays, the masses giving the number of bodies, and the space they move in.
+pub struct Arrays<'a> {
+    pub mass: &'a [f64],
+    pub x: &'a [f64],
+    pub y: &'a [f64],
+    pub vx: &'a mut [f64],
+    pub vy: &'a mut [f64],
+    pub ax: &'a mut [f64],
+    pub ay: &'a mut [f64],
+    pub space: &'a Space,
+}
+
+/// applies the collisions with all the bodies to the velocities of the bodies from..to.
+pub fn handle_collision(bodies: &mut Arrays, from: usize, to: usize) {
     unsafe {
-        let size = *SIZE;
-        let radius = RADIUS;
-        let mass = mass.as_ptr();
-        let vx = vx.as_mut_ptr();
-        let vy = vy.as_mut_ptr();
-        let x_pos = x_pos.as_mut_ptr();
-        let y_pos = y_pos.as_mut_ptr();
+        let space = bodies.space;
+        let size = bodies.mass.len();
+        let radius = space.physics.radius;
+        let period = space.period::<2>();
+        let (period_x, period_y) = (period.x, period.y);
+        let mass = bodies.mass.as_ptr();
+        let vx = bodies.vx.as_mut_ptr();
+        let vy = bodies.vy.as_mut_ptr();
+        let x_pos = bodies.x.as_ptr();
+        let y_pos = bodies.y.as_ptr();
         cpp!(
             [mass as "const double *",
             size as "size_t", radius as "double",
-            x_pos as "double *", y_pos as "double *",
+            period_x as "double", period_y as "double",
+            x_pos as "const double *", y_pos as "const double *",
             vx as "double *", vy as "double *", from as "size_t", to as "size_t"] -> () as "void" {
                 std::vector<double> impact_x(to - from, 0);
                 std::vector<double> impact_y(to - from, 0);
@@ -79,83 +132,48 @@ pub fn handle_collision(mass: &[f64],
 }
 
 
-pub fn update_state(x_pos: &mut [f64],
-                    y_pos: &mut [f64],
-                    ax: &mut [f64],
-                    ay: &mut [f64],
-                    vx: &mut [f64],
-                    vy: &mut [f64],
-                    from: usize,
-                    to: usize,
-) {
-    unsafe {
-        let radius = RADIUS;
-        let width = *WIDTH / *SCALE_FACTOR;
-        let height = *HEIGHT / *SCALE_FACTOR;
-        let eps = EPSILON;
-        let ax = ax.as_mut_ptr();
-        let ay = ay.as_mut_ptr();
-        let vx = vx.as_mut_ptr();
-        let vy = vy.as_mut_ptr();
-        let x_pos = x_pos.as_mut_ptr();
-        let y_pos = y_pos.as_mut_ptr();
-        let alpha = ALPHA;
-        cpp!(
-            [radius as "double", alpha as "double", width as "double", height as "double",
-            x_pos as "double *", y_pos as "double *", eps as "double", from as "size_t", to as "size_t",
-            ax as "double *", ay as "double *", vx as "double *", vy as "double *"] -> () as "void" {
-                #pragma omp parallel for schedule(guided)
-                for (size_t i = from; i < to; ++i) {
-                    if (std::isnan(vx[i])) {
-                        vx[i] = 0;
-                        x_pos[i] = 0.618 * width;
-                    }
-                    if (std::isnan(vy[i])) {
-                        vy[i] = 0;
-                        y_pos[i] = 0.618 * height;
-                    }
-                    vx[i] += ax[i] * alpha;
-                    vy[i] += ay[i] * alpha;
-                    x_pos[i] += vx[i] * alpha + 0.5 * ax[i] * alpha * alpha;
-                    y_pos[i] += vy[i] * alpha + 0.5 * ay[i] * alpha * alpha;
-                    if (x_pos[(i)] + radius >= width) { x_pos[(i)] = width - radius - eps; vx[(i)] = -0.5 * vx[(i)]; }
-                    if (x_pos[(i)] - radius <= 0) { x_pos[(i)] = radius + eps;  vx[(i)] = -0.5 * vx[(i)]; }
-                    if (y_pos[(i)] + radius >= height) { y_pos[(i)] = height - radius - eps; vy[(i)] = -0.5 * vy[(i)]; }
-                    if (y_pos[(i)] - radius <= 0) { y_pos[(i)] = radius + eps; vy[(i)] = -0.5 * vy[(i)]; }
-                }
-            }
-        )
-    }
-}
-
-pub fn update_acc(mass: &[f64],
-                  x_pos: &mut [f64],
-                  y_pos: &mut [f64],
-                  ax: &mut [f64],
-                  ay: &mut [f64],
-                  from: usize,
-                  to: usize,
-) {
+/// computes the accelerations of the bodies from..to from all the bodies.
+pub fn update_acc(bodies: &mut Arrays, from: usize, to: usize) {
     unsafe {
-        let size = *SIZE;
-        let radius = RADIUS;
-        let g = G;
-        let mass = mass.as_ptr();
-        let ax = ax.as_mut_ptr();
-        let ay = ay.as_mut_ptr();
-        let x_pos = x_pos.as_mut_ptr();
-        let y_pos = y_pos.as_mut_ptr();
+        let space = bodies.space;
+        let size = bodies.mass.len();
+        let radius = space.physics.radius;
+        let period = space.period::<2>();
+        let (period_x, period_y) = (period.x, period.y);
+        let g = space.physics.g;
+        let softening = space.physics.softening.code();
+        let softening_length = space.physics.softening_length;
+        let (ewald, ewald_n, step_x, step_y) = match space.ewald() {
+            Some(table) => (table.values.as_ptr(), table.n, table.step.0, table.step.1),
+            None => (std::ptr::null(), 0, 0.0, 0.0),
+        };
+        let mass = bodies.mass.as_ptr();
+        let ax = bodies.ax.as_mut_ptr();
+        let ay = bodies.ay.as_mut_ptr();
+        let x_pos = bodies.x.as_ptr();
+        let y_pos = bodies.y.as_ptr();
         cpp!(
             [mass as "const double *",
             size as "size_t", radius as "double", g as "double",
-            x_pos as "double *", y_pos as "double *", from as "size_t", to as "size_t",
+            softening as "int", softening_length as "double",
+            period_x as "double", period_y as "double",
+            ewald as "const double *", ewald_n as "size_t", step_x as "double", step_y as "double",
+            x_pos as "const double *", y_pos as "const double *", from as "size_t", to as "size_t",
             ax as "double *", ay as "double *"] -> () as "void" {
                 #pragma omp parallel for schedule(guided)
                 for (size_t i = from; i < to; ++i) {
+                    ax[i] = 0;
+                    ay[i] = 0;
                     for (size_t j = 0; j < size; ++j) {
                         if (check(i, j)) {continue; }
                         else {
                             update_a(i, j);
+                            if (ewald) {
+                                double cx, cy;
+                                ewald_correction(ewald, ewald_n, step_x, step_y, delta_x(i, j), delta_y(i, j), &cx, &cy);
+                                ax[i] -= g * mass[j] * cx;
+                                ay[i] -= g * mass[j] * cy;
+                            }
                         }
                     }
                 }
@@ -1,107 +1,133 @@
-use rand::Rng;
-use sdl2::event::Event;
-use sdl2::pixels::Color;
-use sdl2::rect::Point;
-
-use crate::global;
-use crate::global::MASS_RANGE;
-use crate::openmp::cpp_module::{handle_collision, setup, update_acc, update_state};
+use crate::boundary::Space;
+use crate::config::SimulationConfig;
+use crate::engine::Engine;
+use crate::geometry::SimpleBody;
+use crate::openmp::cpp_module::{handle_collision, setup, update_acc, Arrays};
 
 pub mod cpp_module;
 
-fn to_sdl(x: &[f64], y: &[f64]) -> Vec<Point> {
-    let mut a = Vec::new();
-    for i in 0..x.len() {
-        a.push(Point::new(x[i] as i32, y[i] as i32))
-    }
-    a
+/// OpenMpEngine stores the bodies as a structure of arrays and computes every pairwise interaction with the OpenMP kernels in cpp_module.
+pub struct OpenMpEngine {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    vx: Vec<f64>,
+    vy: Vec<f64>,
+    ax: Vec<f64>,
+    ay: Vec<f64>,
+    m: Vec<f64>,
+    space: Space,
 }
 
-fn benchmark_mode() {
-    let mut x = Vec::new();
-    let mut y = Vec::new();
-    let mut vx = Vec::new();
-    let mut vy = Vec::new();
-    let mut ax = Vec::new();
-    let mut ay = Vec::new();
-    let mut m = Vec::new();
-    let real_width = *global::WIDTH / *global::SCALE_FACTOR;
-    let real_height = *global::HEIGHT / *global::SCALE_FACTOR;
-
-    let mut rng = rand::thread_rng();
-    for _ in 0..*global::SIZE {
-        x.push(rng.gen_range(0.0, real_width - global::RADIUS));
-        y.push(rng.gen_range(0.0, real_height - global::RADIUS));
-        m.push(rng.gen_range(0.0, MASS_RANGE));
-        ax.push(0.0);
-        ay.push(0.0);
-        vx.push(0.0);
-        vy.push(0.0);
+impl OpenMpEngine {
+    pub fn new(config: &SimulationConfig) -> Self {
+        setup(config.threads);
+        OpenMpEngine {
+            x: Vec::new(),
+            y: Vec::new(),
+            vx: Vec::new(),
+            vy: Vec::new(),
+            ax: Vec::new(),
+            ay: Vec::new(),
+            m: Vec::new(),
+            space: Space::new(config),
+        }
     }
-    let start = std::time::SystemTime::now();
-    handle_collision(&m, &mut vx, &mut vy, &mut x, &mut y, 0, *global::SIZE);
-    update_acc(&m, &mut x, &mut y, &mut ax, &mut ay, 0, *global::SIZE);
-    update_state(&mut x, &mut y, &mut ax, &mut ay, &mut vx, &mut vy, 0, *global::SIZE);
-    let end = std::time::SystemTime::now();
-    println!("Duration: {} ms", end.duration_since(start).unwrap().as_millis());
 }
 
-pub fn start_openmp() {
-    setup();
-    if *global::BENCHMARK {
-        return benchmark_mode();
+impl OpenMpEngine {
+    /// applies f to every body, the arrays are updated in place.
+    fn each<F: FnMut(&mut SimpleBody)>(&mut self, mut f: F) {
+        for i in 0..self.m.len() {
+            let mut b = self.body(i);
+            f(&mut b);
+            self.set(i, &b);
+        }
     }
-    let (mut event_pump, mut canvas) = crate::global::init_sdl("OpenMP");
-
-    let mut x = Vec::new();
-    let mut y = Vec::new();
-    let mut vx = Vec::new();
-    let mut vy = Vec::new();
-    let mut ax = Vec::new();
-    let mut ay = Vec::new();
-    let mut m = Vec::new();
-    let real_width = *global::WIDTH / *global::SCALE_FACTOR;
-    let real_height = *global::HEIGHT / *global::SCALE_FACTOR;
-
-    let mut rng = rand::thread_rng();
-    for _ in 0..*global::SIZE {
-        x.push(rng.gen_range(0.0, real_width - global::RADIUS));
-        y.push(rng.gen_range(0.0, real_height - global::RADIUS));
-        m.push(rng.gen_range(0.0, MASS_RANGE));
-        ax.push(0.0);
-        ay.push(0.0);
-        vx.push(0.0);
-        vy.push(0.0);
+
+    /// the arrays of the bodies for the kernels.
+    fn arrays(&mut self) -> Arrays<'_> {
+        Arrays {
+            mass: &self.m,
+            x: &self.x,
+            y: &self.y,
+            vx: &mut self.vx,
+            vy: &mut self.vy,
+            ax: &mut self.ax,
+            ay: &mut self.ay,
+            space: &self.space,
+        }
     }
 
-    canvas.set_draw_color(Color::RGB(0, 255, 255));
-    canvas.clear();
-    canvas.present();
-    let mut i = 0;
-    let mut n = 0;
-    let mut start = std::time::SystemTime::now();
-    'running: loop {
-        n += 1;
-        canvas.set_scale(*global::SCALE_FACTOR as f32, *global::SCALE_FACTOR as f32).unwrap();
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-        canvas.clear();
-        i = (i + 1) % 255;
-        canvas.set_draw_color(Color::RGB(i, 64, 255 - i));
-        let points = to_sdl(x.as_slice(), y.as_slice());
-        canvas.draw_points(points.as_slice()).expect("unable to draw points");
-        handle_collision(&m, &mut vx, &mut vy, &mut x, &mut y, 0, *global::SIZE);
-        update_acc(&m, &mut x, &mut y, &mut ax, &mut ay, 0, *global::SIZE);
-        update_state(&mut x, &mut y, &mut ax, &mut ay, &mut vx, &mut vy, 0, *global::SIZE);
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } => {
-                    break 'running;
-                }
-                _ => {}
-            }
+    fn body(&self, i: usize) -> SimpleBody {
+        SimpleBody {
+            x: self.x[i],
+            y: self.y[i],
+            m: self.m[i],
+            vx: self.vx[i],
+            vy: self.vy[i],
+            ax: self.ax[i],
+            ay: self.ay[i],
+            z: 0.0,
+            vz: 0.0,
+            az: 0.0,
         }
-        canvas.present();
-        global::show_fps(&mut n, &mut start);
+    }
+
+    fn set(&mut self, i: usize, b: &SimpleBody) {
+        self.x[i] = b.x;
+        self.y[i] = b.y;
+        self.vx[i] = b.vx;
+        self.vy[i] = b.vy;
+        self.ax[i] = b.ax;
+        self.ay[i] = b.ay;
     }
 }
 
+impl Engine for OpenMpEngine {
+    fn name(&self) -> &'static str {
+        "OpenMP"
+    }
+
+    fn init(&mut self, bodies: &[SimpleBody]) {
+        self.x = bodies.iter().map(|b| b.x).collect();
+        self.y = bodies.iter().map(|b| b.y).collect();
+        self.vx = bodies.iter().map(|b| b.vx).collect();
+        self.vy = bodies.iter().map(|b| b.vy).collect();
+        self.ax = bodies.iter().map(|b| b.ax).collect();
+        self.ay = bodies.iter().map(|b| b.ay).collect();
+        self.m = bodies.iter().map(|b| b.m).collect();
+    }
+
+    fn collide(&mut self) {
+        let size = self.m.len();
+        handle_collision(&mut self.arrays(), 0, size);
+    }
+
+    fn accelerate(&mut self) {
+        let size = self.m.len();
+        update_acc(&mut self.arrays(), 0, size);
+    }
+
+    fn kick(&mut self, dt: f64) {
+        self.each(|b| b.kick(dt));
+    }
+
+    fn drift(&mut self, dt: f64, with_acceleration: bool) {
+        self.each(|b| b.drift(dt, with_acceleration));
+    }
+
+    fn confine(&mut self) {
+        let space = self.space;
+        self.each(|b| b.confine(&space));
+    }
+
+    fn bodies(&self) -> Vec<SimpleBody> {
+        (0..self.m.len()).map(|i| self.body(i)).collect()
+    }
+
+    fn load(&mut self, bodies: &[SimpleBody]) {
+        for (i, b) in bodies.iter().enumerate() {
+            self.set(i, b);
+        }
+    }
+}
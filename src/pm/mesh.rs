@@ -0,0 +1,211 @@
+use std::sync::Arc;
+
+use nalgebra::SVector;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::boundary::{Boundary, Space};
+
+/// the scale of the force split in cells, the short range forces fade out within a few cells from the bodies.
+const SPLIT: f64 = 1.25;
+
+type Plan = Arc<dyn Fft<f64>>;
+
+/// Mesh is the grid of the pm engines over the domain. The masses are assigned to its nodes by cloud in cell, convolved with the acceleration kernel by FFT and interpolated back to the bodies with the same weights.
+///
+/// The kernel is the acceleration a unit mass gives at every separation of two nodes, taken from the field of the space, so that the mesh follows the same softening and periodic images as the other engines. With periodic boundaries the grid wraps around the box, otherwise it is padded to twice its size so that the convolution does not wrap. The minimum image kernel jumps at half the box, which the mesh smooths out, so the pm engines are far less accurate with periodic boundaries without Ewald summation.
+pub struct Mesh<const D: usize> {
+    /// the lower corner of the domain, where the first node lies
+    origin: SVector<f64, D>,
+    /// the sides of a cell
+    cell: SVector<f64, D>,
+    /// the number of nodes along every axis
+    nodes: [usize; D],
+    /// the number of points of the FFT along every axis
+    size: [usize; D],
+    /// the distance between consecutive points along every axis in the flat grid, the last axis is contiguous
+    strides: [usize; D],
+    periodic: bool,
+    /// the space of the short range forces, if the forces are split between the mesh and a tree
+    short: Option<Space>,
+    /// the transforms of the components of the kernel
+    kernels: Vec<Vec<Complex<f64>>>,
+    /// the forward and inverse FFTs along every axis
+    ffts: Vec<(Plan, Plan)>,
+}
+
+impl<const D: usize> Mesh<D> {
+    /// a mesh of cells along the longest side of the domain of the space, with about cubic cells. With split, the mesh only carries the long range forces and short holds the space of the short range ones.
+    pub fn new(space: &Space, cells: usize, split: bool) -> Self {
+        let domain = space.domain::<D>();
+        let sides = domain.0 - domain.1;
+        let longest = sides.max() / cells as f64;
+        let counts: [usize; D] = std::array::from_fn(|i| ((sides[i] / longest).round() as usize).max(1));
+        let periodic = space.physics.boundary == Boundary::Periodic;
+        let nodes = counts.map(|n| if periodic { n } else { n + 1 });
+        let size = nodes.map(|n| if periodic { n } else { 2 * n });
+        let mut strides = [1; D];
+        for i in (0..D.saturating_sub(1)).rev() {
+            strides[i] = strides[i + 1] * size[i + 1];
+        }
+        let cell = SVector::<f64, D>::from_fn(|i, _| sides[i] / counts[i] as f64);
+        let mut planner = FftPlanner::new();
+        let ffts = size.iter().map(|&n| (planner.plan_fft_forward(n), planner.plan_fft_inverse(n))).collect();
+        let mut mesh = Mesh {
+            origin: domain.1,
+            cell,
+            nodes,
+            size,
+            strides,
+            periodic,
+            short: if split { Some(space.short_range(SPLIT * cell.max())) } else { None },
+            kernels: Vec::new(),
+            ffts,
+        };
+        mesh.kernels = mesh.kernels(space);
+        mesh
+    }
+
+    /// the space of the short range forces, None without a force split.
+    pub fn short(&self) -> Option<Space> {
+        self.short
+    }
+
+    /// the transforms of the D components of the acceleration at every separation of the grid, minus the short range part with a split. The acceleration vanishes at zero separation.
+    fn kernels(&self, space: &Space) -> Vec<Vec<Complex<f64>>> {
+        let total = self.size.iter().product();
+        let mut res = vec![vec![Complex::new(0.0, 0.0); total]; D];
+        for index in 1..total {
+            let mut delta = SVector::<f64, D>::from_fn(|i, _| {
+                let j = index / self.strides[i] % self.size[i];
+                // the upper half of a padded axis holds the negative separations
+                let j = if !self.periodic && j >= self.nodes[i] { j as f64 - self.size[i] as f64 } else { j as f64 };
+                j * self.cell[i]
+            });
+            delta = space.separation(delta);
+            let dist = delta.norm_squared();
+            let mut field = space.field(delta, dist);
+            if let Some(short) = &self.short {
+                field -= short.field(delta, dist);
+            }
+            for (kernel, f) in res.iter_mut().zip(field.iter()) {
+                kernel[index] = Complex::new(-f, 0.0);
+            }
+        }
+        for kernel in res.iter_mut() {
+            self.transform(kernel, false);
+        }
+        if self.short.is_some() {
+            // undoes the smoothing of the assignment and the interpolation, which the long range kernel keeps well below the frequency of the nodes
+            for index in 0..total {
+                let window: f64 = (0..D)
+                    .map(|i| {
+                        let (n, j) = (self.size[i], index / self.strides[i] % self.size[i]);
+                        let j = if 2 * j > n { j as f64 - n as f64 } else { j as f64 };
+                        let x = std::f64::consts::PI * j / n as f64;
+                        if j == 0.0 { 1.0 } else { (x.sin() / x).powi(4) }
+                    })
+                    .product();
+                for kernel in res.iter_mut() {
+                    kernel[index] /= window;
+                }
+            }
+        }
+        res
+    }
+
+    /// the D dimensional FFT of the grid, one axis after the other.
+    fn transform(&self, data: &mut [Complex<f64>], inverse: bool) {
+        let mut lines = vec![Complex::new(0.0, 0.0); data.len()];
+        for axis in 0..D {
+            let (n, stride) = (self.size[axis], self.strides[axis]);
+            let fft = if inverse { &self.ffts[axis].1 } else { &self.ffts[axis].0 };
+            if stride == 1 {
+                fft.process(data);
+                continue;
+            }
+            // gathers the lines along the axis so that they are contiguous
+            let block = n * stride;
+            for (k, line) in lines.chunks_mut(n).enumerate() {
+                let start = k / stride * block + k % stride;
+                for (j, x) in line.iter_mut().enumerate() {
+                    *x = data[start + j * stride];
+                }
+            }
+            fft.process(&mut lines);
+            for (k, line) in lines.chunks(n).enumerate() {
+                let start = k / stride * block + k % stride;
+                for (j, x) in line.iter().enumerate() {
+                    data[start + j * stride] = *x;
+                }
+            }
+        }
+    }
+
+    /// visits the 2^D nodes around x with their cloud in cell weights.
+    fn cloud(&self, x: &SVector<f64, D>, mut visit: impl FnMut(usize, f64)) {
+        let mut base = [0; D];
+        let mut next = [0; D];
+        let mut frac = [0.0; D];
+        for i in 0..D {
+            let n = self.nodes[i];
+            let u = (x[i] - self.origin[i]) / self.cell[i];
+            if self.periodic {
+                let u = u.rem_euclid(n as f64);
+                base[i] = (u.floor() as usize).min(n - 1);
+                next[i] = (base[i] + 1) % n;
+                frac[i] = u - base[i] as f64;
+            } else {
+                let u = u.clamp(0.0, (n - 1) as f64);
+                base[i] = (u.floor() as usize).min(n - 2);
+                next[i] = base[i] + 1;
+                frac[i] = u - base[i] as f64;
+            }
+        }
+        for corner in 0..1 << D {
+            let mut index = 0;
+            let mut weight = 1.0;
+            for i in 0..D {
+                if corner >> i & 1 == 1 {
+                    index += next[i] * self.strides[i];
+                    weight *= frac[i];
+                } else {
+                    index += base[i] * self.strides[i];
+                    weight *= 1.0 - frac[i];
+                }
+            }
+            visit(index, weight);
+        }
+    }
+
+    /// the accelerations of the bodies at the given positions with the given masses, from the mesh.
+    pub fn accelerations(&self, positions: &[SVector<f64, D>], masses: &[f64]) -> Vec<SVector<f64, D>> {
+        let total: usize = self.size.iter().product();
+        let mut density = vec![Complex::new(0.0, 0.0); total];
+        for (x, m) in positions.iter().zip(masses.iter()) {
+            self.cloud(x, |index, weight| density[index].re += m * weight);
+        }
+        self.transform(&mut density, false);
+        let fields: Vec<Vec<f64>> = self
+            .kernels
+            .iter()
+            .map(|kernel| {
+                let mut field: Vec<Complex<f64>> = density.iter().zip(kernel.iter()).map(|(a, b)| a * b).collect();
+                self.transform(&mut field, true);
+                field.iter().map(|f| f.re / total as f64).collect()
+            })
+            .collect();
+        positions
+            .iter()
+            .map(|x| {
+                let mut res = SVector::<f64, D>::zeros();
+                self.cloud(x, |index, weight| {
+                    for (a, field) in res.iter_mut().zip(fields.iter()) {
+                        *a += field[index] * weight;
+                    }
+                });
+                res
+            })
+            .collect()
+    }
+}
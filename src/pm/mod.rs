@@ -0,0 +1,86 @@
+use mesh::Mesh;
+
+use crate::boundary::Space;
+use crate::config::SimulationConfig;
+use crate::engine::Engine;
+use crate::geometry::SimpleBody;
+use crate::seq::TreeEngine;
+
+mod mesh;
+
+/// PmEngine is the particle-mesh engine in D dimensions: the masses are spread over a grid covering the domain, the accelerations are found there with FFTs and interpolated back to the bodies, in O(n + g log g) per step for g nodes. Forces below a few cells are smoothed out.
+///
+/// As tree_pm it is the TreePM hybrid: the forces are split with an erfc at about a cell, the mesh takes the long range part and a Barnes-Hut tree the short range part, within a few cells of every body. Either way the bodies are kept in the tree, which also finds the collisions.
+pub struct PmEngine<const D: usize = 2> {
+    tree: TreeEngine<D>,
+    mesh: Mesh<D>,
+    hybrid: bool,
+}
+
+impl<const D: usize> PmEngine<D> {
+    /// a pm engine, or a tree_pm one if hybrid is set.
+    pub fn new(config: &SimulationConfig, hybrid: bool) -> Self {
+        let space = Space::new(config);
+        let mesh = Mesh::new(&space, config.mesh_size, hybrid);
+        PmEngine {
+            tree: TreeEngine::with_space(mesh.short().unwrap_or(space)),
+            mesh,
+            hybrid,
+        }
+    }
+}
+
+impl<const D: usize> Engine for PmEngine<D> {
+    fn name(&self) -> &'static str {
+        match (self.hybrid, D == 3) {
+            (false, false) => "PM",
+            (false, true) => "PM 3D",
+            (true, false) => "TreePM",
+            (true, true) => "TreePM 3D",
+        }
+    }
+
+    fn init(&mut self, bodies: &[SimpleBody]) {
+        self.tree.init(bodies);
+    }
+
+    fn collide(&mut self) {
+        self.tree.collide();
+    }
+
+    fn accelerate(&mut self) {
+        if self.hybrid {
+            self.tree.accelerate();
+        } else {
+            self.tree.rebuild();
+        }
+        let mut bodies = self.tree.bodies();
+        let positions: Vec<_> = bodies.iter().map(|b| b.position::<D>()).collect();
+        let masses: Vec<_> = bodies.iter().map(|b| b.m).collect();
+        for (b, a) in bodies.iter_mut().zip(self.mesh.accelerations(&positions, &masses)) {
+            let a = if self.hybrid { b.acceleration::<D>() + a } else { a };
+            b.set_acceleration(&a);
+        }
+        self.tree.load(&bodies);
+    }
+
+    fn kick(&mut self, dt: f64) {
+        self.tree.kick(dt);
+    }
+
+    fn drift(&mut self, dt: f64, with_acceleration: bool) {
+        self.tree.drift(dt, with_acceleration);
+    }
+
+    fn confine(&mut self) {
+        self.tree.confine();
+    }
+
+    fn bodies(&self) -> Vec<SimpleBody> {
+        self.tree.bodies()
+    }
+
+    fn load(&mut self, bodies: &[SimpleBody]) {
+        self.tree.load(bodies);
+    }
+}
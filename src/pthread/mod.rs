@@ -1,101 +1,101 @@
-use rand::Rng;
-use sdl2::event::Event;
-use sdl2::pixels::Color;
-
-use crate::geometry::Body;
-use crate::global;
-use crate::global::{MASS_RANGE, RADIUS};
+use crate::boundary::Space;
+use crate::config::SimulationConfig;
+use crate::engine::Engine;
+use crate::geometry::{Body, Point, SimpleBody};
 use crate::pthread::pool::*;
-use crate::quad_tree::node::QuadNode;
-use std::f64::EPSILON;
-use std::sync::Arc;
+use crate::quad_tree::tree::Tree;
 
 pub mod pool;
 
-fn generate_body_wrappers() -> (Vec<BodyWrapper>, Arc<QuadNode>) {
-    let mut body_wrappers = Vec::new();
-    let mut rng = rand::thread_rng();
-    // let mut root = pool::new_root();
-    let root = global::new_root();
+/// ThreadTreeEngine is the parallel Barnes-Hut engine in D dimensions: the tree is built in parallel from the bodies sorted by their Morton keys, the collisions and forces are computed in parallel. The flag with_rayon determines whether to use Rayon or PThread library for parallelism.
+pub struct ThreadTreeEngine<const D: usize = 2> {
+    bodies: Vec<Body<D>>,
+    tree: Tree<D>,
+    space: Space,
+    threads: usize,
+    with_rayon: bool,
+}
 
-    for _ in 0..*global::SIZE {
-        let body = Body::new(
-            rng.gen_range(RADIUS + EPSILON, *global::REAL_WIDTH),
-            rng.gen_range(RADIUS + EPSILON, *global::REAL_HEIGHT),
-            rng.gen_range(0.0, MASS_RANGE),
-            root.clone(),
-        );
-        body_wrappers.push(BodyWrapper::from(body));
+impl<const D: usize> ThreadTreeEngine<D> {
+    pub fn new(config: &SimulationConfig, with_rayon: bool) -> Self {
+        let space = Space::new(config);
+        ThreadTreeEngine {
+            bodies: Vec::new(),
+            tree: Tree::new(&[], space),
+            space,
+            threads: config.threads,
+            with_rayon,
+        }
     }
-
-    (body_wrappers, root)
 }
 
-fn benchmark_threading_algorithm(
-    with_rayon: bool,
-    body_wrappers: &Vec<BodyWrapper>,
-    root: Arc<QuadNode>,
-) {
-    let start = std::time::SystemTime::now();
-    if with_rayon {
-        thread_rayon(body_wrappers, root);
-    } else {
-        thread_go(body_wrappers, root);
+impl<const D: usize> ThreadTreeEngine<D> {
+    /// rebuilds the tree at the current positions, in parallel like the forces. The tree has to be complete before any force is computed.
+    fn rebuild(&mut self) {
+        let points: Vec<Point<D>> = self.bodies.iter().map(|b| b.position).collect();
+        self.tree = Tree::build(&points, self.space, self.threads, self.with_rayon);
     }
-    let end = std::time::SystemTime::now();
-    println!(
-        "Duration: {} ms",
-        end.duration_since(start).unwrap().as_millis()
-    );
 }
 
-/// generates and animates a tree-like structure of nodes (bodies) using threads. The function takes a boolean parameter with_rayon which determines whether to use Rayon or PThread library for parallelism.
-pub fn start_thread_tree(with_rayon: bool) {
-    let (body_wrappers, mut root) = generate_body_wrappers();
+impl<const D: usize> Engine for ThreadTreeEngine<D> {
+    fn name(&self) -> &'static str {
+        match (self.with_rayon, D) {
+            (true, 3) => "RayonTree 3D",
+            (true, _) => "RayonTree",
+            (false, 3) => "PThread 3D",
+            (false, _) => "PThread",
+        }
+    }
 
-    if *global::BENCHMARK {
-        benchmark_threading_algorithm(with_rayon, &body_wrappers, root);
-    } else {
-        let (mut event_pump, mut canvas) =
-            crate::global::init_sdl(if with_rayon { "RayonTree" } else { "PThread" });
+    fn init(&mut self, bodies: &[SimpleBody]) {
+        self.bodies = bodies.iter().map(Body::from_simple).collect();
+        self.rebuild();
+    }
 
-        canvas.set_draw_color(Color::RGB(0, 255, 255));
-        canvas.clear();
-        canvas.present();
+    fn collide(&mut self) {
+        let velocities = velocities(&self.bodies);
+        let tree = &self.tree;
+        for_each(&mut self.bodies, self.threads, self.with_rayon, |_, i| i.collision_detect(tree, &velocities));
+    }
 
-        let mut i = 0;
-        let mut n = 0;
-        let mut start = std::time::SystemTime::now();
+    fn accelerate(&mut self) {
+        self.rebuild();
+        let tree = &self.tree;
+        for_each(&mut self.bodies, self.threads, self.with_rayon, |_, i| i.gravity_impact(tree));
+    }
 
-        'running: loop {
-            n += 1;
-            canvas
-                .set_scale(*global::SCALE_FACTOR as f32, *global::SCALE_FACTOR as f32)
-                .unwrap();
-            canvas.set_draw_color(Color::RGB(255, 255, 255));
-            canvas.clear();
+    fn accelerate_some(&mut self, active: &[bool]) {
+        self.rebuild();
+        let tree = &self.tree;
+        for_each(&mut self.bodies, self.threads, self.with_rayon, |k, i| {
+            if active[k] {
+                i.gravity_impact(tree)
+            }
+        });
+    }
 
-            i = (i + 1) % 255;
-            canvas.set_draw_color(Color::RGB(i, 64, 255 - i));
-            let points = body_wrappers.iter().map(|x| x.to_sdl()).collect::<Vec<_>>();
-            canvas
-                .draw_points(points.as_slice())
-                .expect("unable to draw points");
+    fn kick(&mut self, dt: f64) {
+        for_each(&mut self.bodies, self.threads, self.with_rayon, |_, i| i.update_velocity(dt));
+    }
 
-            if with_rayon {
-                root = thread_rayon(&body_wrappers, root);
-            } else {
-                root = thread_go(&body_wrappers, root);
-            }
+    fn drift(&mut self, dt: f64, with_acceleration: bool) {
+        for_each(&mut self.bodies, self.threads, self.with_rayon, |_, i| {
+            i.update_position(dt, with_acceleration)
+        });
+    }
 
-            for event in event_pump.poll_iter() {
-                match event {
-                    Event::Quit { .. } => break 'running,
-                    _ => {}
-                }
-            }
-            canvas.present();
-            crate::global::show_fps(&mut n, &mut start);
+    fn confine(&mut self) {
+        let space = &self.space;
+        for_each(&mut self.bodies, self.threads, self.with_rayon, |_, i| i.check_boundary(space));
+    }
+
+    fn bodies(&self) -> Vec<SimpleBody> {
+        self.bodies.iter().map(|x| x.to_simple()).collect()
+    }
+
+    fn load(&mut self, bodies: &[SimpleBody]) {
+        for (i, b) in self.bodies.iter_mut().zip(bodies.iter()) {
+            i.load(b);
         }
     }
 }
@@ -1,3 +1,2 @@
-pub mod node;
-
-
+mod morton;
+pub mod tree;
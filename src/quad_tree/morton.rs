@@ -0,0 +1,90 @@
+use nalgebra::SVector;
+
+use crate::geometry::Square;
+use crate::pthread::pool::for_each;
+
+/// the buckets of smaller keys are sorted by comparison rather than by digits.
+const SMALL: usize = 256;
+
+/// the number of levels a key of D dimensions holds, the bits of every coordinate.
+pub const fn levels(d: usize) -> usize {
+    64 / d
+}
+
+/// the Morton key of x within the region: the coordinates are quantized to levels bits and interleaved, the bit of axis i at level l being bit (levels - 1 - l) * D + i of the key. Sorted keys list the bodies of every node of the tree contiguously, in the order of Square::split. Points outside the region are clamped to it.
+pub fn key<const D: usize>(x: &SVector<f64, D>, region: &Square<D>) -> u64 {
+    let levels = levels(D);
+    let cells = (1_u64 << levels) as f64;
+    let mut res = 0;
+    for i in 0..D {
+        let side = region.0[i] - region.1[i];
+        let u = if side > 0.0 { (x[i] - region.1[i]) / side * cells } else { 0.0 };
+        // NaN goes to 0
+        let q = u.clamp(0.0, cells - 1.0) as u64;
+        for l in 0..levels {
+            res |= (q >> l & 1) << (l * D + i);
+        }
+    }
+    res
+}
+
+/// the child of the node at depth the key lies in, as an index of Square::split.
+#[inline]
+pub fn child<const D: usize>(key: u64, depth: usize) -> usize {
+    (key >> ((levels(D) - 1 - depth) * D)) as usize & ((1 << D) - 1)
+}
+
+/// the indices of the keys in increasing order, equal keys keeping their order. The keys are radix sorted: they are distributed into buckets by their top byte, which are then sorted by their lower bytes in parallel, on threads threads or with rayon.
+pub fn sort(keys: &[u64], threads: usize, with_rayon: bool) -> Vec<u32> {
+    let mut counts = [0; 256];
+    for k in keys {
+        counts[(k >> 56) as usize] += 1;
+    }
+    let mut offsets = [0; 256];
+    for b in 1..256 {
+        offsets[b] = offsets[b - 1] + counts[b - 1];
+    }
+    let mut pairs = vec![(0, 0); keys.len()];
+    for (i, k) in keys.iter().enumerate() {
+        let b = (k >> 56) as usize;
+        pairs[offsets[b]] = (*k, i as u32);
+        offsets[b] += 1;
+    }
+    let mut buckets = Vec::new();
+    let mut rest = pairs.as_mut_slice();
+    for count in counts.iter().filter(|c| **c > 0) {
+        let (bucket, tail) = rest.split_at_mut(*count);
+        buckets.push(bucket);
+        rest = tail;
+    }
+    for_each(&mut buckets, threads, with_rayon, |_, bucket| radix_sort(bucket));
+    pairs.iter().map(|p| p.1).collect()
+}
+
+/// sorts the pairs of a bucket stably by their keys, one byte below the top one after the other from the lowest. Bytes all the keys share are skipped.
+fn radix_sort(pairs: &mut [(u64, u32)]) {
+    if pairs.len() < SMALL {
+        pairs.sort_by_key(|p| p.0);
+        return;
+    }
+    let mut scratch = pairs.to_vec();
+    for shift in (0..56).step_by(8) {
+        let mut counts = [0; 256];
+        for p in pairs.iter() {
+            counts[(p.0 >> shift) as usize & 255] += 1;
+        }
+        if counts.contains(&pairs.len()) {
+            continue;
+        }
+        let mut offsets = [0; 256];
+        for b in 1..256 {
+            offsets[b] = offsets[b - 1] + counts[b - 1];
+        }
+        for p in pairs.iter() {
+            let b = (p.0 >> shift) as usize & 255;
+            scratch[offsets[b]] = *p;
+            offsets[b] += 1;
+        }
+        pairs.copy_from_slice(&scratch);
+    }
+}
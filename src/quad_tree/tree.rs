@@ -0,0 +1,307 @@
+use std::ops::Range;
+
+use nalgebra::SVector;
+
+use super::morton;
+use crate::boundary::Space;
+use crate::geometry::{Point, Square};
+use crate::multipole::{Expansion, Moments};
+use crate::pthread::pool::for_each;
+
+/// the largest number of bodies in a leaf, larger nodes are divided.
+const LEAF_SIZE: usize = 8;
+
+/// the tree is divided down to about 2^TOP_DEPTH nodes before the subtrees below them are built in parallel.
+const TOP_DEPTH: usize = 6;
+
+/// Node is a node of the tree, holding the bodies points[start..end] within its region.
+#[derive(Copy, Clone)]
+struct Node<const D: usize> {
+    region: Square<D>,
+    mass: f64,
+    /// the centre of mass, the centre of the region without mass
+    center: SVector<f64, D>,
+    start: u32,
+    end: u32,
+    /// the children are nodes[first..first + count], a leaf has none
+    first: u32,
+    count: u32,
+}
+
+/// Tree is the Barnes-Hut tree of a D dimensional space, a quadtree in 2D and an octree in 3D, stored in flat arrays: the bodies are sorted by their Morton keys, so that the bodies of every node are contiguous, and the nodes are indexed by u32, the children of a node being consecutive and after it. A node with more than LEAF_SIZE bodies is divided into the non-empty ones of its 2^D children, unless it is not larger than min_size in every direction.
+pub struct Tree<const D: usize = 2> {
+    /// the constants and boundary of the run
+    space: Space,
+    nodes: Vec<Node<D>>,
+    /// the higher moments of every node, only kept with an expansion beyond the monopole
+    moments: Vec<Moments<D>>,
+    /// the bodies in the order of their keys
+    points: Vec<Point<D>>,
+    /// the index of every sorted body among the points the tree was built from
+    order: Vec<u32>,
+}
+
+impl<const D: usize> Node<D> {
+    fn new(region: Square<D>, start: usize, end: usize) -> Self {
+        Node {
+            region,
+            mass: 0.0,
+            center: SVector::zeros(),
+            start: start as u32,
+            end: end as u32,
+            first: 0,
+            count: 0,
+        }
+    }
+}
+
+impl<const D: usize> Tree<D> {
+    /// the tree of the points over the root region of the space, built on the current thread.
+    pub fn new(points: &[Point<D>], space: Space) -> Self {
+        Self::build(points, space, 1, false)
+    }
+
+    /// the tree of the points over the root region of the space, built in parallel on threads threads or with rayon. The keys are computed and radix sorted in parallel, the top TOP_DEPTH levels are divided first and the subtrees below them are divided and summed bottom-up in parallel, each in arrays of its own, which are then appended to the tree. Nothing is shared between the threads, and the tree is the same however it is built.
+    pub fn build(points: &[Point<D>], space: Space, threads: usize, with_rayon: bool) -> Self {
+        let region = space.root(points.iter().map(|p| p.coords));
+        let mut keys = vec![0; points.len()];
+        for_each(&mut keys, threads, with_rayon, |k, key| *key = morton::key(&points[k].coords, &region));
+        let order = morton::sort(&keys, threads, with_rayon);
+        let keys: Vec<u64> = order.iter().map(|&i| keys[i as usize]).collect();
+        let points: Vec<Point<D>> = order.iter().map(|&i| points[i as usize]).collect();
+        let (expansion, min_size) = (space.physics.expansion, space.physics.min_size);
+        let top = TOP_DEPTH.div_ceil(D);
+
+        let mut nodes = vec![Node::new(region, 0, points.len())];
+        let mut frontier = Vec::new();
+        split(&mut nodes, 0, 0, top, &keys, min_size, &mut frontier);
+        let mut subtrees: Vec<_> = frontier.into_iter().map(|index| (index, vec![nodes[index]], Vec::new())).collect();
+        for_each(&mut subtrees, threads, with_rayon, |_, (_, arena, moments)| {
+            split(arena, 0, top, usize::MAX, &keys, min_size, &mut Vec::new());
+            if expansion != Expansion::Monopole {
+                *moments = vec![Moments::default(); arena.len()];
+            }
+            let all = 0..arena.len();
+            sum(arena, moments, &points, expansion, all);
+        });
+
+        let top_nodes = nodes.len();
+        let mut moments = Vec::new();
+        if expansion != Expansion::Monopole {
+            moments = vec![Moments::default(); top_nodes];
+        }
+        for (index, arena, arena_moments) in subtrees {
+            // the root of the subtree replaces the node it was built from, the other nodes go after the tree so far
+            let base = nodes.len() as u32 - 1;
+            for (k, mut node) in arena.into_iter().enumerate() {
+                if node.count > 0 {
+                    node.first += base;
+                }
+                if k == 0 {
+                    nodes[index] = node;
+                } else {
+                    nodes.push(node);
+                }
+            }
+            if let Some((root, rest)) = arena_moments.split_first() {
+                moments[index] = *root;
+                moments.extend_from_slice(rest);
+            }
+        }
+        sum(&mut nodes, &mut moments, &points, expansion, 0..top_nodes);
+        Tree {
+            space,
+            nodes,
+            moments,
+            points,
+            order,
+        }
+    }
+
+    /// the children of the node.
+    fn children(&self, node: &Node<D>) -> std::ops::Range<usize> {
+        node.first as usize..(node.first + node.count) as usize
+    }
+
+    /// whether the node is far enough from the point a to be approximated by its centre of mass according to the opening criterion of the run, the squared distance and the separation of the centre of mass from a. acceleration is the magnitude of the last acceleration of a, 0 if unknown.
+    fn check_limit(&self, node: &Node<D>, a: &Point<D>, acceleration: f64) -> (bool, f64, SVector<f64, D>) {
+        let delta = self.space.separation(node.center - a.coords);
+        let dist = delta.norm_squared();
+        let physics = &self.space.physics;
+        let far = physics.opening.accepts(physics, &node.region, &node.center, node.mass, &delta, acceleration)
+            && self.space.sees_whole(&node.region, a);
+        (far, dist, delta)
+    }
+
+    /// the gravitational force on the point a from the bodies of the tree, pulling it towards them. Bodies closer than twice the radius do not attract it. acceleration is the magnitude of the last acceleration of a, which the relative opening criterion needs.
+    pub fn impact(&self, a: &Point<D>, acceleration: f64) -> SVector<f64, D> {
+        if self.points.is_empty() {
+            return SVector::zeros();
+        }
+        self.impact_at(0, a, acceleration)
+    }
+
+    fn impact_at(&self, index: usize, a: &Point<D>, acceleration: f64) -> SVector<f64, D> {
+        let node = &self.nodes[index];
+        let space = &self.space;
+        if let Some(cutoff) = space.cutoff() {
+            if !space.touch(&node.region, a, cutoff) {
+                return SVector::zeros();
+            }
+        }
+        if let (true, dist, delta) = self.check_limit(node, a, acceleration) {
+            let physics = &space.physics;
+            let mut res = space.field(delta, dist) * node.mass;
+            if let Some(moments) = self.moments.get(index) {
+                let higher = moments.field(node.mass, &node.center, &-delta, physics.expansion);
+                res += higher * (physics.g * space.short_force(dist));
+            }
+            return res * a.mass;
+        }
+        if node.count > 0 {
+            return self
+                .children(node)
+                .fold(SVector::zeros(), |now, child| now + self.impact_at(child, a, acceleration));
+        }
+        let radius = space.physics.radius;
+        let mut now = SVector::zeros();
+        for obj in &self.points[node.start as usize..node.end as usize] {
+            let delta = space.separation(obj.coords - a.coords);
+            let dist = delta.norm_squared();
+            if dist < 4.0 * radius * radius {
+                continue;
+            }
+            now += space.field(delta, dist) * (a.mass * obj.mass);
+        }
+        now
+    }
+
+    /// the gravitational potential energy of the point a in the tree, approximated like impact without a last acceleration. The point itself is skipped.
+    pub fn potential(&self, a: &Point<D>) -> f64 {
+        if self.points.is_empty() {
+            return 0.0;
+        }
+        self.potential_at(0, a)
+    }
+
+    fn potential_at(&self, index: usize, a: &Point<D>) -> f64 {
+        let node = &self.nodes[index];
+        let space = &self.space;
+        if let (true, dist, delta) = self.check_limit(node, a, 0.0) {
+            let physics = &space.physics;
+            let higher = self
+                .moments
+                .get(index)
+                .map_or(0.0, |moments| moments.potential(node.mass, &node.center, &-delta, physics.expansion));
+            return (space.potential(delta, dist) * node.mass + higher * physics.g) * a.mass;
+        }
+        if node.count > 0 {
+            return self.children(node).map(|child| self.potential_at(child, a)).sum();
+        }
+        let mut now = 0.0;
+        for obj in &self.points[node.start as usize..node.end as usize] {
+            if obj.coords != a.coords {
+                let delta = space.separation(obj.coords - a.coords);
+                now += space.potential(delta, delta.norm_squared()) * a.mass * obj.mass;
+            }
+        }
+        now
+    }
+
+    /// the velocity change of the point a, moving with velocity, from its collisions with the other bodies of the tree. velocities holds the velocities from before the collisions of the points the tree was built from. Across the edge of a periodic box the nearest image of the other bodies is taken.
+    pub fn collision(&self, a: &Point<D>, velocity: &SVector<f64, D>, velocities: &[SVector<f64, D>]) -> SVector<f64, D> {
+        let mut res = SVector::zeros();
+        if !self.points.is_empty() {
+            self.collision_at(0, a, velocity, velocities, &mut res);
+        }
+        res
+    }
+
+    fn collision_at(&self, index: usize, a: &Point<D>, velocity: &SVector<f64, D>, velocities: &[SVector<f64, D>], res: &mut SVector<f64, D>) {
+        let node = &self.nodes[index];
+        let radius = self.space.physics.radius;
+        if !self.space.touch(&node.region, a, 2.0 * radius) {
+            return;
+        }
+        for child in self.children(node) {
+            self.collision_at(child, a, velocity, velocities, res);
+        }
+        if node.count > 0 {
+            return;
+        }
+        for k in node.start as usize..node.end as usize {
+            let obj = &self.points[k];
+            if obj.coords == a.coords {
+                continue;
+            }
+            let delta = self.space.separation(a.coords - obj.coords);
+            let dist = delta.norm_squared();
+            if dist < 4.0 * radius * radius {
+                let delta_v = velocity - velocities[self.order[k] as usize];
+                let coefficient = 2.0 * obj.mass / (a.mass + obj.mass) * delta.dot(&delta_v) / dist;
+                *res -= delta * coefficient;
+            }
+        }
+    }
+}
+
+/// divides nodes[index] at the given depth and its descendants down to the depth limit, keys being the sorted keys of the bodies. The nodes at the limit which would be divided are added to frontier instead.
+fn split<const D: usize>(nodes: &mut Vec<Node<D>>, index: usize, depth: usize, limit: usize, keys: &[u64], min_size: f64, frontier: &mut Vec<usize>) {
+    let node = &nodes[index];
+    let (start, end) = (node.start as usize, node.end as usize);
+    let range = node.region.0 - node.region.1;
+    if end - start <= LEAF_SIZE || depth == morton::levels(D) || range.iter().all(|x| *x <= min_size) {
+        return;
+    }
+    if depth == limit {
+        frontier.push(index);
+        return;
+    }
+    let regions = node.region.split();
+    let first = nodes.len();
+    let mut s = start;
+    while s < end {
+        let child = morton::child::<D>(keys[s], depth);
+        let e = s + keys[s..end].partition_point(|k| morton::child::<D>(*k, depth) <= child);
+        nodes.push(Node::new(regions[child], s, e));
+        s = e;
+    }
+    let count = nodes.len() - first;
+    nodes[index].first = first as u32;
+    nodes[index].count = count as u32;
+    for child in first..first + count {
+        split(nodes, child, depth + 1, limit, keys, min_size, frontier);
+    }
+}
+
+/// the masses, centres of mass and moments of nodes[range], from the bodies of the leaves and from the children of the others, which come after their parent and are summed first. moments is empty with the monopole expansion.
+fn sum<const D: usize>(nodes: &mut [Node<D>], moments: &mut [Moments<D>], points: &[Point<D>], expansion: Expansion, range: Range<usize>) {
+    for index in range.rev() {
+        let node = &nodes[index];
+        let (mut mass, mut weighted) = (0.0, SVector::<f64, D>::zeros());
+        let mut higher = Moments::default();
+        if node.count == 0 {
+            for p in &points[node.start as usize..node.end as usize] {
+                mass += p.mass;
+                weighted += p.coords * p.mass;
+                higher.add(&p.coords, p.mass, expansion);
+            }
+        } else {
+            let first = node.first as usize;
+            for (k, child_node) in nodes[first..first + node.count as usize].iter().enumerate() {
+                mass += child_node.mass;
+                weighted += child_node.center * child_node.mass;
+                if let Some(m) = moments.get(first + k) {
+                    higher += *m;
+                }
+            }
+        }
+        let center = if mass > 0.0 { weighted / mass } else { (node.region.0 + node.region.1) / 2.0 };
+        let node = &mut nodes[index];
+        node.mass = mass;
+        node.center = center;
+        if let Some(m) = moments.get_mut(index) {
+            *m = higher;
+        }
+    }
+}
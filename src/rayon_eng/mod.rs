@@ -1,97 +1,140 @@
-use rand::Rng;
+use nalgebra::SVector;
 use rayon::prelude::*;
-use sdl2::event::Event;
-use sdl2::pixels::Color;
 
 use rayon_module::*;
 
+use crate::boundary::Space;
+use crate::config::SimulationConfig;
+use crate::engine::Engine;
 use crate::geometry::SimpleBody;
-use crate::global::*;
 
 mod rayon_module;
 
-fn refresh(universe: &mut Vec<(usize, SimpleBody)>) {
-    let impact = universe
+/// sums f(i, j) over all j for every body i in parallel.
+fn pairwise<const D: usize>(
+    universe: &[(usize, SimpleBody)],
+    space: &Space,
+    f: fn(&SimpleBody, &SimpleBody, &Space, &mut SVector<f64, D>),
+) -> Vec<SVector<f64, D>> {
+    universe
         .par_iter()
         .map(|i| {
-            let mut res = (0.0, 0.0, 0.0, 0.0);
-            for j in &*universe {
-                handle_impact(&i.1, &j.1, &mut res);
+            let mut res = SVector::zeros();
+            for j in universe {
+                f(&i.1, &j.1, space, &mut res);
             }
             res
         })
-        .collect::<Vec<_>>();
-
-    universe.par_iter_mut().for_each(|i| {
-        i.1.vx += impact[i.0].0;
-        i.1.vy += impact[i.0].1;
-        i.1.ax += impact[i.0].2;
-        i.1.ay += impact[i.0].3;
-        update(&mut i.1);
-    });
+        .collect()
 }
 
-pub fn start_rayon() {
-    let mut universe = Vec::new();
-    let mut rng = rand::thread_rng();
-    let real_width = *WIDTH / *SCALE_FACTOR;
-    let real_height = *HEIGHT / *SCALE_FACTOR;
-    for i in 0..*SIZE {
-        universe.push((
-            i,
-            SimpleBody {
-                x: rng.gen_range(0.0, real_width),
-                y: rng.gen_range(0.0, real_height),
-                m: rng.gen_range(0.0, MASS_RANGE),
-                vx: 0.0,
-                vy: 0.0,
-                ax: 0.0,
-                ay: 0.0,
-            },
-        ));
+/// sums f(i, j) over all j for every active body i in parallel, the sums come with the index of their body.
+fn pairwise_of<const D: usize>(
+    universe: &[(usize, SimpleBody)],
+    active: &[bool],
+    space: &Space,
+    f: fn(&SimpleBody, &SimpleBody, &Space, &mut SVector<f64, D>),
+) -> Vec<(usize, SVector<f64, D>)> {
+    universe
+        .par_iter()
+        .filter(|i| active[i.0])
+        .map(|i| {
+            let mut res = SVector::zeros();
+            for j in universe {
+                f(&i.1, &j.1, space, &mut res);
+            }
+            (i.0, res)
+        })
+        .collect()
+}
+
+/// RayonEngine computes every pairwise interaction like BruteForceEngine, but in parallel with rayon, in the dimensions of the space.
+pub struct RayonEngine {
+    universe: Vec<(usize, SimpleBody)>,
+    space: Space,
+}
+
+impl RayonEngine {
+    pub fn new(config: &SimulationConfig) -> Self {
+        RayonEngine {
+            universe: Vec::new(),
+            space: Space::new(config),
+        }
+    }
+}
+
+impl RayonEngine {
+    fn collide_in<const D: usize>(&mut self) {
+        let impulse = pairwise(&self.universe, &self.space, handle_collision::<D>);
+        self.universe.par_iter_mut().for_each(|i| {
+            let v = i.1.velocity::<D>() + impulse[i.0];
+            i.1.set_velocity(&v);
+        });
     }
 
-    if *BENCHMARK {
-        let start = std::time::SystemTime::now();
-        refresh(&mut universe);
-        let end = std::time::SystemTime::now();
-        println!(
-            "Duration: {} ms",
-            end.duration_since(start).unwrap().as_millis()
-        );
-    } else {
-        let (mut event_pump, mut canvas) = crate::global::init_sdl("Rayon");
-
-        canvas.set_draw_color(Color::RGB(0, 255, 255));
-        canvas.clear();
-        canvas.present();
-        let mut i = 0;
-        let mut n = 0;
-        let mut start = std::time::SystemTime::now();
-        'running: loop {
-            n += 1;
-            canvas
-                .set_scale(*SCALE_FACTOR as f32, *SCALE_FACTOR as f32)
-                .unwrap();
-            canvas.set_draw_color(Color::RGB(255, 255, 255));
-            canvas.clear();
-            i = (i + 1) % 255;
-            canvas.set_draw_color(Color::RGB(i, 64, 255 - i));
-            let points = universe.iter().map(|x| x.1.to_sdl()).collect::<Vec<_>>();
-            canvas
-                .draw_points(points.as_slice())
-                .expect("unable to draw points");
-            canvas.present();
-            refresh(&mut universe);
-            for event in event_pump.poll_iter() {
-                match event {
-                    Event::Quit { .. } => {
-                        break 'running;
-                    }
-                    _ => {}
-                }
-            }
-            crate::global::show_fps(&mut n, &mut start);
+    fn accelerate_in<const D: usize>(&mut self) {
+        let acceleration = pairwise(&self.universe, &self.space, handle_impact::<D>);
+        self.universe.par_iter_mut().for_each(|i| i.1.set_acceleration(&acceleration[i.0]));
+    }
+
+    fn accelerate_some_in<const D: usize>(&mut self, active: &[bool]) {
+        for (i, a) in pairwise_of(&self.universe, active, &self.space, handle_impact::<D>) {
+            self.universe[i].1.set_acceleration(&a);
         }
     }
 }
+
+impl Engine for RayonEngine {
+    fn name(&self) -> &'static str {
+        "Rayon"
+    }
+
+    fn init(&mut self, bodies: &[SimpleBody]) {
+        self.universe = bodies.iter().cloned().enumerate().collect();
+    }
+
+    fn collide(&mut self) {
+        if self.space.dimensions == 3 {
+            self.collide_in::<3>();
+        } else {
+            self.collide_in::<2>();
+        }
+    }
+
+    fn accelerate(&mut self) {
+        if self.space.dimensions == 3 {
+            self.accelerate_in::<3>();
+        } else {
+            self.accelerate_in::<2>();
+        }
+    }
+
+    fn accelerate_some(&mut self, active: &[bool]) {
+        if self.space.dimensions == 3 {
+            self.accelerate_some_in::<3>(active);
+        } else {
+            self.accelerate_some_in::<2>(active);
+        }
+    }
+
+    fn kick(&mut self, dt: f64) {
+        self.universe.par_iter_mut().for_each(|i| i.1.kick(dt));
+    }
+
+    fn drift(&mut self, dt: f64, with_acceleration: bool) {
+        self.universe.par_iter_mut().for_each(|i| i.1.drift(dt, with_acceleration));
+    }
+
+    fn confine(&mut self) {
+        let space = &self.space;
+        self.universe.par_iter_mut().for_each(|i| i.1.confine(space));
+    }
+
+    fn bodies(&self) -> Vec<SimpleBody> {
+        self.universe.iter().map(|x| x.1).collect()
+    }
+
+    fn load(&mut self, bodies: &[SimpleBody]) {
+        self.universe.iter_mut().zip(bodies.iter()).for_each(|(i, b)| i.1 = *b);
+    }
+}
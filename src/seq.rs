@@ -1,99 +1,101 @@
-use std::sync::Arc;
+use crate::boundary::Space;
+use crate::config::SimulationConfig;
+use crate::engine::Engine;
+use crate::geometry::{Body, Point, SimpleBody};
+use crate::quad_tree::tree::Tree;
 
+/// TreeEngine is the sequential Barnes-Hut engine in D dimensions: the bodies are sorted into a quadtree, or an octree in 3D, that is rebuilt whenever the accelerations are computed.
+pub struct TreeEngine<const D: usize = 2> {
+    tree: Tree<D>,
+    pool: Vec<Body<D>>,
+    space: Space,
+}
 
-use rand::Rng;
-use sdl2::event::Event;
-use sdl2::pixels::Color;
-
-// use crate::geometry;
-use crate::geometry::{Body, Square};
-use crate::global;
-// use crate::quad_tree;
-use crate::quad_tree::node::QuadNode;
-// use std::f64::EPSILON;
+impl<const D: usize> TreeEngine<D> {
+    pub fn new(config: &SimulationConfig) -> Self {
+        TreeEngine::with_space(Space::new(config))
+    }
 
-fn refresh(pool: &mut Vec<Body>, root: &mut Arc<QuadNode>, boundary: &Square) {
-    {
-        let mut a = global::VMAP.write();
-        a.clear();
-        for i in &*pool {
-            a.insert(i.position.clone(), i.velocity);
+    /// an engine working in the given space, the short range space of the tree_pm engine.
+    pub fn with_space(space: Space) -> Self {
+        TreeEngine {
+            tree: Tree::new(&[], space),
+            pool: Vec::new(),
+            space,
         }
-        for i in &*pool {
-            assert!(a.contains_key(&i.position))
+    }
+}
+
+impl<const D: usize> TreeEngine<D> {
+    /// rebuilds the tree at the current positions, which collide walks.
+    pub(crate) fn rebuild(&mut self) {
+        let points: Vec<Point<D>> = self.pool.iter().map(|b| b.position).collect();
+        self.tree = Tree::new(&points, self.space);
+    }
+}
+
+impl<const D: usize> Engine for TreeEngine<D> {
+    fn name(&self) -> &'static str {
+        if D == 3 {
+            "Sequential 3D"
+        } else {
+            "Sequential"
         }
     }
-    for i in &mut *pool {
-        i.make_ready();
-        i.collision_detect();
-        i.update_velocity();
-        i.update_position();
-        i.gravity_impact(root.clone());
-        i.check_boundary();
+
+    fn init(&mut self, bodies: &[SimpleBody]) {
+        self.pool = bodies.iter().map(Body::from_simple).collect();
+        self.rebuild();
     }
-    *root = Arc::new(QuadNode::new(boundary.clone()));
-    for i in &mut *pool {
-        i.reinsert(root.clone());
+
+    fn collide(&mut self) {
+        // the collisions are resolved with the velocities from before the step
+        let velocities: Vec<_> = self.pool.iter().map(|i| i.velocity).collect();
+        for i in &mut self.pool {
+            i.collision_detect(&self.tree, &velocities);
+        }
     }
-}
 
-fn init_tree() -> (Arc<QuadNode>, Vec<Body>, Square) {
-    let root = global::new_root();
-    let boundary = global::new_boundary();
-    let mut rng = rand::thread_rng();
-    let mut pool = Vec::new();
-    for _ in 0..*global::SIZE {
-        pool.push(Body::new(
-            rng.gen_range(global::RADIUS + f64::EPSILON, *global::REAL_WIDTH - global::RADIUS),
-            rng.gen_range(global::RADIUS + f64::EPSILON, *global::REAL_HEIGHT - global::RADIUS),
-            rng.gen_range(0.0, global::MASS_RANGE),
-            root.clone(),
-        ));
+    fn accelerate(&mut self) {
+        self.rebuild();
+        for i in &mut self.pool {
+            i.gravity_impact(&self.tree);
+        }
     }
-    
-    (root, pool, boundary)
-}
 
-pub fn start_tree() {
-    let (mut root, mut pool, boundary) = init_tree();
+    fn accelerate_some(&mut self, active: &[bool]) {
+        // the tree holds every body, but only the active ones walk it
+        self.rebuild();
+        for (i, _) in self.pool.iter_mut().zip(active.iter()).filter(|x| *x.1) {
+            i.gravity_impact(&self.tree);
+        }
+    }
 
-    if *global::BENCHMARK {
-        let start = std::time::SystemTime::now();
-        refresh(&mut pool, &mut root, &boundary);
-        let end = std::time::SystemTime::now();
-        println!("Duration: {} ms", end.duration_since(start).unwrap().as_millis());
-    } else {
-        let (mut event_pump, mut canvas) = crate::global::init_sdl("Sequential");
+    fn kick(&mut self, dt: f64) {
+        for i in &mut self.pool {
+            i.update_velocity(dt);
+        }
+    }
 
-        canvas.set_draw_color(Color::RGB(0, 255, 255));
-        canvas.clear();
-        canvas.present();
-        let mut i = 0;
-        let mut n = 0;
-        let mut start = std::time::SystemTime::now();
-        'running: loop {
-            n += 1;
-            //println!("{:?}", pool);
-            canvas.set_scale(*global::SCALE_FACTOR as f32, *global::SCALE_FACTOR as f32).unwrap();
-            canvas.set_draw_color(Color::RGB(255, 255, 255));
-            canvas.clear();
-            i = (i + 1) % 255;
-            canvas.set_draw_color(Color::RGB(i, 64, 255 - i));
-            let points = pool.iter().map(|x| x.geometric()).collect::<Vec<_>>();
-            canvas.draw_points(points.as_slice()).expect("unable to draw points");
+    fn drift(&mut self, dt: f64, with_acceleration: bool) {
+        for i in &mut self.pool {
+            i.update_position(dt, with_acceleration);
+        }
+    }
+
+    fn confine(&mut self) {
+        for i in &mut self.pool {
+            i.check_boundary(&self.space);
+        }
+    }
 
-            for event in event_pump.poll_iter() {
-                match event {
-                    Event::Quit { .. } => {
-                        break 'running;
-                    }
-                    _ => {}
-                }
-            }
+    fn bodies(&self) -> Vec<SimpleBody> {
+        self.pool.iter().map(|b| b.to_simple()).collect()
+    }
 
-            canvas.present();
-            refresh(&mut pool, &mut root, &boundary);
-            global::show_fps(&mut n, &mut start);
+    fn load(&mut self, bodies: &[SimpleBody]) {
+        for (i, b) in self.pool.iter_mut().zip(bodies.iter()) {
+            i.load(b);
         }
     }
-}
\ No newline at end of file
+}
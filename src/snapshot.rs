@@ -0,0 +1,244 @@
+use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use crate::geometry::SimpleBody;
+use crate::initial::file::FileFormat;
+
+/// magic number at the start of the binary snapshot format.
+pub const MAGIC: &[u8; 8] = b"NBODYSN1";
+
+/// magic number at the start of the binary snapshot format of 3D bodies.
+pub const MAGIC_3D: &[u8; 8] = b"NBODYSN3";
+
+/// Snapshot is the state of all bodies after a given step.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Snapshot {
+    pub step: usize,
+    pub time: f64,
+    pub bodies: Vec<SimpleBody>,
+}
+
+/// SnapshotWriter stores a sequence of snapshots.
+pub trait SnapshotWriter {
+    fn write(&mut self, step: usize, time: f64, bodies: &[SimpleBody]) -> io::Result<()>;
+
+    /// flushes everything written so far.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// CsvWriter writes one row per body and snapshot, with the columns step, time, id, x, y, vx, vy, ax, ay and m. In 3D there are the columns z, vz and az as well, after y, vy and ay.
+pub struct CsvWriter<W: Write> {
+    writer: W,
+    three_d: bool,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(mut writer: W, dimensions: usize) -> io::Result<Self> {
+        let three_d = dimensions == 3;
+        if three_d {
+            writeln!(writer, "step,time,id,x,y,z,vx,vy,vz,ax,ay,az,m")?;
+        } else {
+            writeln!(writer, "step,time,id,x,y,vx,vy,ax,ay,m")?;
+        }
+        Ok(CsvWriter { writer, three_d })
+    }
+}
+
+impl<W: Write> SnapshotWriter for CsvWriter<W> {
+    fn write(&mut self, step: usize, time: f64, bodies: &[SimpleBody]) -> io::Result<()> {
+        for (id, b) in bodies.iter().enumerate() {
+            if self.three_d {
+                writeln!(
+                    self.writer,
+                    "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                    step, time, id, b.x, b.y, b.z, b.vx, b.vy, b.vz, b.ax, b.ay, b.az, b.m
+                )?;
+            } else {
+                writeln!(
+                    self.writer,
+                    "{},{},{},{},{},{},{},{},{},{}",
+                    step, time, id, b.x, b.y, b.vx, b.vy, b.ax, b.ay, b.m
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// BinaryWriter writes the chunked binary format: the 8 byte magic number NBODYSN1, then one chunk per snapshot.
+///
+/// A chunk starts with the step (u64), the time (f64) and the number of bodies n (u64), followed by the columns x, y, vx, vy, ax, ay and m, each as n consecutive f64. Everything is little endian, so a chunk is 24 + 56 * n bytes long and readers can skip snapshots they do not need.
+///
+/// 3D snapshots start with NBODYSN3 instead, and their chunks hold the columns x, y, z, vx, vy, vz, ax, ay, az and m, 24 + 80 * n bytes.
+pub struct BinaryWriter<W: Write> {
+    writer: W,
+    three_d: bool,
+}
+
+/// the columns of a 2D chunk.
+const COLUMNS: [fn(&SimpleBody) -> f64; 7] = [|b| b.x, |b| b.y, |b| b.vx, |b| b.vy, |b| b.ax, |b| b.ay, |b| b.m];
+
+/// the columns of a 3D chunk.
+const COLUMNS_3D: [fn(&SimpleBody) -> f64; 10] = [
+    |b| b.x,
+    |b| b.y,
+    |b| b.z,
+    |b| b.vx,
+    |b| b.vy,
+    |b| b.vz,
+    |b| b.ax,
+    |b| b.ay,
+    |b| b.az,
+    |b| b.m,
+];
+
+impl<W: Write> BinaryWriter<W> {
+    pub fn new(mut writer: W, dimensions: usize) -> io::Result<Self> {
+        let three_d = dimensions == 3;
+        writer.write_all(if three_d { MAGIC_3D } else { MAGIC })?;
+        Ok(BinaryWriter { writer, three_d })
+    }
+}
+
+impl<W: Write> SnapshotWriter for BinaryWriter<W> {
+    fn write(&mut self, step: usize, time: f64, bodies: &[SimpleBody]) -> io::Result<()> {
+        self.writer.write_all(&(step as u64).to_le_bytes())?;
+        self.writer.write_all(&time.to_le_bytes())?;
+        self.writer.write_all(&(bodies.len() as u64).to_le_bytes())?;
+        let columns: &[fn(&SimpleBody) -> f64] = if self.three_d { &COLUMNS_3D } else { &COLUMNS };
+        for column in columns.iter() {
+            for b in bodies {
+                self.writer.write_all(&column(b).to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// BinaryReader iterates over the snapshots of the chunked binary format, 2D or 3D.
+pub struct BinaryReader<R: Read> {
+    reader: R,
+    three_d: bool,
+}
+
+impl<R: Read> BinaryReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        let three_d = if &magic == MAGIC {
+            false
+        } else if &magic == MAGIC_3D {
+            true
+        } else {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not a binary snapshot file",
+            ));
+        };
+        Ok(BinaryReader { reader, three_d })
+    }
+
+    fn read_u64(&mut self) -> io::Result<u64> {
+        let mut buffer = [0u8; 8];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(u64::from_le_bytes(buffer))
+    }
+
+    fn read_chunk(&mut self, step: u64) -> io::Result<Snapshot> {
+        let time = f64::from_bits(self.read_u64()?);
+        let count = self.read_u64()? as usize;
+        // the columns x, y, z, vx, vy, vz, ax, ay, az and m, those of z are not stored in 2D.
+        // They grow as they are read, so a corrupt count fails at the end of the file instead of allocating it.
+        let mut columns = Vec::with_capacity(10);
+        for k in 0..10 {
+            let column = if self.three_d || ![2, 5, 8].contains(&k) {
+                (0..count).map(|_| self.read_u64().map(f64::from_bits)).collect::<io::Result<Vec<_>>>()?
+            } else {
+                vec![0.0; count]
+            };
+            columns.push(column);
+        }
+        let bodies = (0..count)
+            .map(|i| SimpleBody {
+                x: columns[0][i],
+                y: columns[1][i],
+                z: columns[2][i],
+                vx: columns[3][i],
+                vy: columns[4][i],
+                vz: columns[5][i],
+                ax: columns[6][i],
+                ay: columns[7][i],
+                az: columns[8][i],
+                m: columns[9][i],
+            })
+            .collect();
+        Ok(Snapshot {
+            step: step as usize,
+            time,
+            bodies,
+        })
+    }
+}
+
+impl<R: Read> Iterator for BinaryReader<R> {
+    type Item = io::Result<Snapshot>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // a clean end of file is only allowed between two chunks
+        let mut buffer = [0u8; 8];
+        let mut read = 0;
+        while read < buffer.len() {
+            match self.reader.read(&mut buffer[read..]) {
+                Ok(0) if read == 0 => return None,
+                Ok(0) => return Some(Err(ErrorKind::UnexpectedEof.into())),
+                Ok(n) => read += n,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        Some(self.read_chunk(u64::from_le_bytes(buffer)))
+    }
+}
+
+/// Snapshots writes the state of the bodies to a file every few steps.
+pub struct Snapshots {
+    writer: Box<dyn SnapshotWriter>,
+    every: usize,
+}
+
+impl Snapshots {
+    /// creates the file at path, a snapshot of bodies in the given dimensions is written for every step that is a multiple of every.
+    pub fn create(path: &Path, format: FileFormat, every: usize, dimensions: usize) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let writer: Box<dyn SnapshotWriter> = match format {
+            FileFormat::Csv => Box::new(CsvWriter::new(file, dimensions)?),
+            FileFormat::Binary => Box::new(BinaryWriter::new(file, dimensions)?),
+        };
+        Ok(Snapshots {
+            writer,
+            every: every.max(1),
+        })
+    }
+
+    /// whether a snapshot is due after the given step.
+    pub fn is_due(&self, step: usize) -> bool {
+        step.is_multiple_of(self.every)
+    }
+
+    pub fn write(&mut self, step: usize, time: f64, bodies: &[SimpleBody]) -> io::Result<()> {
+        self.writer.write(step, time, bodies)
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer.finish()
+    }
+}
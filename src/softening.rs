@@ -0,0 +1,105 @@
+use std::fmt::{Display, Error, Formatter};
+use std::str::FromStr;
+
+/// Softening replaces the point mass 1 / r^2 force at short distances, so that close approaches stay finite. The softening length h is the Plummer radius, or the radius of the kernel beyond which the spline and Wendland forces are exactly Newtonian.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Softening {
+    /// point masses, 1 / r^2 all the way down.
+    None,
+    /// 1 / (r^2 + h^2), the force of a Plummer sphere.
+    Plummer,
+    /// the force of the cubic spline density kernel of Monaghan and Lattanzio, as in Gadget-2.
+    Spline,
+    /// the force of the Wendland C2 density kernel.
+    Wendland,
+}
+
+impl Softening {
+    pub const ALL: [Softening; 4] = [Softening::None, Softening::Plummer, Softening::Spline, Softening::Wendland];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Softening::None => "none",
+            Softening::Plummer => "plummer",
+            Softening::Spline => "spline",
+            Softening::Wendland => "wendland",
+        }
+    }
+
+    /// the code of the softening in the OpenMP kernels.
+    pub fn code(&self) -> i32 {
+        match self {
+            Softening::None => 0,
+            Softening::Plummer => 1,
+            Softening::Spline => 2,
+            Softening::Wendland => 3,
+        }
+    }
+
+    /// 1 / r^3 at the squared distance r2, softened with length h. A unit mass at distance d pulls with d * force(r2, h).
+    pub fn force(&self, r2: f64, h: f64) -> f64 {
+        match self {
+            Softening::None => 1.0 / r2 / r2.sqrt(),
+            Softening::Plummer => {
+                let s = r2 + h * h;
+                1.0 / s / s.sqrt()
+            }
+            Softening::Spline | Softening::Wendland if r2 >= h * h => 1.0 / r2 / r2.sqrt(),
+            Softening::Spline => {
+                let u = r2.sqrt() / h;
+                let f = if u < 0.5 {
+                    32.0 / 3.0 + u * u * (32.0 * u - 38.4)
+                } else {
+                    64.0 / 3.0 - 48.0 * u + 38.4 * u * u - 32.0 / 3.0 * u * u * u - 1.0 / 15.0 / (u * u * u)
+                };
+                f / (h * h * h)
+            }
+            Softening::Wendland => {
+                let q = r2.sqrt() / h;
+                let f = 14.0 + q * q * (-84.0 + q * (140.0 + q * (-90.0 + 21.0 * q)));
+                f / (h * h * h)
+            }
+        }
+    }
+
+    /// -1 / r at the squared distance r2, softened consistently with force.
+    pub fn potential(&self, r2: f64, h: f64) -> f64 {
+        match self {
+            Softening::None => -1.0 / r2.sqrt(),
+            Softening::Plummer => -1.0 / (r2 + h * h).sqrt(),
+            Softening::Spline | Softening::Wendland if r2 >= h * h => -1.0 / r2.sqrt(),
+            Softening::Spline => {
+                let u = r2.sqrt() / h;
+                let p = if u < 0.5 {
+                    -2.8 + u * u * (16.0 / 3.0 + u * u * (6.4 * u - 9.6))
+                } else {
+                    -3.2 + 1.0 / 15.0 / u + u * u * (32.0 / 3.0 + u * (-16.0 + u * (9.6 - 32.0 / 15.0 * u)))
+                };
+                p / h
+            }
+            Softening::Wendland => {
+                let q = r2.sqrt() / h;
+                let p = 3.0 + q * q * (-7.0 + q * q * (21.0 + q * (-28.0 + q * (15.0 - 3.0 * q))));
+                -p / h
+            }
+        }
+    }
+}
+
+impl Display for Softening {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Softening {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Softening::ALL
+            .iter()
+            .find(|e| e.name() == s)
+            .cloned()
+            .ok_or_else(|| format!("{} is not a valid softening", s))
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt::{Display, Error, Formatter};
+use std::str::FromStr;
+
+use crate::config::SimulationConfig;
+use crate::engine::Engine;
+use crate::geometry::SimpleBody;
+use crate::integrator::Integrator;
+
+/// Timestep selects how a step of the driver is divided into substeps.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Timestep {
+    /// one substep of the full length.
+    Fixed,
+    /// substeps as long as the body with the smallest timestep allows, shared by all bodies.
+    Adaptive,
+    /// hierarchical block timesteps: every body is advanced with its own power of two fraction of the step.
+    Block,
+}
+
+impl Timestep {
+    pub const ALL: [Timestep; 3] = [Timestep::Fixed, Timestep::Adaptive, Timestep::Block];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Timestep::Fixed => "fixed",
+            Timestep::Adaptive => "adaptive",
+            Timestep::Block => "block",
+        }
+    }
+}
+
+impl Display for Timestep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Timestep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Timestep::ALL
+            .iter()
+            .find(|e| e.name() == s)
+            .cloned()
+            .ok_or_else(|| format!("{} is not a valid timestep", s))
+    }
+}
+
+/// Criterion determines the largest timestep of a body.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Criterion {
+    /// eta * sqrt(radius / |a|), the time a body starting at rest needs to move by its radius.
+    Acceleration,
+    /// eta * |a| / |da/dt|, the time the acceleration needs to change by itself. The derivative is estimated from the last two force evaluations, until there are two the acceleration criterion is used.
+    Jerk,
+}
+
+impl Criterion {
+    pub const ALL: [Criterion; 2] = [Criterion::Acceleration, Criterion::Jerk];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Criterion::Acceleration => "acceleration",
+            Criterion::Jerk => "jerk",
+        }
+    }
+
+    /// the largest timestep of the body, jerk is the estimated magnitude of the derivative of its acceleration if known.
+    fn timestep(&self, eta: f64, radius: f64, body: &SimpleBody, jerk: Option<f64>) -> f64 {
+        let a = (body.ax * body.ax + body.ay * body.ay + body.az * body.az).sqrt();
+        match (self, jerk) {
+            (Criterion::Jerk, Some(jerk)) => eta * a / jerk,
+            _ if a > 0.0 => eta * (radius / a).sqrt(),
+            _ => f64::INFINITY,
+        }
+    }
+}
+
+impl Display for Criterion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Criterion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Criterion::ALL
+            .iter()
+            .find(|e| e.name() == s)
+            .cloned()
+            .ok_or_else(|| format!("{} is not a valid timestep criterion", s))
+    }
+}
+
+/// Stepper advances an engine by the steps of the driver, each one divided into substeps according to the timestep settings of the configuration.
+///
+/// A step of length dt is divided into 2^levels ticks, every substep is a whole number of ticks. So the substeps always add up to dt exactly, and the driver, the output and the checkpoints keep counting steps of length dt.
+///
+/// Block timesteps are integrated with leapfrog in kick-drift-kick form: a body on level k opens its substep of dt / 2^k with half a kick, drifts along with all the others and closes it with half a kick once its forces are recomputed. Only the bodies whose substep ends get new forces, and only then they may change their level. A body can move to a coarser level only at a tick its new substep is aligned to.
+///
+/// The collisions are resolved once at the start of every substep (adaptive) or step (block). Under MPI the root process decides on the substeps, the other processes follow.
+#[derive(Clone)]
+pub struct Stepper {
+    integrator: Integrator,
+    timestep: Timestep,
+    criterion: Criterion,
+    eta: f64,
+    radius: f64,
+    levels: u32,
+    /// the acceleration of every body at its last force evaluation, and the jerk estimated from the last two, for the jerk criterion
+    previous: Vec<(f64, f64, f64)>,
+    jerk: Vec<Option<f64>>,
+    /// the block level of every body
+    level: Vec<u32>,
+}
+
+impl Stepper {
+    /// fails if block timesteps are combined with another integrator than leapfrog.
+    pub fn new(config: &SimulationConfig) -> Result<Self, String> {
+        if config.timestep == Timestep::Block && config.integrator != Integrator::Leapfrog {
+            return Err(format!(
+                "block timesteps are integrated with leapfrog, not {}",
+                config.integrator
+            ));
+        }
+        Ok(Stepper {
+            integrator: config.integrator,
+            timestep: config.timestep,
+            criterion: config.criterion,
+            eta: config.eta,
+            radius: config.physics.radius,
+            levels: config.timestep_levels,
+            previous: Vec::new(),
+            jerk: Vec::new(),
+            level: Vec::new(),
+        })
+    }
+
+    /// initializes the engine with the bodies and computes their accelerations. Any history of a previous run is dropped.
+    pub fn init(&mut self, engine: &mut dyn Engine, bodies: &[SimpleBody], dt: f64) {
+        self.integrator.init(engine, bodies);
+        let bodies = engine.bodies();
+        self.previous = bodies.iter().map(|b| (b.ax, b.ay, b.az)).collect();
+        self.jerk = vec![None; bodies.len()];
+        self.level = bodies.iter().enumerate().map(|(i, b)| self.wanted(i, b, dt)).collect();
+    }
+
+    /// advances the engine by one step of length dt.
+    pub fn step(&mut self, engine: &mut dyn Engine, dt: f64) {
+        match self.timestep {
+            Timestep::Fixed => self.integrator.step(engine, dt),
+            Timestep::Adaptive => self.adaptive(engine, dt),
+            Timestep::Block => self.block(engine, dt),
+        }
+    }
+
+    /// the number of ticks of a substep on the given level.
+    fn length(&self, level: u32) -> u64 {
+        1 << (self.levels - level)
+    }
+
+    /// the level the criterion asks for body i, the finest level whose substep is not longer than the timestep of the body.
+    fn wanted(&self, i: usize, body: &SimpleBody, dt: f64) -> u32 {
+        let timestep = self.criterion.timestep(self.eta, self.radius, body, self.jerk[i]);
+        let mut level = 0;
+        while level < self.levels && dt / (1u64 << level) as f64 > timestep {
+            level += 1;
+        }
+        level
+    }
+
+    /// records the new acceleration of body i, computed elapsed seconds after the previous one.
+    fn remember(&mut self, i: usize, body: &SimpleBody, elapsed: f64) {
+        let (ax, ay, az) = self.previous[i];
+        let change = (body.ax - ax) * (body.ax - ax) + (body.ay - ay) * (body.ay - ay) + (body.az - az) * (body.az - az);
+        self.jerk[i] = Some(change.sqrt() / elapsed);
+        self.previous[i] = (body.ax, body.ay, body.az);
+    }
+
+    fn adaptive(&mut self, engine: &mut dyn Engine, dt: f64) {
+        let ticks = 1u64 << self.levels;
+        let tick = dt / ticks as f64;
+        let mut now = 0;
+        while now < ticks {
+            let bodies = engine.bodies();
+            let level = (0..bodies.len())
+                .map(|i| self.wanted(i, &bodies[i], dt))
+                .max()
+                .unwrap_or(0);
+            // the substep may not cross the end of the step
+            let length = self.length(level).min(ticks - now);
+            let length = engine.sync_value(length as f64) as u64;
+            self.integrator.step(engine, length as f64 * tick);
+            for (i, b) in engine.bodies().iter().enumerate() {
+                self.remember(i, b, length as f64 * tick);
+            }
+            now += length;
+        }
+    }
+
+    fn block(&mut self, engine: &mut dyn Engine, dt: f64) {
+        let ticks = 1u64 << self.levels;
+        let tick = dt / ticks as f64;
+        let n = self.level.len();
+        // the tick every body started its current substep at
+        let mut start = vec![0; n];
+        engine.collide();
+        let opening = self.level.iter().map(|&k| 0.5 * self.length(k) as f64 * tick).collect::<Vec<_>>();
+        engine.kick_each(&opening);
+        let mut now = 0;
+        while now < ticks {
+            let next = (0..n).map(|i| start[i] + self.length(self.level[i])).min().unwrap_or(ticks);
+            let next = engine.sync_value(next as f64) as u64;
+            engine.drift((next - now) as f64 * tick, false);
+            engine.confine();
+            now = next;
+            let ending = (0..n)
+                .map(|i| start[i] + self.length(self.level[i]) == now)
+                .collect::<Vec<_>>();
+            engine.accelerate_some(&ending);
+            let bodies = engine.bodies();
+            let mut kicks = vec![0.0; n];
+            for i in (0..n).filter(|&i| ending[i]) {
+                let elapsed = self.length(self.level[i]) as f64 * tick;
+                // closes the substep of the body, and opens the next one unless the step is over
+                kicks[i] = 0.5 * elapsed;
+                if now < ticks {
+                    let mut level = self.wanted(i, &bodies[i], dt);
+                    while now % self.length(level) != 0 {
+                        level += 1;
+                    }
+                    self.level[i] = level;
+                    start[i] = now;
+                    kicks[i] += 0.5 * self.length(level) as f64 * tick;
+                }
+                self.remember(i, &bodies[i], elapsed);
+            }
+            engine.kick_each(&kicks);
+        }
+        // the levels of the next step
+        let bodies = engine.bodies();
+        self.level = bodies.iter().enumerate().map(|(i, b)| self.wanted(i, b, dt)).collect();
+    }
+}
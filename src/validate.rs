@@ -0,0 +1,159 @@
+use std::fmt::{Display, Error, Formatter};
+
+use crate::config::{EngineKind, SimulationConfig};
+use crate::engine::{new_engine, Engine};
+use crate::geometry::SimpleBody;
+use crate::timestep::Stepper;
+
+/// Divergence is the largest distance between the positions and between the velocities of the same body in two body sets.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct Divergence {
+    pub position: f64,
+    pub velocity: f64,
+}
+
+impl Divergence {
+    /// compares the bodies pairwise, both sets have to list the bodies in the same order.
+    pub fn between(a: &[SimpleBody], b: &[SimpleBody]) -> Self {
+        assert_eq!(a.len(), b.len(), "the body sets have different sizes");
+        let mut res = Divergence::default();
+        for (i, j) in a.iter().zip(b.iter()) {
+            let position = ((i.x - j.x) * (i.x - j.x) + (i.y - j.y) * (i.y - j.y) + (i.z - j.z) * (i.z - j.z)).sqrt();
+            let velocity =
+                ((i.vx - j.vx) * (i.vx - j.vx) + (i.vy - j.vy) * (i.vy - j.vy) + (i.vz - j.vz) * (i.vz - j.vz)).sqrt();
+            // NaN never compares greater, so a NaN divergence has to be kept explicitly
+            if position > res.position || position.is_nan() {
+                res.position = position;
+            }
+            if velocity > res.velocity || velocity.is_nan() {
+                res.velocity = velocity;
+            }
+        }
+        res
+    }
+
+    /// whether both divergences are within the tolerances of the configuration.
+    pub fn within(&self, config: &SimulationConfig) -> bool {
+        self.position <= config.position_tolerance && self.velocity <= config.velocity_tolerance
+    }
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "position {:e}, velocity {:e}", self.position, self.velocity)
+    }
+}
+
+/// ForceErrors are statistics of the relative errors |a - a_exact| / |a_exact| of the accelerations of the bodies against exact ones.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct ForceErrors {
+    pub mean: f64,
+    pub rms: f64,
+    pub median: f64,
+    /// the error 99% of the bodies stay below
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl ForceErrors {
+    /// compares the accelerations pairwise, both sets have to list the bodies in the same order. Bodies without an exact acceleration are skipped.
+    pub fn between(a: &[SimpleBody], exact: &[SimpleBody]) -> Self {
+        assert_eq!(a.len(), exact.len(), "the body sets have different sizes");
+        let mut errors = a
+            .iter()
+            .zip(exact.iter())
+            .filter(|(_, j)| j.ax != 0.0 || j.ay != 0.0 || j.az != 0.0)
+            .map(|(i, j)| {
+                let norm = (j.ax * j.ax + j.ay * j.ay + j.az * j.az).sqrt();
+                let error = ((i.ax - j.ax) * (i.ax - j.ax) + (i.ay - j.ay) * (i.ay - j.ay) + (i.az - j.az) * (i.az - j.az)).sqrt();
+                error / norm
+            })
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            return ForceErrors::default();
+        }
+        errors.sort_by(|x, y| x.total_cmp(y));
+        let n = errors.len() as f64;
+        let quantile = |q: f64| errors[((n - 1.0) * q).round() as usize];
+        ForceErrors {
+            mean: errors.iter().sum::<f64>() / n,
+            rms: (errors.iter().map(|e| e * e).sum::<f64>() / n).sqrt(),
+            median: quantile(0.5),
+            p99: quantile(0.99),
+            max: errors[errors.len() - 1],
+        }
+    }
+}
+
+impl Display for ForceErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(
+            f,
+            "mean {:e}, rms {:e}, median {:e}, 99% {:e}, max {:e}",
+            self.mean, self.rms, self.median, self.p99, self.max
+        )
+    }
+}
+
+/// initializes both engines with the same bodies and compares the accelerations of a against the exact ones of b. The accelerations are computed twice, so that the relative opening criterion has the first ones to measure its error against. Under MPI only the root process gets a meaningful result.
+pub fn measure_forces(a: &mut dyn Engine, exact: &mut dyn Engine, bodies: &[SimpleBody]) -> ForceErrors {
+    let accelerate = |engine: &mut dyn Engine| {
+        engine.init(bodies);
+        engine.accelerate();
+        engine.accelerate();
+    };
+    accelerate(a);
+    accelerate(exact);
+    if a.is_root() {
+        ForceErrors::between(&a.bodies(), &exact.bodies())
+    } else {
+        ForceErrors::default()
+    }
+}
+
+/// the force errors of the engine of the configuration against the brute force engine, from the same bodies.
+pub fn force_errors(config: &SimulationConfig, bodies: &[SimpleBody]) -> Result<ForceErrors, String> {
+    let exact = SimulationConfig {
+        engine: EngineKind::BruteForce,
+        ..config.clone()
+    };
+    let mut a = new_engine(config)?;
+    let mut b = new_engine(&exact)?;
+    Ok(measure_forces(a.as_mut(), b.as_mut(), bodies))
+}
+
+/// initializes both engines with the same bodies, advances them steps times by dt with a copy of the stepper each and returns their divergence. Under MPI only the root process gets a meaningful result.
+pub fn compare(
+    a: &mut dyn Engine,
+    b: &mut dyn Engine,
+    stepper: &Stepper,
+    bodies: &[SimpleBody],
+    steps: usize,
+    dt: f64,
+) -> Divergence {
+    let mut sa = stepper.clone();
+    let mut sb = stepper.clone();
+    sa.init(a, bodies, dt);
+    sb.init(b, bodies, dt);
+    for _ in 0..steps {
+        sa.step(a, dt);
+        sb.step(b, dt);
+    }
+    if a.is_root() && b.is_root() {
+        Divergence::between(&a.bodies(), &b.bodies())
+    } else {
+        Divergence::default()
+    }
+}
+
+/// runs the engine of the configuration against config.reference for config.steps steps from the same bodies.
+pub fn validate(config: &SimulationConfig, bodies: &[SimpleBody], dt: f64) -> Result<Divergence, String> {
+    let reference = SimulationConfig {
+        engine: config.reference,
+        ..config.clone()
+    };
+    let stepper = Stepper::new(config)?;
+    let mut a = new_engine(config)?;
+    let mut b = new_engine(&reference)?;
+    Ok(compare(a.as_mut(), b.as_mut(), &stepper, bodies, config.steps, dt))
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use nbody::benchmark::Report;
+
+fn report(times: &[u64]) -> Report {
+    let times: Vec<Duration> = times.iter().map(|x| Duration::from_millis(*x)).collect();
+    Report::new("Tree", 1001, 2, 3, &times)
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * b.abs().max(1.0)
+}
+
+#[test]
+fn statistics_of_fixed_timings() {
+    let odd = report(&[4, 1, 3]);
+    assert_eq!(odd.times, vec![4.0, 1.0, 3.0]);
+    assert_eq!((odd.min(), odd.median(), odd.max(), odd.total()), (1.0, 3.0, 4.0, 8.0));
+
+    let even = report(&[4, 1, 3, 2]);
+    assert_eq!(even.median(), 2.5);
+    assert_eq!(even.mean(), 2.5);
+    assert!(close(even.stddev(), (5.0_f64 / 3.0).sqrt()), "{}", even.stddev());
+    // 1001 * 1000 interactions every 2.5 ms
+    assert!(close(even.throughput(), 4.004e8), "{}", even.throughput());
+}
+
+#[test]
+fn empty_and_single_step_reports() {
+    let empty = report(&[]);
+    assert_eq!((empty.min(), empty.median(), empty.mean(), empty.stddev(), empty.throughput()), (0.0, 0.0, 0.0, 0.0, 0.0));
+    let single = report(&[7]);
+    assert_eq!((single.median(), single.stddev()), (7.0, 0.0));
+}
+
+#[test]
+fn json_reports_carry_the_statistics() {
+    let json: serde_json::Value = serde_json::from_str(&report(&[4, 1, 3]).to_json()).unwrap();
+    assert_eq!(json["engine"], "Tree");
+    assert_eq!((json["bodies"].as_u64(), json["steps"].as_u64()), (Some(1001), Some(3)));
+    assert_eq!((json["median_ms"].as_f64(), json["total_ms"].as_f64()), (Some(3.0), Some(8.0)));
+    assert_eq!(json["step_ms"], serde_json::json!([4.0, 1.0, 3.0]));
+}
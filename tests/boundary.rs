@@ -0,0 +1,164 @@
+use nalgebra::Vector2;
+use nbody::boundary::{Boundary, PeriodicSum, Space};
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::global::Physics;
+use nbody::{initial, new_engine, SimpleBody};
+
+const ENGINES: [EngineKind; 4] = [EngineKind::BruteForce, EngineKind::Rayon, EngineKind::Tree, EngineKind::RayonTree];
+
+mod common;
+
+use common::body;
+
+fn config(engine: EngineKind, boundary: Boundary) -> SimulationConfig {
+    common::config(
+        engine,
+        200,
+        5,
+        Physics {
+            boundary,
+            ..Physics::default()
+        },
+    )
+}
+
+/// a body of unit mass at (x, y) moving along x.
+/// the bodies after one call of f on an engine of the configuration initialized with them.
+fn after<F: Fn(&mut dyn nbody::Engine)>(config: &SimulationConfig, bodies: &[SimpleBody], f: F) -> Vec<SimpleBody> {
+    let mut engine = new_engine(config).unwrap();
+    engine.init(bodies);
+    f(engine.as_mut());
+    engine.bodies()
+}
+
+#[test]
+fn reflective_boundaries_keep_the_restitution() {
+    for engine in ENGINES.iter() {
+        for restitution in [0.0, 0.5, 1.0].iter() {
+            let mut config = config(*engine, Boundary::Reflective);
+            config.physics.restitution = *restitution;
+            let bodies = [body([config.domain_width + 1.0, 75.0, 0.0], [10.0, 0.0, 0.0], 1.0)];
+            let res = after(&config, &bodies, |e| e.confine());
+            assert_eq!(res[0].vx, -10.0 * restitution, "{}", engine);
+            assert!(res[0].x < config.domain_width, "{}", engine);
+        }
+    }
+}
+
+#[test]
+fn periodic_boundaries_wrap_the_bodies() {
+    for engine in ENGINES.iter() {
+        let config = config(*engine, Boundary::Periodic);
+        let (w, h) = (config.domain_width, config.domain_height);
+        let bodies = [body([w + 1.0, -2.0, 0.0], [10.0, 0.0, 0.0], 1.0), body([-1.0, h + 3.0, 0.0], [-10.0, 0.0, 0.0], 1.0)];
+        let res = after(&config, &bodies, |e| e.confine());
+        assert_eq!((res[0].x, res[0].y, res[0].vx), (1.0, h - 2.0, 10.0), "{}", engine);
+        assert_eq!((res[1].x, res[1].y, res[1].vx), (w - 1.0, 3.0, -10.0), "{}", engine);
+    }
+}
+
+#[test]
+fn open_boundaries_let_the_bodies_go() {
+    for engine in ENGINES.iter() {
+        let config = config(*engine, Boundary::Open);
+        let bodies = [body([-500.0, 75.0, 0.0], [-10.0, 0.0, 0.0], 1.0), body([3000.0, -40.0, 0.0], [10.0, 0.0, 0.0], 1.0)];
+        let res = after(&config, &bodies, |e| {
+            e.confine();
+            e.accelerate();
+        });
+        assert_eq!((res[0].x, res[0].vx), (-500.0, -10.0), "{}", engine);
+        assert_eq!((res[1].x, res[1].vx), (3000.0, 10.0), "{}", engine);
+        assert!(res[0].ax > 0.0 && res[1].ax < 0.0, "{}", engine);
+    }
+}
+
+#[test]
+fn periodic_forces_pull_through_the_nearest_image() {
+    for engine in ENGINES.iter() {
+        let config = config(*engine, Boundary::Periodic);
+        let w = config.domain_width;
+        let bodies = [body([1.0, 75.0, 0.0], [0.0; 3], 1.0), body([w - 1.0, 75.0, 0.0], [0.0; 3], 1.0)];
+        let res = after(&config, &bodies, |e| e.accelerate());
+        let expected = config.physics.g / 4.0;
+        assert!((res[0].ax + expected).abs() < 1e-12, "{}: {}", engine, res[0].ax);
+        assert!((res[1].ax - expected).abs() < 1e-12, "{}: {}", engine, res[1].ax);
+    }
+}
+
+#[test]
+fn periodic_collisions_reach_across_the_edge() {
+    for engine in ENGINES.iter() {
+        let config = config(*engine, Boundary::Periodic);
+        let w = config.domain_width;
+        let bodies = [body([0.3, 75.0, 0.0], [-1.0, 0.0, 0.0], 1.0), body([w - 0.3, 75.0, 0.0], [1.0, 0.0, 0.0], 1.0)];
+        let res = after(&config, &bodies, |e| e.collide());
+        assert_eq!((res[0].vx, res[1].vx), (1.0, -1.0), "{}", engine);
+    }
+}
+
+#[test]
+fn ewald_sums_match_the_direct_lattice_sum() {
+    let mut config = config(EngineKind::BruteForce, Boundary::Periodic);
+    config.physics.periodic_sum = PeriodicSum::Ewald;
+    let space = Space::new(&config);
+    let (w, h) = (config.domain_width, config.domain_height);
+    // a point of the Ewald table, so that no interpolation is involved
+    let (x, y) = (w / 4.0, h / 8.0);
+    // the symmetric lattice sum converges like 1 / n, which the extrapolation of two sums removes
+    let direct = |n: i32| {
+        let mut res = (0.0, 0.0);
+        for i in -n..=n {
+            for j in -n..=n {
+                let (dx, dy) = (x + i as f64 * w, y + j as f64 * h);
+                let r3 = (dx * dx + dy * dy).powf(1.5);
+                res.0 += dx / r3;
+                res.1 += dy / r3;
+            }
+        }
+        res
+    };
+    let (coarse, fine) = (direct(200), direct(400));
+    let expected = (2.0 * fine.0 - coarse.0, 2.0 * fine.1 - coarse.1);
+    let field = space.field(Vector2::new(x, y), x * x + y * y);
+    let (fx, fy) = (field.x, field.y);
+    let g = config.physics.g;
+    assert!((fx / g - expected.0).abs() < 1e-4 * expected.0.abs(), "{} {}", fx / g, expected.0);
+    assert!((fy / g - expected.1).abs() < 1e-4 * expected.1.abs(), "{} {}", fy / g, expected.1);
+    // half a box away every image has a mirror image
+    let fx = space.field(Vector2::new(w / 2.0, 0.0), w * w / 4.0).x;
+    assert!(fx.abs() < 1e-9, "{}", fx);
+}
+
+#[test]
+fn engines_agree_in_every_boundary() {
+    for (boundary, sum) in [
+        (Boundary::Open, PeriodicSum::MinimumImage),
+        (Boundary::Periodic, PeriodicSum::MinimumImage),
+        (Boundary::Periodic, PeriodicSum::Ewald),
+    ]
+    .iter()
+    {
+        let mut reference = config(EngineKind::BruteForce, *boundary);
+        reference.physics.periodic_sum = *sum;
+        // the tree never approximates a node, so that it computes the same sums in another order
+        reference.physics.dist_scale_limit = 1e-12;
+        let mut bodies = initial::generate(&reference);
+        bodies[0].x = -300.0;
+        bodies[1].y = 400.0;
+        for b in bodies.iter_mut() {
+            b.confine(&Space::new(&reference));
+        }
+        let expected = after(&reference, &bodies, |e| e.accelerate());
+        for engine in ENGINES.iter() {
+            let config = SimulationConfig {
+                engine: *engine,
+                ..reference.clone()
+            };
+            let res = after(&config, &bodies, |e| e.accelerate());
+            for (a, b) in res.iter().zip(expected.iter()) {
+                let scale = b.ax.abs().max(b.ay.abs()).max(1.0);
+                assert!((a.ax - b.ax).abs() < 1e-9 * scale && (a.ay - b.ay).abs() < 1e-9 * scale, "{} {}", boundary, engine);
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! fixtures shared by the integration tests, every test file uses some of them.
+#![allow(dead_code)]
+
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::global::Physics;
+use nbody::{new_engine, SimpleBody};
+
+/// the step of the orbit tests.
+pub const DT: f64 = 0.01;
+
+/// one orbit of a binary takes about ORBIT_STEPS steps of DT.
+pub const ORBIT_STEPS: usize = 2500;
+
+/// a run of size bodies generated from seed on the engine with the given physics, the defaults otherwise.
+pub fn config(engine: EngineKind, size: usize, seed: u64, physics: Physics) -> SimulationConfig {
+    SimulationConfig {
+        engine,
+        size,
+        seed,
+        physics,
+        ..SimulationConfig::default()
+    }
+}
+
+/// a body of mass m at position moving with velocity, without acceleration.
+pub fn body(position: [f64; 3], velocity: [f64; 3], m: f64) -> SimpleBody {
+    let [x, y, z] = position;
+    let [vx, vy, vz] = velocity;
+    SimpleBody {
+        x,
+        y,
+        z,
+        m,
+        vx,
+        vy,
+        vz,
+        ..SimpleBody::default()
+    }
+}
+
+/// the bodies with their accelerations computed by the engine of the configuration.
+pub fn accelerated(config: &SimulationConfig, bodies: &[SimpleBody]) -> Vec<SimpleBody> {
+    let mut engine = new_engine(config).unwrap();
+    config.integrator.init(engine.as_mut(), bodies);
+    engine.bodies()
+}
+
+/// two bodies of mass 50 on an orbit with the given eccentricity and semi-major axis 20 around the centre of the simulation space, starting at apocentre, far from the walls.
+pub fn binary(eccentricity: f64) -> Vec<SimpleBody> {
+    // the separation at apocentre is a * (1 + e) and the relative speed sqrt(G * M * (1 - e) / (a * (1 + e))) with G * M = 500
+    let d = 20.0 * (1.0 + eccentricity);
+    let v = (500.0 * (1.0 - eccentricity) / d).sqrt();
+    vec![
+        body([100.0 - d / 2.0, 75.0, 0.0], [0.0, -v / 2.0, 0.0], 50.0),
+        body([100.0 + d / 2.0, 75.0, 0.0], [0.0, v / 2.0, 0.0], 50.0),
+    ]
+}
@@ -0,0 +1,127 @@
+use std::fs;
+use std::path::Path;
+
+use nbody::config::{EngineKind, Mode, SimulationConfig};
+use nbody::config_file::{load, RunFile};
+use nbody::Integrator;
+
+/// the configuration described by the TOML text, on top of the defaults.
+fn from_toml(text: &str) -> Result<SimulationConfig, String> {
+    let mut config = SimulationConfig::default();
+    RunFile::from_toml(text)?.apply(&mut config)?;
+    config.check()?;
+    Ok(config)
+}
+
+#[test]
+fn the_example_run_file_is_valid() {
+    let config = load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("run.toml")).unwrap();
+    assert_eq!(config.engine, EngineKind::Tree);
+    assert_eq!(config.mode, Mode::Benchmark);
+    assert_eq!(config.seed, 42);
+    assert_eq!(config.steps, 100);
+    assert_eq!(config.diagnostics_every, 10);
+}
+
+#[test]
+fn missing_keys_keep_the_defaults() {
+    let config = from_toml("").unwrap();
+    let default = SimulationConfig::default();
+    assert_eq!(config.engine, default.engine);
+    assert_eq!(config.physics, default.physics);
+    assert_eq!(config.size, default.size);
+}
+
+#[test]
+fn toml_and_json_describe_the_same_run() {
+    let toml = from_toml(
+        "engine = \"rayon\"\n\
+         [scenario]\nsize = 300\n\
+         [physics]\ng = 1.5\n\
+         [integration]\nintegrator = \"rk4\"\neta = 0.01\n",
+    )
+    .unwrap();
+    let mut json = SimulationConfig::default();
+    RunFile::from_json(
+        r#"{"engine": "rayon", "scenario": {"size": 300}, "physics": {"g": 1.5},
+            "integration": {"integrator": "rk4", "eta": 0.01}}"#,
+    )
+    .unwrap()
+    .apply(&mut json)
+    .unwrap();
+    for config in [toml, json].iter() {
+        assert_eq!(config.engine, EngineKind::Rayon);
+        assert_eq!(config.size, 300);
+        assert_eq!(config.physics.g, 1.5);
+        assert_eq!(config.integrator, Integrator::Rk4);
+        assert_eq!(config.eta, 0.01);
+    }
+}
+
+#[test]
+fn unknown_keys_are_errors() {
+    assert!(from_toml("engines = \"tree\"").is_err());
+    assert!(from_toml("[physics]\ngravity = 1.0").is_err());
+    assert!(from_toml("[camera]\nzoom = 2.0").is_err());
+}
+
+#[test]
+fn invalid_values_are_errors() {
+    for text in [
+        "engine = \"warp\"",
+        "threads = -1",
+        "steps = 0",
+        "[scenario]\nsize = \"many\"",
+        "[physics]\ndt = 0.0",
+        "[physics]\nradius = -0.5",
+        "[physics]\nsoftening = \"gaussian\"",
+        "[physics]\nsoftening_length = 0.0",
+        "[physics]\nboundary = \"closed\"",
+        "[physics]\nrestitution = 1.5",
+        "[integration]\ntimestep = \"variable\"",
+        "[integration]\nlevels = 31",
+        "[output]\nsnapshot_format = \"hdf5\"",
+        "[validate]\nposition_tolerance = -1.0",
+    ]
+    .iter()
+    {
+        assert!(from_toml(text).is_err(), "{} is accepted", text);
+    }
+}
+
+#[test]
+fn the_domain_must_be_wider_than_a_body() {
+    let config = SimulationConfig {
+        domain_width: 1.0,
+        domain_height: 1.0,
+        ..SimulationConfig::default()
+    };
+    assert!(config.check().is_err());
+    let mut huge = SimulationConfig::default();
+    huge.physics.radius = 200.0;
+    assert!(huge.check().is_err());
+    // the depth only counts in 3D
+    let flat = SimulationConfig {
+        domain_depth: 1.0,
+        ..SimulationConfig::default()
+    };
+    assert!(flat.check().is_ok());
+    assert!(SimulationConfig { dimensions: 3, ..flat }.check().is_err());
+}
+
+#[test]
+fn relative_paths_are_relative_to_the_file() {
+    let directory = std::env::temp_dir().join(format!("nbody-config-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("run.json");
+    fs::write(
+        &path,
+        r#"{"output": {"checkpoint": "run.ck", "json": "/tmp/bench.json"}, "diagnostics": {"file": "out/energy.csv"}}"#,
+    )
+    .unwrap();
+    let config = load(&path).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(config.checkpoint, Some(directory.join("run.ck")));
+    assert_eq!(config.diagnostics, Some(directory.join("out/energy.csv")));
+    assert_eq!(config.json, Some(Path::new("/tmp/bench.json").to_path_buf()));
+}
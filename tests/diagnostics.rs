@@ -0,0 +1,50 @@
+use nbody::boundary::Space;
+use nbody::config::SimulationConfig;
+use nbody::diagnostics::Diagnostics;
+
+mod common;
+
+use common::body;
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-12 * b.abs().max(1.0)
+}
+
+#[test]
+fn a_pair_is_measured_by_hand() {
+    // masses 1 and 3, 5 apart, with G = 5 and no softening
+    let bodies = [body([10.0, 20.0, 0.0], [3.0, 0.0, 0.0], 1.0), body([14.0, 23.0, 0.0], [-1.0, 2.0, 0.0], 3.0)];
+    let space = Space::new(&SimulationConfig::default());
+    let expected = Diagnostics {
+        kinetic: 0.5 * 9.0 + 0.5 * 3.0 * 5.0,
+        potential: -5.0 * 3.0 / 5.0,
+        momentum_y: 6.0,
+        // 1 * (10 * 0 - 20 * 3) + 3 * (14 * 2 + 23 * 1)
+        angular_momentum: 93.0,
+        center_x: 13.0,
+        center_y: 22.25,
+        ..Diagnostics::default()
+    };
+    // the quadtree of two bodies sums the pair exactly as well
+    for exact_limit in [2, 0].iter() {
+        let res = Diagnostics::measure(&bodies, &space, *exact_limit);
+        assert_eq!(Diagnostics { potential: expected.potential, ..res }, expected);
+        assert!(close(res.potential, expected.potential), "{}", res.potential);
+        assert!(close(res.energy(), 9.0));
+    }
+}
+
+#[test]
+fn a_pair_is_measured_by_hand_in_3d() {
+    let bodies = [body([1.0, 2.0, 2.0], [0.0, 0.0, 1.0], 2.0), body([1.0, 2.0, 6.0], [0.0, 1.0, 0.0], 2.0)];
+    let space = Space::new(&SimulationConfig {
+        dimensions: 3,
+        ..SimulationConfig::default()
+    });
+    let res = Diagnostics::measure(&bodies, &space, 2);
+    assert_eq!((res.kinetic, res.momentum_y, res.momentum_z), (2.0, 2.0, 2.0));
+    // 2 * (2 * 1 - 2 * 0) + 2 * (2 * 0 - 6 * 1), then 2 * (0 - 1 * 1) and 2 * (1 * 1)
+    assert_eq!((res.angular_momentum_x, res.angular_momentum_y, res.angular_momentum), (-8.0, -2.0, 2.0));
+    assert_eq!((res.center_x, res.center_y, res.center_z), (1.0, 2.0, 4.0));
+    assert!(close(res.potential, -5.0 * 4.0 / 4.0), "{}", res.potential);
+}
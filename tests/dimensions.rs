@@ -0,0 +1,224 @@
+use nbody::boundary::{Boundary, PeriodicSum};
+use nbody::checkpoint::Checkpoint;
+use nalgebra::{Vector2, Vector3};
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::geometry::{Cube, Square};
+use nbody::global::Physics;
+use nbody::initial::file::{read_binary, read_csv, write_binary, write_csv};
+use nbody::snapshot::{BinaryReader, BinaryWriter, SnapshotWriter};
+use nbody::{initial, new_engine, SimpleBody};
+
+const ENGINES_3D: [EngineKind; 5] =
+    [EngineKind::BruteForce, EngineKind::Rayon, EngineKind::Tree, EngineKind::PThread, EngineKind::RayonTree];
+
+mod common;
+
+use common::{accelerated, body};
+
+fn config(engine: EngineKind, physics: Physics) -> SimulationConfig {
+    SimulationConfig {
+        dimensions: 3,
+        domain_depth: 100.0,
+        ..common::config(engine, 200, 11, physics)
+    }
+}
+
+#[test]
+fn generated_bodies_fill_the_box() {
+    let config = config(EngineKind::Tree, Physics::default());
+    let bodies = initial::generate(&config);
+    assert!(bodies.iter().all(|b| b.z > 0.0 && b.z < config.domain_depth));
+    assert!(bodies.iter().any(|b| b.z > config.domain_depth / 2.0));
+
+    // the 2D bodies of a seed do not change
+    let flat = SimulationConfig {
+        dimensions: 2,
+        ..config.clone()
+    };
+    let bodies = initial::generate(&flat);
+    assert!(bodies.iter().all(|b| b.z == 0.0));
+    assert_eq!(bodies, initial::generate(&SimulationConfig { domain_depth: 5.0, ..flat }));
+}
+
+#[test]
+fn engines_agree_in_3d() {
+    // a tree that never approximates sums the same pairs as the brute force
+    let physics = Physics {
+        dist_scale_limit: 0.0,
+        ..Physics::default()
+    };
+    let bodies = initial::generate(&config(EngineKind::BruteForce, physics));
+    let expected = accelerated(&config(EngineKind::BruteForce, physics), &bodies);
+    assert!(expected.iter().any(|b| b.az != 0.0));
+    for engine in ENGINES_3D.iter() {
+        let res = accelerated(&config(*engine, physics), &bodies);
+        for (a, b) in res.iter().zip(expected.iter()) {
+            let scale = b.ax.abs() + b.ay.abs() + b.az.abs();
+            let error = (a.ax - b.ax).abs() + (a.ay - b.ay).abs() + (a.az - b.az).abs();
+            assert!(error <= 1e-9 * scale, "{}: {:?} {:?}", engine, a, b);
+        }
+    }
+}
+
+#[test]
+fn flat_3d_runs_match_2d_ones() {
+    // bodies in a plane of constant z feel the same forces as in 2D
+    let physics = Physics {
+        dist_scale_limit: 0.0,
+        ..Physics::default()
+    };
+    let flat = SimulationConfig {
+        dimensions: 2,
+        ..config(EngineKind::BruteForce, physics)
+    };
+    let bodies = initial::generate(&flat);
+    let lifted = bodies.iter().map(|b| SimpleBody { z: 30.0, ..*b }).collect::<Vec<_>>();
+    for engine in ENGINES_3D.iter() {
+        let expected = accelerated(&SimulationConfig { engine: *engine, ..flat.clone() }, &bodies);
+        let res = accelerated(&config(*engine, physics), &lifted);
+        for (a, b) in res.iter().zip(expected.iter()) {
+            assert_eq!(a.az, 0.0, "{}", engine);
+            let error = (a.ax - b.ax).abs() + (a.ay - b.ay).abs();
+            assert!(error <= 1e-9 * (b.ax.abs() + b.ay.abs()), "{}: {:?} {:?}", engine, a, b);
+        }
+    }
+}
+
+#[test]
+fn boxes_split_into_2_to_the_d_children() {
+    let square = Square(Vector2::new(4.0, 2.0), Vector2::new(0.0, 0.0));
+    let quadrants = square.split();
+    assert_eq!(quadrants.len(), 4);
+    assert_eq!((quadrants[1].0, quadrants[1].1), (Vector2::new(4.0, 1.0), Vector2::new(2.0, 0.0)));
+    assert_eq!((quadrants[2].0, quadrants[2].1), (Vector2::new(2.0, 2.0), Vector2::new(0.0, 1.0)));
+
+    let cube: Cube = Square(Vector3::new(4.0, 2.0, 8.0), Vector3::new(0.0, 0.0, 0.0));
+    let octants = cube.split();
+    assert_eq!(octants.len(), 8);
+    for (k, octant) in octants.iter().enumerate() {
+        assert_eq!(octant.0 - octant.1, Vector3::new(2.0, 1.0, 4.0));
+        assert_eq!(octant.1.z, if k & 4 == 0 { 0.0 } else { 4.0 });
+    }
+}
+
+#[test]
+fn the_tree_approximates_in_3d() {
+    let physics = Physics::default();
+    let bodies = initial::generate(&config(EngineKind::BruteForce, physics));
+    let expected = accelerated(&config(EngineKind::BruteForce, physics), &bodies);
+    let res = accelerated(&config(EngineKind::Tree, physics), &bodies);
+    let error: f64 = res
+        .iter()
+        .zip(expected.iter())
+        .map(|(a, b)| ((a.ax - b.ax).powi(2) + (a.ay - b.ay).powi(2) + (a.az - b.az).powi(2)).sqrt())
+        .sum();
+    let total: f64 = expected.iter().map(|b| (b.ax * b.ax + b.ay * b.ay + b.az * b.az).sqrt()).sum();
+    assert!(error < 0.05 * total, "{} of {}", error, total);
+}
+
+#[test]
+fn collisions_happen_along_z() {
+    let bodies = vec![body([100.0, 75.0, 49.75], [0.0, 0.0, 1.0], 1.0), body([100.0, 75.0, 50.25], [0.0, 0.0, -1.0], 1.0)];
+    for engine in ENGINES_3D.iter() {
+        let mut engine = new_engine(&config(*engine, Physics::default())).unwrap();
+        engine.init(&bodies);
+        engine.collide();
+        let res = engine.bodies();
+        assert!(res[0].vz < 0.0 && res[1].vz > 0.0, "{}", engine.name());
+        assert_eq!((res[0].vx, res[0].vy), (0.0, 0.0), "{}", engine.name());
+    }
+}
+
+#[test]
+fn the_boundary_holds_along_z() {
+    // a body crossing the far face of the box at z = 100
+    let radius = Physics::default().radius;
+    for (boundary, z) in [(Boundary::Reflective, 100.0 - radius), (Boundary::Periodic, 0.5)].iter() {
+        let physics = Physics {
+            boundary: *boundary,
+            restitution: 1.0,
+            ..Physics::default()
+        };
+        for engine in ENGINES_3D.iter() {
+            let mut engine = new_engine(&config(*engine, physics)).unwrap();
+            engine.init(&[body([100.0, 75.0, 99.0], [0.0, 0.0, 1.0], 1.0)]);
+            engine.drift(1.5, false);
+            engine.confine();
+            let b = engine.bodies()[0];
+            assert!((b.z - z).abs() < 1e-9, "{} {}: {}", boundary, engine.name(), b.z);
+            let vz = if *boundary == Boundary::Reflective { -1.0 } else { 1.0 };
+            assert_eq!(b.vz, vz, "{} {}", boundary, engine.name());
+        }
+    }
+}
+
+#[test]
+fn runs_without_3d_support_are_rejected() {
+    for engine in EngineKind::ALL.iter().filter(|e| !e.supports_3d()) {
+        assert!(new_engine(&config(*engine, Physics::default())).is_err(), "{}", engine);
+    }
+    let ewald = config(
+        EngineKind::Rayon,
+        Physics {
+            boundary: Boundary::Periodic,
+            periodic_sum: PeriodicSum::Ewald,
+            ..Physics::default()
+        },
+    );
+    assert!(ewald.check().is_err());
+    assert!(SimulationConfig {
+        dimensions: 4,
+        ..SimulationConfig::default()
+    }
+    .check()
+    .is_err());
+}
+
+#[test]
+fn files_keep_the_third_dimension() {
+    let bodies = initial::generate(&config(EngineKind::Tree, Physics::default()));
+
+    let mut csv = Vec::new();
+    write_csv(&mut csv, &bodies, 3).unwrap();
+    assert_eq!(read_csv(csv.as_slice()).unwrap(), bodies);
+    let mut binary = Vec::new();
+    write_binary(&mut binary, &bodies, 3).unwrap();
+    assert_eq!(read_binary(binary.as_slice()).unwrap(), bodies);
+
+    let mut snapshots = Vec::new();
+    let mut writer = BinaryWriter::new(&mut snapshots, 3).unwrap();
+    writer.write(4, 0.5, &bodies).unwrap();
+    writer.finish().unwrap();
+    let snapshot = BinaryReader::new(snapshots.as_slice()).unwrap().next().unwrap().unwrap();
+    assert_eq!(snapshot.bodies, bodies);
+
+    let checkpoint = Checkpoint::start(&config(EngineKind::Tree, Physics::default())).unwrap();
+    let mut bytes = Vec::new();
+    checkpoint.write(&mut bytes).unwrap();
+    let mut restored = SimulationConfig::default();
+    let read = Checkpoint::read(bytes.as_slice()).unwrap();
+    read.apply(&mut restored);
+    assert_eq!((restored.dimensions, restored.domain_depth), (3, 100.0));
+    assert_eq!(read.bodies, bodies);
+}
+
+#[test]
+fn a_2d_run_rejects_3d_bodies() {
+    let bodies = initial::generate(&config(EngineKind::Tree, Physics::default()));
+    let dir = std::env::temp_dir().join(format!("nbody-dimensions-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("bodies.csv");
+    let mut file = std::fs::File::create(&path).unwrap();
+    write_csv(&mut file, &bodies, 3).unwrap();
+    let flat = SimulationConfig {
+        input: Some(path.clone()),
+        ..SimulationConfig::default()
+    };
+    assert!(initial::bodies(&flat).is_err());
+    let deep = SimulationConfig {
+        dimensions: 3,
+        ..flat
+    };
+    assert_eq!(initial::bodies(&deep).unwrap(), bodies);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
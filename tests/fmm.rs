@@ -0,0 +1,126 @@
+use nbody::boundary::Boundary;
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::global::Physics;
+use nbody::validate::force_errors;
+use nbody::{initial, new_engine};
+
+mod common;
+
+use common::body;
+
+fn config(fmm_order: usize, theta: f64) -> SimulationConfig {
+    let physics = Physics {
+        theta,
+        ..Physics::default()
+    };
+    SimulationConfig {
+        fmm_order,
+        ..common::config(EngineKind::Fmm, 1000, 5, physics)
+    }
+}
+
+/// a body of mass 2 at (x, y) moving along x.
+#[test]
+fn forces_converge_with_the_order() {
+    let bodies = initial::generate(&config(6, 0.5));
+    let mut last = f64::INFINITY;
+    for order in [2, 4, 6, 8].iter() {
+        let errors = force_errors(&config(*order, 0.5), &bodies).unwrap();
+        assert!(errors.mean < last / 5.0, "order {}: {}", order, errors);
+        last = errors.mean;
+    }
+    let errors = force_errors(&config(10, 0.3), &bodies).unwrap();
+    assert!(errors.max < 1e-5, "{}", errors);
+    // the default settings are about as accurate as the quadrupole tree
+    let defaults = SimulationConfig {
+        engine: EngineKind::Fmm,
+        ..SimulationConfig::default()
+    };
+    let errors = force_errors(&defaults, &bodies).unwrap();
+    assert!(errors.mean < 1e-3 && errors.max < 0.05, "{}", errors);
+}
+
+#[test]
+fn larger_leaves_sum_more_directly() {
+    let bodies = initial::generate(&config(10, 0.3));
+    let mut last = f64::INFINITY;
+    for leaf_size in [1, 4, 64, 1000].iter() {
+        let config = SimulationConfig {
+            leaf_size: *leaf_size,
+            ..config(10, 0.3)
+        };
+        let errors = force_errors(&config, &bodies).unwrap();
+        assert!(errors.mean < last && errors.max < 1e-3, "leaf size {}: {}", leaf_size, errors);
+        last = errors.mean;
+    }
+    // a single leaf is the brute force sum
+    assert_eq!(last, 0.0);
+}
+
+#[test]
+fn collisions_match_the_rayon_engine() {
+    // a grid of touching bodies, which rayon also resolves with the velocities from before the collisions
+    let bodies = (0..900)
+        .map(|i| {
+            let position = [50.0 + (i % 30) as f64 * 0.95, 40.0 + (i / 30) as f64 * 0.95, 0.0];
+            body(position, [((i * 7) % 11) as f64 - 5.0, 0.0, 0.0], 2.0)
+        })
+        .collect::<Vec<_>>();
+    let config = SimulationConfig {
+        leaf_size: 4,
+        ..config(6, 0.5)
+    };
+    let mut expected = new_engine(&SimulationConfig {
+        engine: EngineKind::Rayon,
+        ..config.clone()
+    })
+    .unwrap();
+    let mut engine = new_engine(&config).unwrap();
+    expected.init(&bodies);
+    engine.init(&bodies);
+    expected.collide();
+    engine.collide();
+    let (expected, res) = (expected.bodies(), engine.bodies());
+    assert!(expected.iter().zip(bodies.iter()).filter(|(a, b)| a.vx != b.vx).count() > 100);
+    for (a, b) in res.iter().zip(expected.iter()) {
+        assert!((a.vx - b.vx).abs() + (a.vy - b.vy).abs() <= 1e-9 * (1.0 + b.vx.abs() + b.vy.abs()), "{:?} {:?}", a, b);
+    }
+}
+
+#[test]
+fn coincident_bodies_share_a_leaf() {
+    let mut bodies = vec![body([50.0, 50.0, 0.0], [0.0; 3], 2.0); 40];
+    bodies.push(body([150.0, 100.0, 0.0], [0.0; 3], 2.0));
+    let errors = force_errors(&config(6, 0.5), &bodies).unwrap();
+    assert!(errors.max < 1e-9, "{}", errors);
+
+    // two bodies closing in collide, however small the leaves
+    let config = SimulationConfig {
+        leaf_size: 1,
+        ..config(6, 0.5)
+    };
+    let mut engine = new_engine(&config).unwrap();
+    engine.init(&[
+        body([100.0, 75.0, 0.0], [1.0, 0.0, 0.0], 2.0),
+        body([100.8, 75.0, 0.0], [-1.0, 0.0, 0.0], 2.0),
+        body([10.0, 10.0, 0.0], [0.0; 3], 2.0),
+    ]);
+    engine.collide();
+    let res = engine.bodies();
+    assert_eq!((res[0].vx, res[1].vx, res[2].vx), (-1.0, 1.0, 0.0));
+}
+
+#[test]
+fn unsupported_runs_are_rejected() {
+    let periodic = SimulationConfig {
+        physics: Physics {
+            boundary: Boundary::Periodic,
+            ..Physics::default()
+        },
+        ..config(6, 0.5)
+    };
+    assert!(new_engine(&periodic).is_err());
+    assert!(!EngineKind::Fmm.supports_3d());
+    assert!(config(6, 1.0).check().is_err());
+    assert!(config(0, 0.5).check().is_err());
+}
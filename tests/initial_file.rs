@@ -0,0 +1,56 @@
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::global::Physics;
+use nbody::initial;
+use nbody::initial::file::{read_binary, read_csv, write_binary, write_csv};
+
+mod common;
+
+fn config() -> SimulationConfig {
+    common::config(EngineKind::Tree, 50, 5, Physics::default())
+}
+
+#[test]
+fn files_round_trip_in_2d() {
+    let bodies = initial::generate(&config());
+    assert!(bodies.iter().all(|b| b.z == 0.0 && b.vz == 0.0));
+
+    let mut csv = Vec::new();
+    write_csv(&mut csv, &bodies, 2).unwrap();
+    assert!(csv.starts_with(b"x,y,vx,vy,m\n"));
+    assert_eq!(read_csv(csv.as_slice()).unwrap(), bodies);
+
+    let mut binary = Vec::new();
+    write_binary(&mut binary, &bodies, 2).unwrap();
+    // the magic number, the count and five values per body
+    assert_eq!(binary.len(), 16 + 5 * 8 * bodies.len());
+    assert_eq!(read_binary(binary.as_slice()).unwrap(), bodies);
+}
+
+#[test]
+fn broken_csv_files_are_rejected() {
+    // columns may come in any order, y is missing in the second file
+    assert_eq!(read_csv("# bodies\nm, y, x\n\n2, 1, 3\n".as_bytes()).unwrap()[0].x, 3.0);
+    assert!(read_csv("x,vx,vy,m\n1,0,0,1\n".as_bytes()).is_err());
+    assert!(read_csv("x,y,m\n1,two,1\n".as_bytes()).is_err());
+    assert!(read_csv("x,y,m\n1,2\n".as_bytes()).is_err());
+    assert!(read_csv("x,y,m\n1,2,0\n".as_bytes()).is_err());
+    assert!(read_csv("x,y,m\n1,NaN,1\n".as_bytes()).is_err());
+}
+
+#[test]
+fn broken_binary_files_are_rejected() {
+    let bodies = initial::generate(&config());
+    let mut binary = Vec::new();
+    write_binary(&mut binary, &bodies, 2).unwrap();
+
+    for length in [0, 7, 12, 16, 20, binary.len() - 1].iter() {
+        assert!(read_binary(&binary[..*length]).is_err(), "{} bytes", length);
+    }
+    let mut wrong_magic = binary.clone();
+    wrong_magic[7] = b'X';
+    assert!(read_binary(wrong_magic.as_slice()).is_err());
+    // a count far beyond the bodies stored
+    let mut too_many = binary.clone();
+    too_many[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(read_binary(too_many.as_slice()).is_err());
+}
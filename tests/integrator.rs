@@ -0,0 +1,86 @@
+use nbody::boundary::Space;
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::diagnostics::Diagnostics;
+use nbody::validate::Divergence;
+use nbody::{initial, new_engine, Integrator, SimpleBody};
+
+mod common;
+
+use common::{binary, DT, ORBIT_STEPS};
+
+/// advances the bodies with the brute force engine and returns them after every step.
+fn run(integrator: Integrator, bodies: &[SimpleBody], steps: usize) -> Vec<SimpleBody> {
+    let config = SimulationConfig {
+        engine: EngineKind::BruteForce,
+        integrator,
+        ..SimulationConfig::default()
+    };
+    let mut engine = new_engine(&config).unwrap();
+    integrator.init(engine.as_mut(), bodies);
+    for _ in 0..steps {
+        integrator.step(engine.as_mut(), DT);
+    }
+    engine.bodies()
+}
+
+/// the largest relative change of the energy of the binary over one orbit.
+fn energy_drift(integrator: Integrator) -> f64 {
+    let config = SimulationConfig {
+        engine: EngineKind::BruteForce,
+        integrator,
+        ..SimulationConfig::default()
+    };
+    let energy = |bodies: &[SimpleBody]| Diagnostics::measure(bodies, &Space::new(&config), 2).energy();
+    let start = energy(&binary(0.0));
+    let mut engine = new_engine(&config).unwrap();
+    integrator.init(engine.as_mut(), &binary(0.0));
+    let mut drift: f64 = 0.0;
+    for _ in 0..ORBIT_STEPS {
+        integrator.step(engine.as_mut(), DT);
+        drift = drift.max(((energy(&engine.bodies()) - start) / start).abs());
+    }
+    drift
+}
+
+#[test]
+fn names_round_trip() {
+    for integrator in Integrator::ALL.iter() {
+        assert_eq!(integrator.name().parse::<Integrator>(), Ok(*integrator));
+    }
+    assert!("midpoint".parse::<Integrator>().is_err());
+}
+
+#[test]
+fn higher_order_integrators_conserve_energy_better() {
+    let euler = energy_drift(Integrator::Euler);
+    let leapfrog = energy_drift(Integrator::Leapfrog);
+    let verlet = energy_drift(Integrator::Verlet);
+    let rk4 = energy_drift(Integrator::Rk4);
+    assert!(leapfrog < 1e-8, "leapfrog drifts by {}", leapfrog);
+    assert!(verlet < 1e-8, "verlet drifts by {}", verlet);
+    assert!(rk4 < 1e-10, "rk4 drifts by {}", rk4);
+    assert!(leapfrog < euler && rk4 < leapfrog, "euler {}, leapfrog {}, rk4 {}", euler, leapfrog, rk4);
+}
+
+#[test]
+fn leapfrog_and_verlet_agree() {
+    // both are the same scheme written in a different order
+    let divergence = Divergence::between(
+        &run(Integrator::Leapfrog, &binary(0.0), ORBIT_STEPS),
+        &run(Integrator::Verlet, &binary(0.0), ORBIT_STEPS),
+    );
+    assert!(divergence.position < 1e-9 && divergence.velocity < 1e-9, "{}", divergence);
+}
+
+#[test]
+fn every_integrator_is_deterministic() {
+    let config = SimulationConfig {
+        size: 200,
+        seed: 7,
+        ..SimulationConfig::default()
+    };
+    let bodies = initial::generate(&config);
+    for integrator in Integrator::ALL.iter() {
+        assert_eq!(run(*integrator, &bodies, 20), run(*integrator, &bodies, 20), "{}", integrator);
+    }
+}
@@ -0,0 +1,90 @@
+use nalgebra::Vector3;
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::global::Physics;
+use nbody::initial;
+use nbody::multipole::{Expansion, Moments};
+use nbody::opening::Opening;
+use nbody::validate::force_errors;
+
+/// a lopsided cluster of bodies within a unit of the origin.
+fn cluster() -> Vec<(Vector3<f64>, f64)> {
+    (0..20)
+        .map(|i| {
+            let i = i as f64;
+            let x = Vector3::new((i * 0.37).sin(), (i * 0.73).cos() * 0.5, (i * 1.31).sin() * 0.8);
+            (x, 1.0 + (i * 0.17) % 1.0)
+        })
+        .collect()
+}
+
+#[test]
+fn expansions_are_named() {
+    for expansion in Expansion::ALL.iter() {
+        assert_eq!(expansion.name().parse::<Expansion>(), Ok(*expansion));
+    }
+    assert!("dipole".parse::<Expansion>().is_err());
+    assert_eq!(Physics::default().expansion, Expansion::Monopole);
+}
+
+#[test]
+fn every_order_gets_closer() {
+    let bodies = cluster();
+    let m = bodies.iter().map(|b| b.1).sum::<f64>();
+    let c = bodies.iter().map(|b| b.0 * b.1).sum::<Vector3<f64>>() / m;
+    let directions = (0..12).map(|i| {
+        let i = i as f64;
+        Vector3::new((i * 2.1).cos(), (i * 2.1).sin(), (i * 0.9).cos()).normalize()
+    });
+    for distance in [12.0, 24.0].iter() {
+        let mut last = (f64::INFINITY, f64::INFINITY);
+        for expansion in Expansion::ALL.iter() {
+            let mut moments = Moments::default();
+            for (x, mass) in bodies.iter() {
+                moments.add(x, *mass, *expansion);
+            }
+            let mut error = (0.0, 0.0);
+            for direction in directions.clone() {
+                let point = direction * *distance;
+                let field = bodies.iter().map(|(x, mass)| (x - point) * (mass / (x - point).norm().powi(3))).sum::<Vector3<f64>>();
+                let potential = bodies.iter().map(|(x, mass)| -mass / (x - point).norm()).sum::<f64>();
+                let r = point - c;
+                let approximate = -r * (m / r.norm().powi(3)) + moments.field(m, &c, &r, *expansion);
+                error.0 += (approximate - field).norm() / field.norm();
+                error.1 += (-m / r.norm() + moments.potential(m, &c, &r, *expansion) - potential).abs() / potential.abs();
+            }
+            assert!(error.0 < last.0 / 3.0 && error.1 < last.1 / 3.0, "{} at {}: {:?} {:?}", expansion, distance, error, last);
+            last = error;
+        }
+    }
+}
+
+#[test]
+fn higher_orders_allow_larger_angles() {
+    for dimensions in [2, 3].iter() {
+        let at = |expansion, engine| {
+            let config = SimulationConfig {
+                engine,
+                size: 500,
+                seed: 3,
+                dimensions: *dimensions,
+                physics: Physics {
+                    opening: Opening::Classic,
+                    theta: 0.8,
+                    expansion,
+                    ..Physics::default()
+                },
+                ..SimulationConfig::default()
+            };
+            force_errors(&config, &initial::generate(&config)).unwrap()
+        };
+        let monopole = at(Expansion::Monopole, EngineKind::Tree);
+        let quadrupole = at(Expansion::Quadrupole, EngineKind::Tree);
+        let octupole = at(Expansion::Octupole, EngineKind::Tree);
+        assert!(quadrupole.mean < monopole.mean / 2.0, "{}D: {} / {}", dimensions, quadrupole, monopole);
+        assert!(octupole.mean < quadrupole.mean, "{}D: {} / {}", dimensions, octupole, quadrupole);
+        for engine in [EngineKind::PThread, EngineKind::RayonTree].iter() {
+            let res = at(Expansion::Octupole, *engine);
+            assert!((res.mean - octupole.mean).abs() <= 1e-9 * octupole.mean, "{}: {} / {}", engine, res, octupole);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::global::Physics;
+use nbody::initial;
+use nbody::opening::Opening;
+use nbody::validate::{force_errors, ForceErrors};
+use nbody::SimpleBody;
+
+mod common;
+
+fn config(engine: EngineKind, dimensions: usize, physics: Physics) -> SimulationConfig {
+    SimulationConfig {
+        dimensions,
+        ..common::config(engine, 500, 3, physics)
+    }
+}
+
+fn errors(engine: EngineKind, dimensions: usize, physics: Physics) -> ForceErrors {
+    let config = config(engine, dimensions, physics);
+    force_errors(&config, &initial::generate(&config)).unwrap()
+}
+
+fn accelerating(ax: f64, ay: f64) -> SimpleBody {
+    SimpleBody {
+        ax,
+        ay,
+        ..SimpleBody::default()
+    }
+}
+
+#[test]
+fn criteria_are_named() {
+    for opening in Opening::ALL.iter() {
+        assert_eq!(opening.name().parse::<Opening>(), Ok(*opening));
+    }
+    assert!("theta".parse::<Opening>().is_err());
+    assert_eq!(Physics::default().opening, Opening::Scale);
+}
+
+#[test]
+fn force_errors_are_relative() {
+    let exact = [accelerating(1.0, 0.0), accelerating(0.0, 2.0), accelerating(0.0, 0.0), accelerating(-4.0, 0.0)];
+    let approximate = [accelerating(1.0, 0.0), accelerating(0.0, 2.2), accelerating(3.0, 0.0), accelerating(-4.0, 2.0)];
+    let errors = ForceErrors::between(&approximate, &exact);
+    // the body without an exact acceleration is skipped
+    assert!((errors.mean - 0.2).abs() < 1e-12, "{}", errors);
+    assert!((errors.max - 0.5).abs() < 1e-12, "{}", errors);
+    assert!((errors.median - 0.1).abs() < 1e-12, "{}", errors);
+    assert_eq!(ForceErrors::between(&exact[2..3], &exact[2..3]), ForceErrors::default());
+}
+
+#[test]
+fn smaller_parameters_give_smaller_errors() {
+    for dimensions in [2, 3].iter() {
+        for opening in [Opening::Classic, Opening::Bmax].iter() {
+            let at = |theta| {
+                let physics = Physics {
+                    opening: *opening,
+                    theta,
+                    ..Physics::default()
+                };
+                errors(EngineKind::Tree, *dimensions, physics)
+            };
+            let (coarse, fine) = (at(0.8), at(0.3));
+            assert!(fine.mean < coarse.mean / 4.0, "{} {}D: {} / {}", opening, dimensions, fine, coarse);
+            assert!(coarse.median < 0.05, "{} {}D: {}", opening, dimensions, coarse);
+            assert!(at(1e-6).max < 1e-9, "{} {}D", opening, dimensions);
+        }
+        let at = |force_tolerance| {
+            let physics = Physics {
+                opening: Opening::Relative,
+                force_tolerance,
+                ..Physics::default()
+            };
+            errors(EngineKind::Tree, *dimensions, physics)
+        };
+        let (coarse, fine) = (at(0.01), at(0.0005));
+        assert!(fine.max < coarse.max / 4.0, "relative {}D: {} / {}", dimensions, fine, coarse);
+        // the error of every body stays close to the tolerance
+        assert!(coarse.max < 0.05 && fine.max < 0.0025, "relative {}D: {} / {}", dimensions, coarse, fine);
+    }
+}
+
+#[test]
+fn every_tree_engine_honours_the_criterion() {
+    let physics = Physics {
+        opening: Opening::Relative,
+        force_tolerance: 0.001,
+        ..Physics::default()
+    };
+    let expected = errors(EngineKind::Tree, 2, physics);
+    for engine in [EngineKind::PThread, EngineKind::RayonTree].iter() {
+        let res = errors(*engine, 2, physics);
+        assert!((res.mean - expected.mean).abs() <= 1e-9 * expected.mean, "{}: {} / {}", engine, res, expected);
+    }
+    assert_eq!(errors(EngineKind::Rayon, 2, physics).max, 0.0);
+}
+
+#[test]
+fn the_parameters_must_be_positive() {
+    let physics = Physics::default();
+    let config = config(EngineKind::Tree, 2, physics);
+    assert!(config.check().is_ok());
+    for theta in [0.0, -1.0, f64::NAN].iter() {
+        let physics = Physics { theta: *theta, ..physics };
+        assert!(SimulationConfig { physics, ..config.clone() }.check().is_err());
+    }
+    let physics = Physics { force_tolerance: 0.0, ..physics };
+    assert!(SimulationConfig { physics, ..config }.check().is_err());
+}
@@ -0,0 +1,176 @@
+use nbody::boundary::{Boundary, PeriodicSum};
+use nbody::checkpoint::Checkpoint;
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::global::Physics;
+use nbody::multipole::Expansion;
+use nbody::opening::Opening;
+use nbody::softening::Softening;
+use nbody::{initial, new_engine};
+
+mod common;
+
+use common::{accelerated, body};
+
+fn config(engine: EngineKind, physics: Physics) -> SimulationConfig {
+    common::config(engine, 300, 3, physics)
+}
+
+#[test]
+fn accelerations_scale_with_g() {
+    let physics = Physics::default();
+    let doubled = Physics {
+        g: 2.0 * physics.g,
+        ..physics
+    };
+    let bodies = initial::generate(&config(EngineKind::BruteForce, physics));
+    for engine in [EngineKind::BruteForce, EngineKind::Rayon, EngineKind::Tree, EngineKind::RayonTree].iter() {
+        let single = accelerated(&config(*engine, physics), &bodies);
+        let double = accelerated(&config(*engine, doubled), &bodies);
+        for (a, b) in single.iter().zip(double.iter()) {
+            assert_eq!((2.0 * a.ax, 2.0 * a.ay), (b.ax, b.ay), "{}", engine);
+        }
+    }
+}
+
+#[test]
+fn collisions_follow_the_radius() {
+    // two bodies 1.5 apart moving towards each other, they only collide if the radius is larger than 0.75
+    let bodies = vec![body([99.25, 75.0, 0.0], [1.0, 0.0, 0.0], 1.0), body([100.75, 75.0, 0.0], [-1.0, 0.0, 0.0], 1.0)];
+    for engine in [EngineKind::BruteForce, EngineKind::Rayon, EngineKind::Tree].iter() {
+        for (radius, vx) in [(0.5, 1.0), (1.0, -1.0)].iter() {
+            let config = config(
+                *engine,
+                Physics {
+                    radius: *radius,
+                    ..Physics::default()
+                },
+            );
+            let mut engine = new_engine(&config).unwrap();
+            engine.init(&bodies);
+            engine.collide();
+            assert_eq!(engine.bodies()[0].vx, *vx, "{} with radius {}", config.engine, radius);
+        }
+    }
+}
+
+#[test]
+fn generated_bodies_follow_the_mass_range_and_radius() {
+    let physics = Physics {
+        mass_range: 2.0,
+        radius: 10.0,
+        ..Physics::default()
+    };
+    let config = config(EngineKind::Tree, physics);
+    for b in initial::generate(&config) {
+        assert!(b.m < 2.0);
+        assert!(b.x > 10.0 && b.x < config.domain_width - 10.0);
+        assert!(b.y > 10.0 && b.y < config.domain_height - 10.0);
+    }
+}
+
+#[test]
+fn checkpoints_keep_the_physics() {
+    let physics = Physics {
+        g: 1.5,
+        dt: 0.01,
+        radius: 0.1,
+        mass_range: 3.0,
+        min_size: 2.5,
+        opening: Opening::Relative,
+        dist_scale_limit: 0.3,
+        theta: 0.4,
+        force_tolerance: 0.01,
+        expansion: Expansion::Octupole,
+        softening: Softening::Wendland,
+        softening_length: 0.7,
+        boundary: Boundary::Periodic,
+        restitution: 0.9,
+        periodic_sum: PeriodicSum::Ewald,
+    };
+    let checkpoint = Checkpoint::start(&config(EngineKind::Tree, physics)).unwrap();
+    let mut bytes = Vec::new();
+    checkpoint.write(&mut bytes).unwrap();
+    let mut restored = SimulationConfig::default();
+    Checkpoint::read(bytes.as_slice()).unwrap().apply(&mut restored);
+    assert_eq!(restored.physics, physics);
+}
+
+#[test]
+fn the_window_does_not_change_the_domain() {
+    let physics = Physics::default();
+    let mut base = config(EngineKind::Tree, physics);
+    base.domain_width = 300.0;
+    base.domain_height = 100.0;
+    let window = SimulationConfig {
+        width: 1920.0,
+        height: 1080.0,
+        scale: 1.5,
+        ..base.clone()
+    };
+    assert_eq!(initial::generate(&base), initial::generate(&window));
+    assert_eq!(accelerated(&base, &initial::generate(&base)), accelerated(&window, &initial::generate(&window)));
+
+    let checkpoint = Checkpoint::start(&window).unwrap();
+    let mut bytes = Vec::new();
+    checkpoint.write(&mut bytes).unwrap();
+    let mut restored = SimulationConfig::default();
+    Checkpoint::read(bytes.as_slice()).unwrap().apply(&mut restored);
+    assert_eq!((restored.domain_width, restored.domain_height), (300.0, 100.0));
+}
+
+#[test]
+fn softened_kernels_are_newtonian_beyond_the_softening_length() {
+    let h = 2.0;
+    for softening in [Softening::Spline, Softening::Wendland].iter() {
+        for r in [2.0, 2.5, 10.0].iter() {
+            let r2: f64 = r * r;
+            assert_eq!(softening.force(r2, h), Softening::None.force(r2, h), "{}", softening);
+            assert_eq!(softening.potential(r2, h), Softening::None.potential(r2, h), "{}", softening);
+        }
+        // continuous at the edge of the kernel
+        let inside = (h * (1.0 - 1e-9)).powi(2);
+        assert!((softening.force(inside, h) - Softening::None.force(h * h, h)).abs() < 1e-6, "{}", softening);
+        assert!((softening.potential(inside, h) - Softening::None.potential(h * h, h)).abs() < 1e-6, "{}", softening);
+    }
+    let half = |x: f64| (x * h / 2.0).powi(2);
+    assert!((Softening::Spline.force(half(1.0 - 1e-9), h) - Softening::Spline.force(half(1.0), h)).abs() < 1e-6);
+    assert!((Softening::Spline.potential(half(1.0 - 1e-9), h) - Softening::Spline.potential(half(1.0), h)).abs() < 1e-6);
+}
+
+#[test]
+fn softened_potentials_match_the_forces() {
+    // the force is minus the derivative of the potential, r * force(r^2) = d potential / dr
+    let h = 1.5;
+    for softening in Softening::ALL.iter() {
+        for r in [0.1, 0.5, 0.75, 1.0, 1.4, 3.0].iter() {
+            let r: f64 = *r;
+            let e = 1e-6;
+            let derivative = (softening.potential((r + e).powi(2), h) - softening.potential((r - e).powi(2), h)) / (2.0 * e);
+            let force = r * softening.force(r * r, h);
+            assert!((derivative - force).abs() < 1e-6 * force.max(1.0), "{} at {}: {} {}", softening, r, derivative, force);
+        }
+    }
+}
+
+#[test]
+fn softening_bounds_close_approaches() {
+    // two bodies just outside the collision distance, far closer than the softening length
+    let bodies = vec![body([100.0, 75.0, 0.0], [0.0; 3], 1.0), body([100.001, 75.0, 0.0], [0.0; 3], 1.0)];
+    for softening in Softening::ALL.iter() {
+        let physics = Physics {
+            radius: 1e-4,
+            softening: *softening,
+            softening_length: 1.0,
+            ..Physics::default()
+        };
+        let expected = physics.force(1e-6) * 0.001;
+        for engine in [EngineKind::BruteForce, EngineKind::Rayon, EngineKind::Tree].iter() {
+            let res = accelerated(&config(*engine, physics), &bodies);
+            assert!((res[0].ax - expected).abs() < 1e-9 * expected, "{} {}", softening, engine);
+            assert_eq!(res[0].ax, -res[1].ax, "{} {}", softening, engine);
+            if *softening != Softening::None {
+                assert!(res[0].ax < 10.0 * physics.g, "{} {}", softening, engine);
+            }
+        }
+    }
+}
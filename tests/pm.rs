@@ -0,0 +1,95 @@
+use nbody::boundary::{Boundary, PeriodicSum};
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::global::Physics;
+use nbody::opening::Opening;
+use nbody::validate::force_errors;
+use nbody::{initial, new_engine};
+
+mod common;
+
+use common::body;
+
+fn config(engine: EngineKind, mesh_size: usize) -> SimulationConfig {
+    let physics = Physics {
+        opening: Opening::Classic,
+        ..Physics::default()
+    };
+    SimulationConfig {
+        mesh_size,
+        ..common::config(engine, 1000, 5, physics)
+    }
+}
+
+fn periodic(config: SimulationConfig, periodic_sum: PeriodicSum) -> SimulationConfig {
+    SimulationConfig {
+        physics: Physics {
+            boundary: Boundary::Periodic,
+            periodic_sum,
+            ..config.physics
+        },
+        ..config
+    }
+}
+
+#[test]
+fn pm_forces_converge_with_the_mesh() {
+    let bodies = initial::generate(&config(EngineKind::Pm, 64));
+    let mut last = f64::INFINITY;
+    for mesh_size in [16, 32, 64, 128].iter() {
+        let errors = force_errors(&config(EngineKind::Pm, *mesh_size), &bodies).unwrap();
+        assert!(errors.median < last, "mesh {}: {}", mesh_size, errors);
+        last = errors.median;
+    }
+    assert!(last < 0.2, "{}", last);
+}
+
+#[test]
+fn tree_pm_matches_brute_force() {
+    let bodies = initial::generate(&config(EngineKind::TreePm, 64));
+    for mesh_size in [32, 64].iter() {
+        let errors = force_errors(&config(EngineKind::TreePm, *mesh_size), &bodies).unwrap();
+        assert!(errors.mean < 0.01 && errors.p99 < 0.05, "mesh {}: {}", mesh_size, errors);
+    }
+    let ewald = periodic(config(EngineKind::TreePm, 64), PeriodicSum::Ewald);
+    let errors = force_errors(&ewald, &initial::generate(&ewald)).unwrap();
+    assert!(errors.mean < 0.02, "{}", errors);
+
+    let cube = SimulationConfig {
+        dimensions: 3,
+        ..config(EngineKind::TreePm, 32)
+    };
+    let errors = force_errors(&cube, &initial::generate(&cube)).unwrap();
+    assert!(errors.mean < 0.01, "{}", errors);
+}
+
+#[test]
+fn bodies_closing_in_collide() {
+    for engine in [EngineKind::Pm, EngineKind::TreePm].iter() {
+        let mut engine = new_engine(&config(*engine, 64)).unwrap();
+        let along_x = |x: f64, vx: f64| body([x, 75.0, 0.0], [vx, 0.0, 0.0], 2.0);
+        engine.init(&[along_x(100.0, 1.0), along_x(103.0, -1.0), along_x(10.0, 0.0)]);
+        engine.drift(1.2, false);
+        // the tree the collisions are found in follows the bodies
+        engine.accelerate();
+        engine.collide();
+        let res = engine.bodies();
+        assert_eq!((res[0].vx, res[1].vx, res[2].vx), (-1.0, 1.0, 0.0));
+    }
+}
+
+#[test]
+fn unsupported_runs_are_rejected() {
+    for engine in [EngineKind::Pm, EngineKind::TreePm].iter() {
+        assert!(engine.supports_3d());
+        let open = SimulationConfig {
+            physics: Physics {
+                boundary: Boundary::Open,
+                ..Physics::default()
+            },
+            ..config(*engine, 64)
+        };
+        assert!(new_engine(&open).is_err());
+        assert!(new_engine(&periodic(config(*engine, 64), PeriodicSum::MinimumImage)).is_ok());
+        assert!(config(*engine, 0).check().is_err());
+    }
+}
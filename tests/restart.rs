@@ -0,0 +1,92 @@
+use std::fs;
+use std::path::Path;
+
+use nbody::checkpoint::Checkpoint;
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::driver::run;
+use nbody::global::Physics;
+use nbody::new_engine;
+use nbody::opening::Opening;
+
+mod common;
+
+fn config(engine: EngineKind, steps: usize, checkpoint: &Path) -> SimulationConfig {
+    with_physics(engine, steps, checkpoint, Physics::default())
+}
+
+fn with_physics(engine: EngineKind, steps: usize, checkpoint: &Path, physics: Physics) -> SimulationConfig {
+    SimulationConfig {
+        steps,
+        checkpoint: Some(checkpoint.to_path_buf()),
+        checkpoint_every: 1000,
+        ..common::config(engine, 300, 4, physics)
+    }
+}
+
+/// runs the configuration from the given state and returns its final checkpoint.
+fn run_from(mut config: SimulationConfig, start: &Checkpoint) -> Checkpoint {
+    start.apply(&mut config);
+    let mut engine = new_engine(&config).unwrap();
+    run(engine.as_mut(), &config, start).unwrap();
+    Checkpoint::load(config.checkpoint.as_ref().unwrap()).unwrap()
+}
+
+#[test]
+fn restarts_are_bit_identical() {
+    let directory = std::env::temp_dir().join(format!("nbody-restart-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let (whole, half) = (directory.join("whole.ck"), directory.join("half.ck"));
+    for engine in [EngineKind::Tree, EngineKind::PThread, EngineKind::RayonTree].iter() {
+        let straight = config(*engine, 10, &whole);
+        let initial = Checkpoint::start(&straight).unwrap();
+        let expected = run_from(straight, &initial);
+        let first = config(*engine, 5, &half);
+        let middle = run_from(first.clone(), &initial);
+        let restarted = run_from(first, &middle);
+        assert_eq!((middle.step, restarted.step, expected.step), (5, 10, 10));
+        assert!(expected.bodies != initial.bodies);
+        assert!(restarted.bodies == expected.bodies, "{} diverges after a restart", engine);
+    }
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn relative_opening_restarts_stay_close() {
+    // the restart opens the trees by the stored accelerations instead of those of the step before, which may flip a few decisions
+    let directory = std::env::temp_dir().join(format!("nbody-restart-relative-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let (whole, half) = (directory.join("whole.ck"), directory.join("half.ck"));
+    let physics = Physics {
+        opening: Opening::Relative,
+        ..Physics::default()
+    };
+    let straight = with_physics(EngineKind::Tree, 10, &whole, physics);
+    let initial = Checkpoint::start(&straight).unwrap();
+    let expected = run_from(straight, &initial);
+    let first = with_physics(EngineKind::Tree, 5, &half, physics);
+    let middle = run_from(first.clone(), &initial);
+    let restarted = run_from(first, &middle);
+    assert_eq!((restarted.step, expected.step), (10, 10));
+    for (a, b) in restarted.bodies.iter().zip(expected.bodies.iter()) {
+        let error = (a.x - b.x).abs() + (a.y - b.y).abs();
+        assert!(error < 1e-6 * (b.x.abs() + b.y.abs()), "{:?} {:?}", a, b);
+    }
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn corrupt_checkpoints_are_rejected() {
+    let mut bytes = Vec::new();
+    let start = Checkpoint::start(&config(EngineKind::Tree, 1, Path::new("unused.ck"))).unwrap();
+    start.write(&mut bytes).unwrap();
+    assert_eq!(Checkpoint::read(bytes.as_slice()).unwrap().bodies, start.bodies);
+
+    for length in [0, 12, 16, 100, bytes.len() - 1].iter() {
+        assert!(Checkpoint::read(&bytes[..*length]).is_err(), "{} bytes", length);
+    }
+    // a configuration section far longer than the file must not be allocated up front
+    let mut too_long = bytes;
+    too_long[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+    let error = Checkpoint::read(too_long.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
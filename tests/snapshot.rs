@@ -0,0 +1,48 @@
+use nbody::config::EngineKind;
+use nbody::global::Physics;
+use nbody::snapshot::{BinaryReader, BinaryWriter, Snapshot, SnapshotWriter};
+use nbody::{initial, SimpleBody};
+
+mod common;
+
+fn bodies() -> Vec<SimpleBody> {
+    initial::generate(&common::config(EngineKind::Tree, 20, 3, Physics::default()))
+}
+
+/// two snapshots of the 2D bodies in the binary format.
+fn snapshots() -> Vec<u8> {
+    let bodies = bodies();
+    let mut bytes = Vec::new();
+    let mut writer = BinaryWriter::new(&mut bytes, 2).unwrap();
+    writer.write(0, 0.0, &bodies).unwrap();
+    writer.write(5, 0.5, &bodies).unwrap();
+    writer.finish().unwrap();
+    bytes
+}
+
+fn read(bytes: &[u8]) -> std::io::Result<Vec<Snapshot>> {
+    BinaryReader::new(bytes)?.collect()
+}
+
+#[test]
+fn snapshots_are_read_back() {
+    let snapshots = read(&snapshots()).unwrap();
+    assert_eq!(snapshots.iter().map(|s| (s.step, s.time)).collect::<Vec<_>>(), vec![(0, 0.0), (5, 0.5)]);
+    assert!(snapshots.iter().all(|s| s.bodies == bodies()));
+}
+
+#[test]
+fn broken_snapshots_are_rejected() {
+    let bytes = snapshots();
+    // the magic number, then step, time and count of the first chunk
+    for length in [0, 4, 12, 20, 28, 40, bytes.len() / 2, bytes.len() - 1].iter() {
+        assert!(read(&bytes[..*length]).is_err(), "{} bytes", length);
+    }
+    let mut wrong_magic = bytes.clone();
+    wrong_magic[0] = b'X';
+    assert!(read(&wrong_magic).is_err());
+    // a count far beyond the bodies stored must not be allocated up front
+    let mut too_many = bytes;
+    too_many[24..32].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(read(&too_many).is_err());
+}
@@ -0,0 +1,178 @@
+use nbody::boundary::Space;
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::diagnostics::Diagnostics;
+use nbody::timestep::{Criterion, Stepper, Timestep};
+use nbody::validate::Divergence;
+use nbody::{new_engine, Engine, Integrator, SimpleBody};
+
+mod common;
+
+use common::{binary, body, DT, ORBIT_STEPS};
+
+/// a binary with separation 2 on a circular orbit, which needs substeps all the time, and light bodies at rest far away from it, which need none.
+fn binary_and_field() -> Vec<SimpleBody> {
+    // v^2 = G * m / (2 * d) with G = 5, m = 50 and d = 2
+    let v = 62.5_f64.sqrt();
+    let mut bodies = vec![body([99.0, 75.0, 0.0], [0.0, -v, 0.0], 50.0), body([101.0, 75.0, 0.0], [0.0, v, 0.0], 50.0)];
+    for i in 0..10 {
+        bodies.push(body([10.0 + 18.0 * i as f64, 10.0, 0.0], [0.0; 3], 0.001));
+        bodies.push(body([10.0 + 18.0 * i as f64, 140.0, 0.0], [0.0; 3], 0.001));
+    }
+    bodies
+}
+
+fn config(timestep: Timestep) -> SimulationConfig {
+    SimulationConfig {
+        engine: EngineKind::BruteForce,
+        timestep,
+        criterion: Criterion::Acceleration,
+        ..SimulationConfig::default()
+    }
+}
+
+/// Counting wraps an engine and counts the accelerations it computes.
+struct Counting {
+    engine: Box<dyn Engine>,
+    accelerations: usize,
+}
+
+impl Engine for Counting {
+    fn name(&self) -> &'static str {
+        self.engine.name()
+    }
+
+    fn init(&mut self, bodies: &[SimpleBody]) {
+        self.engine.init(bodies)
+    }
+
+    fn collide(&mut self) {
+        self.engine.collide()
+    }
+
+    fn accelerate(&mut self) {
+        self.accelerations += self.engine.bodies().len();
+        self.engine.accelerate()
+    }
+
+    fn accelerate_some(&mut self, active: &[bool]) {
+        self.accelerations += active.iter().filter(|x| **x).count();
+        self.engine.accelerate_some(active)
+    }
+
+    fn kick(&mut self, dt: f64) {
+        self.engine.kick(dt)
+    }
+
+    fn drift(&mut self, dt: f64, with_acceleration: bool) {
+        self.engine.drift(dt, with_acceleration)
+    }
+
+    fn confine(&mut self) {
+        self.engine.confine()
+    }
+
+    fn bodies(&self) -> Vec<SimpleBody> {
+        self.engine.bodies()
+    }
+
+    fn load(&mut self, bodies: &[SimpleBody]) {
+        self.engine.load(bodies)
+    }
+}
+
+/// runs the bodies for the given steps and returns the largest relative change of the energy, the number of computed accelerations and the final bodies.
+fn run(config: &SimulationConfig, bodies: &[SimpleBody], steps: usize) -> (f64, usize, Vec<SimpleBody>) {
+    let energy = |bodies: &[SimpleBody]| Diagnostics::measure(bodies, &Space::new(config), bodies.len()).energy();
+    let start = energy(bodies);
+    let mut engine = Counting {
+        engine: new_engine(config).unwrap(),
+        accelerations: 0,
+    };
+    let mut stepper = Stepper::new(config).unwrap();
+    stepper.init(&mut engine, bodies, DT);
+    let mut drift: f64 = 0.0;
+    for _ in 0..steps {
+        stepper.step(&mut engine, DT);
+        drift = drift.max(((energy(&engine.bodies()) - start) / start).abs());
+    }
+    (drift, engine.accelerations, engine.bodies())
+}
+
+#[test]
+fn names_round_trip() {
+    for timestep in Timestep::ALL.iter() {
+        assert_eq!(timestep.name().parse::<Timestep>(), Ok(*timestep));
+    }
+    for criterion in Criterion::ALL.iter() {
+        assert_eq!(criterion.name().parse::<Criterion>(), Ok(*criterion));
+    }
+}
+
+#[test]
+fn block_timesteps_need_leapfrog() {
+    let config = SimulationConfig {
+        integrator: Integrator::Rk4,
+        ..config(Timestep::Block)
+    };
+    assert!(Stepper::new(&config).is_err());
+}
+
+#[test]
+fn fixed_timestep_matches_the_integrator() {
+    let config = config(Timestep::Fixed);
+    let mut engine = new_engine(&config).unwrap();
+    config.integrator.init(engine.as_mut(), &binary(0.9));
+    for _ in 0..100 {
+        config.integrator.step(engine.as_mut(), DT);
+    }
+    assert_eq!(run(&config, &binary(0.9), 100).2, engine.bodies());
+}
+
+#[test]
+fn adaptive_timesteps_resolve_the_pericentre() {
+    for criterion in Criterion::ALL.iter() {
+        let fixed = run(&config(Timestep::Fixed), &binary(0.9), ORBIT_STEPS);
+        let adaptive = run(
+            &SimulationConfig {
+                criterion: *criterion,
+                ..config(Timestep::Adaptive)
+            },
+            &binary(0.9),
+            ORBIT_STEPS,
+        );
+        assert!(
+            adaptive.0 < fixed.0 / 10.0,
+            "{}: fixed drifts by {}, adaptive by {}",
+            criterion,
+            fixed.0,
+            adaptive.0
+        );
+    }
+}
+
+#[test]
+fn block_timesteps_only_substep_the_binary() {
+    let adaptive = run(&config(Timestep::Adaptive), &binary_and_field(), 500);
+    let block = run(&config(Timestep::Block), &binary_and_field(), 500);
+    assert!(block.0 < 2.0 * adaptive.0, "adaptive drifts by {}, block by {}", adaptive.0, block.0);
+    assert!(
+        block.1 * 4 < adaptive.1,
+        "adaptive computes {} accelerations, block {}",
+        adaptive.1,
+        block.1
+    );
+}
+
+#[test]
+fn every_engine_follows_the_same_block_timesteps() {
+    let reference = run(&config(Timestep::Block), &binary_and_field(), 200).2;
+    // the tree approximates the forces of distant bodies
+    for (engine, tolerance) in [(EngineKind::Rayon, 1e-9), (EngineKind::Tree, 1e-4)].iter() {
+        let config = SimulationConfig {
+            engine: *engine,
+            ..config(Timestep::Block)
+        };
+        let divergence = Divergence::between(&reference, &run(&config, &binary_and_field(), 200).2);
+        assert!(divergence.position < *tolerance, "{}: {}", engine, divergence);
+    }
+}
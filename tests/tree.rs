@@ -0,0 +1,73 @@
+use nalgebra::SVector;
+use nbody::boundary::Space;
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::geometry::Point;
+use nbody::global::Physics;
+use nbody::multipole::Expansion;
+use nbody::quad_tree::tree::Tree;
+use nbody::{initial, new_engine, SimpleBody};
+
+mod common;
+
+fn config(engine: EngineKind, dimensions: usize) -> SimulationConfig {
+    SimulationConfig {
+        dimensions,
+        threads: 3,
+        ..common::config(engine, 3000, 9, Physics::default())
+    }
+}
+
+/// the bodies of the configuration, half of them squeezed into a corner so that many share the top bits of their keys.
+fn clustered(config: &SimulationConfig) -> Vec<SimpleBody> {
+    let mut bodies = initial::generate(config);
+    for b in bodies.iter_mut().step_by(2) {
+        b.x = 10.0 + b.x / 100.0;
+        b.y = 10.0 + b.y / 100.0;
+        b.z /= 100.0;
+    }
+    bodies
+}
+
+fn points<const D: usize>(bodies: &[SimpleBody]) -> Vec<Point<D>> {
+    bodies.iter().map(|b| Point::new(b.position::<D>(), b.m)).collect()
+}
+
+fn same_forces<const D: usize>(config: &SimulationConfig) {
+    let points = points::<D>(&clustered(config));
+    let space = Space::new(config);
+    let forces = |tree: &Tree<D>| -> Vec<(SVector<f64, D>, f64)> {
+        points.iter().map(|p| (tree.impact(p, 0.0), tree.potential(p))).collect()
+    };
+    let expected = forces(&Tree::new(&points, space));
+    assert_eq!(forces(&Tree::build(&points, space, 3, false)), expected);
+    assert_eq!(forces(&Tree::build(&points, space, 1, true)), expected);
+}
+
+#[test]
+fn parallel_builds_match_the_sequential_one() {
+    for expansion in [Expansion::Monopole, Expansion::Octupole].iter() {
+        let physics = Physics {
+            expansion: *expansion,
+            ..Physics::default()
+        };
+        same_forces::<2>(&SimulationConfig { physics, ..config(EngineKind::Tree, 2) });
+        same_forces::<3>(&SimulationConfig { physics, ..config(EngineKind::Tree, 3) });
+    }
+}
+
+#[test]
+fn tree_engines_agree_exactly() {
+    for dimensions in [2, 3].iter() {
+        let bodies = clustered(&config(EngineKind::Tree, *dimensions));
+        let accelerations: Vec<Vec<SimpleBody>> = [EngineKind::Tree, EngineKind::PThread, EngineKind::RayonTree]
+            .iter()
+            .map(|engine| {
+                let mut engine = new_engine(&config(*engine, *dimensions)).unwrap();
+                engine.init(&bodies);
+                engine.accelerate();
+                engine.bodies()
+            })
+            .collect();
+        assert!(accelerations[1] == accelerations[0] && accelerations[2] == accelerations[0]);
+    }
+}
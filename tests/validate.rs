@@ -0,0 +1,110 @@
+use nbody::config::{EngineKind, SimulationConfig};
+use nbody::global::Physics;
+use nbody::initial;
+use nbody::validate::{validate, Divergence};
+
+const DT: f64 = 0.001;
+
+fn config(engine: EngineKind, reference: EngineKind, position_tolerance: f64, velocity_tolerance: f64) -> SimulationConfig {
+    SimulationConfig {
+        engine,
+        reference,
+        size: 500,
+        steps: 20,
+        threads: 4,
+        position_tolerance,
+        velocity_tolerance,
+        ..SimulationConfig::default()
+    }
+}
+
+/// validates the engines of the configuration against each other from several seeds.
+fn check(config: SimulationConfig) {
+    for seed in 1..=3 {
+        let config = SimulationConfig { seed, ..config.clone() };
+        let bodies = initial::generate(&config);
+        let divergence = validate(&config, &bodies, DT).unwrap();
+        assert!(
+            divergence.within(&config),
+            "{} diverges from {} with seed {}: {}",
+            config.engine,
+            config.reference,
+            seed,
+            divergence
+        );
+    }
+}
+
+#[test]
+fn identical_bodies_do_not_diverge() {
+    let bodies = initial::generate(&SimulationConfig::default());
+    assert_eq!(Divergence::between(&bodies, &bodies), Divergence::default());
+}
+
+#[test]
+fn divergence_is_the_largest_difference() {
+    let a = initial::generate(&SimulationConfig::default());
+    let mut b = a.clone();
+    b[3].x += 3.0;
+    b[3].y += 4.0;
+    b[7].vy -= 2.0;
+    let divergence = Divergence::between(&a, &b);
+    assert_eq!(divergence.position, 5.0);
+    assert_eq!(divergence.velocity, 2.0);
+}
+
+#[test]
+fn nan_is_never_within_the_tolerances() {
+    let a = initial::generate(&SimulationConfig::default());
+    let mut b = a.clone();
+    b[0].vx = f64::NAN;
+    let config = SimulationConfig::default();
+    assert!(!Divergence::between(&a, &b).within(&config));
+}
+
+#[test]
+fn brute_force_matches_itself() {
+    check(config(EngineKind::BruteForce, EngineKind::BruteForce, 0.0, 0.0));
+}
+
+#[test]
+fn rayon_matches_brute_force() {
+    check(config(EngineKind::Rayon, EngineKind::BruteForce, 1e-9, 1e-9));
+}
+
+#[test]
+fn rayon_tree_matches_tree() {
+    // the parallel tree engines build the same tree and only split the force walks between the threads
+    check(config(EngineKind::RayonTree, EngineKind::Tree, 0.0, 0.0));
+}
+
+#[test]
+fn pthread_matches_tree() {
+    check(config(EngineKind::PThread, EngineKind::Tree, 0.0, 0.0));
+}
+
+#[cfg(feature = "openmp")]
+#[test]
+fn openmp_matches_brute_force() {
+    check(config(EngineKind::OpenMp, EngineKind::BruteForce, 1e-9, 1e-9));
+}
+
+#[cfg(feature = "mpi")]
+#[test]
+fn mpi_normal_matches_brute_force() {
+    check(config(EngineKind::MpiNormal, EngineKind::BruteForce, 1e-9, 1e-9));
+}
+
+#[test]
+fn fmm_matches_brute_force() {
+    // expansions of high order with a small opening angle are exact to rounding, almost
+    let config = SimulationConfig {
+        fmm_order: 12,
+        physics: Physics {
+            theta: 0.3,
+            ..Physics::default()
+        },
+        ..config(EngineKind::Fmm, EngineKind::BruteForce, 1e-6, 1e-6)
+    };
+    check(config);
+}